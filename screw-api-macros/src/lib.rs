@@ -0,0 +1,604 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Error, Fields, LitInt, LitStr,
+    Result,
+};
+
+const STATUS_CODES: &[(u16, &str)] = &[
+    (400, "BAD_REQUEST"),
+    (401, "UNAUTHORIZED"),
+    (402, "PAYMENT_REQUIRED"),
+    (403, "FORBIDDEN"),
+    (404, "NOT_FOUND"),
+    (405, "METHOD_NOT_ALLOWED"),
+    (406, "NOT_ACCEPTABLE"),
+    (407, "PROXY_AUTHENTICATION_REQUIRED"),
+    (408, "REQUEST_TIMEOUT"),
+    (409, "CONFLICT"),
+    (410, "GONE"),
+    (411, "LENGTH_REQUIRED"),
+    (412, "PRECONDITION_FAILED"),
+    (413, "PAYLOAD_TOO_LARGE"),
+    (414, "URI_TOO_LONG"),
+    (415, "UNSUPPORTED_MEDIA_TYPE"),
+    (416, "RANGE_NOT_SATISFIABLE"),
+    (417, "EXPECTATION_FAILED"),
+    (418, "IM_A_TEAPOT"),
+    (421, "MISDIRECTED_REQUEST"),
+    (422, "UNPROCESSABLE_ENTITY"),
+    (423, "LOCKED"),
+    (424, "FAILED_DEPENDENCY"),
+    (426, "UPGRADE_REQUIRED"),
+    (428, "PRECONDITION_REQUIRED"),
+    (429, "TOO_MANY_REQUESTS"),
+    (431, "REQUEST_HEADER_FIELDS_TOO_LARGE"),
+    (451, "UNAVAILABLE_FOR_LEGAL_REASONS"),
+    (500, "INTERNAL_SERVER_ERROR"),
+    (501, "NOT_IMPLEMENTED"),
+    (502, "BAD_GATEWAY"),
+    (503, "SERVICE_UNAVAILABLE"),
+    (504, "GATEWAY_TIMEOUT"),
+    (505, "HTTP_VERSION_NOT_SUPPORTED"),
+    (506, "VARIANT_ALSO_NEGOTIATES"),
+    (507, "INSUFFICIENT_STORAGE"),
+    (508, "LOOP_DETECTED"),
+    (510, "NOT_EXTENDED"),
+    (511, "NETWORK_AUTHENTICATION_REQUIRED"),
+];
+
+/// Derives `ApiResponseContentBase` and `ApiResponseContentFailure` for an enum of failures.
+///
+/// Every variant takes its status code from `#[status(404)]`, falling back to a `#[status]`
+/// of the enum itself, and may describe itself with `#[message("...")]`, responded as the
+/// reason. Messages refer to fields of the variant by name, or by position as `{0}` for tuple
+/// variants. The identifier is the variant name in snake case unless given by
+/// `#[identifier("...")]`.
+///
+/// ```ignore
+/// #[derive(ApiFailure)]
+/// #[status(400)]
+/// enum UserFailure {
+///     #[status(404)]
+///     #[message("user {0} not found")]
+///     NotFound(u64),
+///     #[message("name is {reason}")]
+///     InvalidName { reason: String },
+/// }
+/// ```
+#[proc_macro_derive(ApiFailure, attributes(status, message, identifier))]
+pub fn derive_api_failure(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_api_failure(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_api_failure(input: DeriveInput) -> Result<TokenStream2> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "ApiFailure can only be derived for enums",
+            ))
+        }
+    };
+    let default_status = status_attribute(&input.attrs)?;
+
+    let mut status_arms = Vec::new();
+    let mut identifier_arms = Vec::new();
+    let mut reason_arms = Vec::new();
+    for variant in &data.variants {
+        let variant_ident = &variant.ident;
+        let status = match status_attribute(&variant.attrs)? {
+            Some(status) => status,
+            None => default_status.clone().ok_or_else(|| {
+                Error::new_spanned(variant_ident, "missing #[status(...)] attribute")
+            })?,
+        };
+        let identifier = match string_attribute(&variant.attrs, "identifier")? {
+            Some(identifier) => identifier.value(),
+            None => snake_case(&variant_ident.to_string()),
+        };
+
+        let (pattern, bindings) = match &variant.fields {
+            Fields::Named(fields) => {
+                let names: Vec<_> = fields.named.iter().map(|f| f.ident.clone()).collect();
+                (quote!(Self::#variant_ident { #(#names),* }), true)
+            }
+            Fields::Unnamed(fields) => {
+                let names: Vec<_> = (0..fields.unnamed.len())
+                    .map(|index| format_ident!("_{}", index))
+                    .collect();
+                (quote!(Self::#variant_ident ( #(#names),* )), true)
+            }
+            Fields::Unit => (quote!(Self::#variant_ident), false),
+        };
+        let wildcard = match &variant.fields {
+            Fields::Named(_) => quote!(Self::#variant_ident { .. }),
+            Fields::Unnamed(_) => quote!(Self::#variant_ident(..)),
+            Fields::Unit => quote!(Self::#variant_ident),
+        };
+
+        status_arms.push(quote!(#wildcard => &::screw_api::__private::StatusCode::#status));
+        identifier_arms.push(quote!(#wildcard => #identifier));
+        reason_arms.push(match string_attribute(&variant.attrs, "message")? {
+            Some(message) => {
+                let message = LitStr::new(&positional_to_named(&message.value()), message.span());
+                let allow = bindings.then(|| quote!(#[allow(unused_variables)]));
+                quote!(#allow #pattern => ::std::option::Option::Some(::std::format!(#message)))
+            }
+            None => quote!(#wildcard => ::std::option::Option::None),
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::screw_api::response::ApiResponseContentBase for #ident #ty_generics
+        #where_clause
+        {
+            fn status_code(&self) -> &'static ::screw_api::__private::StatusCode {
+                match self {
+                    #(#status_arms,)*
+                }
+            }
+        }
+
+        impl #impl_generics ::screw_api::response::ApiResponseContentFailure for #ident #ty_generics
+        #where_clause
+        {
+            fn identifier(&self) -> &'static str {
+                match self {
+                    #(#identifier_arms,)*
+                }
+            }
+            fn reason(&self) -> ::std::option::Option<::std::string::String> {
+                match self {
+                    #(#reason_arms,)*
+                }
+            }
+        }
+    })
+}
+
+/// Derives `ApiRequestContent` for a struct assembled from every input of a request, each
+/// field marked with where it comes from:
+///
+/// - `#[api_request(path)]` deserializes the path parameters of the route, see
+///   `ApiRequestOriginContent::path_params`.
+/// - `#[api_request(query)]` deserializes the query string, see
+///   `ApiRequestOriginContent::query_params`.
+/// - `#[api_request(body)]` takes the data the converter read from the body, at most one field
+///   being the body, whose type becomes the `Data` of the content.
+///
+/// A request failing to provide a field is responded with the `ApiRejection` describing why,
+/// as `text/plain` by default, or by the `fn(ApiRejection) -> Response` given with
+/// `#[api_request(rejection = path::to::function)]` on the struct.
+///
+/// ```ignore
+/// #[derive(ApiRequestContent)]
+/// #[api_request(rejection = reject)]
+/// struct UpdateUser {
+///     #[api_request(path)]
+///     path: UserPath,
+///     #[api_request(query)]
+///     query: UpdateOptions,
+///     #[api_request(body)]
+///     user: UserData,
+/// }
+/// ```
+#[proc_macro_derive(ApiRequestContent, attributes(api_request))]
+pub fn derive_api_request_content(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_api_request_content(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+enum RequestSource {
+    Path,
+    Query,
+    Body,
+}
+
+fn expand_api_request_content(input: DeriveInput) -> Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "ApiRequestContent can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "ApiRequestContent can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut rejection = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("api_request"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rejection") {
+                rejection = Some(meta.value()?.parse::<syn::Path>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `rejection`"))
+            }
+        })?;
+    }
+    let rejection = match rejection {
+        Some(rejection) => quote!(#rejection),
+        None => quote!(::screw_api::__private::plain_rejection),
+    };
+
+    let mut data_type = None;
+    let mut field_values = Vec::new();
+    let mut body_value = None;
+    for field in fields {
+        let field_ident = field.ident.as_ref().unwrap();
+        let mut source = None;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("api_request"))
+        {
+            attr.parse_nested_meta(|meta| {
+                let field_source = if meta.path.is_ident("path") {
+                    RequestSource::Path
+                } else if meta.path.is_ident("query") {
+                    RequestSource::Query
+                } else if meta.path.is_ident("body") {
+                    RequestSource::Body
+                } else {
+                    return Err(meta.error("expected `path`, `query` or `body`"));
+                };
+                if source.replace(field_source).is_some() {
+                    return Err(meta.error("field already has a source"));
+                }
+                Ok(())
+            })?;
+        }
+        match source {
+            Some(RequestSource::Path) => field_values.push(quote! {
+                let #field_ident = origin_content.path_params().map_err(reject)?;
+            }),
+            Some(RequestSource::Query) => field_values.push(quote! {
+                let #field_ident = origin_content.query_params().map_err(reject)?;
+            }),
+            Some(RequestSource::Body) => {
+                if data_type.replace(&field.ty).is_some() {
+                    return Err(Error::new_spanned(
+                        field_ident,
+                        "only one field can be the body",
+                    ));
+                }
+                // Taken last, as it moves the data out of the origin content
+                body_value = Some(quote! {
+                    let #field_ident = origin_content.data_result.map_err(|error| {
+                        reject(::screw_api::rejection::ApiRejection::from_data_error(error))
+                    })?;
+                });
+            }
+            None => {
+                return Err(Error::new_spanned(
+                    field_ident,
+                    "missing #[api_request(path)], #[api_request(query)] or #[api_request(body)]",
+                ))
+            }
+        }
+    }
+    let field_idents = fields.iter().map(|field| &field.ident);
+    let data_type = match data_type {
+        Some(data_type) => quote!(#data_type),
+        None => quote!(()),
+    };
+
+    let ident = &input.ident;
+    let mut generics = input.generics.clone();
+    generics.params.push(parse_quote!(__Extensions));
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let (_, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::screw_api::request::ApiRequestContent<__Extensions> for #ident #ty_generics
+        #where_clause
+        {
+            type Data = #data_type;
+            fn create(
+                origin_content: ::screw_api::request::ApiRequestOriginContent<Self::Data, __Extensions>,
+            ) -> ::std::result::Result<Self, ::std::boxed::Box<::screw_api::__private::Response>> {
+                #[allow(unused_variables)]
+                let reject = |rejection: ::screw_api::rejection::ApiRejection| {
+                    ::std::boxed::Box::new(#rejection(rejection))
+                };
+                #(#field_values)*
+                #body_value
+                ::std::result::Result::Ok(Self { #(#field_idents),* })
+            }
+        }
+    })
+}
+
+/// Tags an enum following a single project-wide convention, so every enum of an API
+/// serializes alike without repeating serde attributes. Without arguments the enum is
+/// internally tagged by a `"type"` field, as `#[serde(tag = "type")]` does; `tag` names
+/// another field and `content` makes it adjacently tagged, the variant data going into that
+/// field. Serde tagging is chosen per type, so no converter setting can apply it to every
+/// enum; define the convention once, e.g. by always writing `#[api_enum(tag = "kind")]`.
+///
+/// The attribute has to come before `#[derive(Serialize, Deserialize)]`, and the enum must
+/// not choose a tagging of its own.
+///
+/// ```ignore
+/// #[api_enum(tag = "kind", content = "data")]
+/// #[derive(Serialize, Deserialize)]
+/// enum Event {
+///     Created { id: u64 },
+///     Deleted(u64),
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn api_enum(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut tag = LitStr::new("type", Span::call_site());
+    let mut content = None;
+    let args_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("tag") {
+            tag = meta.value()?.parse()?;
+            Ok(())
+        } else if meta.path.is_ident("content") {
+            content = Some(meta.value()?.parse::<LitStr>()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `tag` or `content`"))
+        }
+    });
+    parse_macro_input!(args with args_parser);
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_api_enum(input, tag, content)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_api_enum(
+    mut input: DeriveInput,
+    tag: LitStr,
+    content: Option<LitStr>,
+) -> Result<TokenStream2> {
+    if !matches!(input.data, Data::Enum(_)) {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "api_enum can only be applied to enums",
+        ));
+    }
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("serde"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag")
+                || meta.path.is_ident("content")
+                || meta.path.is_ident("untagged")
+            {
+                return Err(meta.error("api_enum already chooses the tagging of the enum"));
+            }
+            // Skips the value of other serde attributes such as `rename_all = "..."`, up to
+            // the comma before the next one
+            if meta.input.peek(syn::Token![=]) {
+                meta.value()?.parse::<syn::Expr>()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                meta.input.parse::<proc_macro2::Group>()?;
+            }
+            Ok(())
+        })?;
+    }
+    // Appended after the derives, which introduce the `serde` helper attribute
+    input.attrs.push(match content {
+        Some(content) => parse_quote!(#[serde(tag = #tag, content = #content)]),
+        None => parse_quote!(#[serde(tag = #tag)]),
+    });
+    Ok(quote!(#input))
+}
+
+fn status_attribute(attrs: &[Attribute]) -> Result<Option<proc_macro2::Ident>> {
+    let attr = match attrs.iter().find(|attr| attr.path().is_ident("status")) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+    let status: LitInt = attr.parse_args()?;
+    let code: u16 = status.base10_parse()?;
+    match STATUS_CODES.iter().find(|(c, _)| *c == code) {
+        Some((_, name)) => Ok(Some(proc_macro2::Ident::new(name, Span::call_site()))),
+        None => Err(Error::new_spanned(
+            status,
+            "expected a standard client or server error status code",
+        )),
+    }
+}
+
+fn string_attribute(attrs: &[Attribute], name: &str) -> Result<Option<LitStr>> {
+    attrs
+        .iter()
+        .find(|attr| attr.path().is_ident(name))
+        .map(|attr| attr.parse_args())
+        .transpose()
+}
+
+/// Rewrites positional arguments such as `{0}` or `{1:?}` to the `_0`, `_1` bindings of
+/// tuple variants, so every message is formatted by capturing bindings.
+fn positional_to_named(message: &str) -> String {
+    let mut result = String::with_capacity(message.len());
+    let mut chars = message.chars().peekable();
+    while let Some(c) = chars.next() {
+        result.push(c);
+        if c != '{' {
+            continue;
+        }
+        if chars.peek() == Some(&'{') {
+            result.push(chars.next().unwrap());
+            continue;
+        }
+        if chars.peek().is_some_and(char::is_ascii_digit) {
+            result.push('_');
+        }
+    }
+    result
+}
+
+fn snake_case(ident: &str) -> String {
+    let chars: Vec<char> = ident.chars().collect();
+    let mut result = String::with_capacity(ident.len() + 4);
+    for (index, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            let previous = chars[index - 1];
+            let next_is_lowercase = chars.get(index + 1).is_some_and(|c| c.is_lowercase());
+            if !previous.is_uppercase() || next_is_lowercase {
+                result.push('_');
+            }
+        }
+        result.extend(c.to_lowercase());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_enum_error(input: DeriveInput) -> Option<String> {
+        expand_api_enum(input, LitStr::new("type", Span::call_site()), None)
+            .err()
+            .map(|error| error.to_string())
+    }
+
+    #[test]
+    fn rejects_tagging_after_other_serde_attributes() {
+        let error = api_enum_error(parse_quote! {
+            #[serde(rename_all = "snake_case", tag = "x")]
+            enum Event {
+                Created,
+            }
+        });
+        assert_eq!(
+            error.as_deref(),
+            Some("api_enum already chooses the tagging of the enum")
+        );
+    }
+
+    #[test]
+    fn keeps_other_serde_attributes() {
+        let error = api_enum_error(parse_quote! {
+            #[serde(rename_all = "snake_case", deny_unknown_fields)]
+            enum Event {
+                Created,
+            }
+        });
+        assert_eq!(error, None);
+    }
+
+    fn api_failure_error(input: DeriveInput) -> Option<String> {
+        expand_api_failure(input)
+            .err()
+            .map(|error| error.to_string())
+    }
+
+    #[test]
+    fn rejects_api_failure_of_structs() {
+        let error = api_failure_error(parse_quote! {
+            #[status(400)]
+            struct Failure;
+        });
+        assert_eq!(
+            error.as_deref(),
+            Some("ApiFailure can only be derived for enums")
+        );
+    }
+
+    #[test]
+    fn rejects_variants_without_status() {
+        let error = api_failure_error(parse_quote! {
+            enum Failure {
+                #[status(404)]
+                NotFound,
+                Invalid,
+            }
+        });
+        assert_eq!(error.as_deref(), Some("missing #[status(...)] attribute"));
+    }
+
+    #[test]
+    fn rejects_non_error_status() {
+        for status in [quote!(200), quote!(399), quote!(600)] {
+            let error = api_failure_error(parse_quote! {
+                #[status(#status)]
+                enum Failure {
+                    Invalid,
+                }
+            });
+            assert_eq!(
+                error.as_deref(),
+                Some("expected a standard client or server error status code")
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_attributes() {
+        let inputs: [DeriveInput; 3] = [
+            parse_quote! {
+                #[status("400")]
+                enum Failure {
+                    Invalid,
+                }
+            },
+            parse_quote! {
+                #[status(400)]
+                enum Failure {
+                    #[message(invalid)]
+                    Invalid,
+                }
+            },
+            parse_quote! {
+                #[status(400)]
+                enum Failure {
+                    #[identifier]
+                    Invalid,
+                }
+            },
+        ];
+        for input in inputs {
+            assert!(api_failure_error(input).is_some());
+        }
+    }
+
+    #[test]
+    fn rewrites_positional_arguments() {
+        assert_eq!(
+            positional_to_named("user {0} not found"),
+            "user {_0} not found"
+        );
+        assert_eq!(positional_to_named("{1:?} after {0}"), "{_1:?} after {_0}");
+        assert_eq!(positional_to_named("{{0}} and {name}"), "{{0}} and {name}");
+        assert_eq!(positional_to_named("no arguments"), "no arguments");
+    }
+
+    #[test]
+    fn converts_to_snake_case() {
+        assert_eq!(snake_case("NotFound"), "not_found");
+        assert_eq!(snake_case("Invalid"), "invalid");
+        assert_eq!(snake_case("HTTPError"), "http_error");
+        assert_eq!(snake_case("InvalidURL"), "invalid_url");
+        assert_eq!(snake_case("Utf8Error"), "utf8_error");
+    }
+}
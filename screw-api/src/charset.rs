@@ -0,0 +1,147 @@
+use super::*;
+use hyper::body::Bytes;
+use hyper::header;
+use hyper::http::request::Parts;
+use screw_components::dyn_result::DResult;
+
+/// Charset of a request body, taken from the `charset` parameter of its content type.
+/// Bodies in charsets other than UTF-8 are transcoded with the `charset` feature enabled,
+/// without it only UTF-8 is supported.
+pub(super) struct RequestCharset {
+    #[cfg(feature = "charset")]
+    encoding: &'static encoding_rs::Encoding,
+}
+
+impl RequestCharset {
+    pub(super) fn from_parts(parts: &Parts) -> Result<Self, rejection::ApiRejection> {
+        let label = parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|header_value| header_value.to_str().ok())
+            .and_then(|content_type| {
+                content_type.split(';').skip(1).find_map(|parameter| {
+                    let (name, value) = parameter.split_once('=')?;
+                    name.trim()
+                        .eq_ignore_ascii_case("charset")
+                        .then(|| value.trim().trim_matches('"'))
+                })
+            });
+        match label {
+            None => Ok(Self::utf_8()),
+            Some(label) if label.eq_ignore_ascii_case("utf-8") => Ok(Self::utf_8()),
+            #[cfg(feature = "charset")]
+            Some(label) => encoding_rs::Encoding::for_label(label.as_bytes())
+                .map(|encoding| Self { encoding })
+                .ok_or_else(|| rejection::ApiRejection::CharsetUnsupported(label.to_owned())),
+            #[cfg(not(feature = "charset"))]
+            Some(label) => Err(rejection::ApiRejection::CharsetUnsupported(
+                label.to_owned(),
+            )),
+        }
+    }
+
+    fn utf_8() -> Self {
+        Self {
+            #[cfg(feature = "charset")]
+            encoding: encoding_rs::UTF_8,
+        }
+    }
+
+    /// Transcodes `bytes` to UTF-8, failing on byte sequences malformed in the charset.
+    pub(super) fn decode(&self, bytes: Bytes) -> DResult<Bytes> {
+        #[cfg(feature = "charset")]
+        if self.encoding != encoding_rs::UTF_8 {
+            return match self
+                .encoding
+                .decode_without_bom_handling_and_without_replacement(&bytes)
+            {
+                Some(text) => Ok(Bytes::from(text.into_owned())),
+                None => Err(format!(
+                    "request body is malformed in charset {}",
+                    self.encoding.name()
+                )
+                .into()),
+            };
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use response::ApiResponseContentBase;
+
+    fn charset(content_type: Option<&str>) -> Result<RequestCharset, rejection::ApiRejection> {
+        let mut http_request = hyper::Request::builder();
+        if let Some(content_type) = content_type {
+            http_request = http_request.header(header::CONTENT_TYPE, content_type);
+        }
+        RequestCharset::from_parts(&http_request.body(()).unwrap().into_parts().0)
+    }
+
+    #[test]
+    fn defaults_to_utf_8_without_charset() {
+        for content_type in [None, Some("application/json")] {
+            let bytes = charset(content_type)
+                .ok()
+                .unwrap()
+                .decode(Bytes::from("caf\u{e9}"))
+                .unwrap();
+            assert_eq!(bytes, "caf\u{e9}");
+        }
+    }
+
+    #[test]
+    fn accepts_quoted_utf_8() {
+        let charset = charset(Some("application/json; Charset=\"UTF-8\"")).ok();
+        assert!(charset.is_some());
+    }
+
+    #[test]
+    fn rejects_unknown_charsets_as_unsupported_media_type() {
+        let rejection = charset(Some("application/json; charset=klingon"))
+            .err()
+            .unwrap();
+        assert!(matches!(
+            &rejection,
+            rejection::ApiRejection::CharsetUnsupported(label) if label == "klingon"
+        ));
+        assert_eq!(
+            rejection.status_code(),
+            &hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+    }
+
+    #[cfg(feature = "charset")]
+    #[test]
+    fn transcodes_other_charsets() {
+        let bytes = charset(Some("application/json; charset=iso-8859-1"))
+            .ok()
+            .unwrap()
+            .decode(Bytes::from_static(b"caf\xe9"))
+            .unwrap();
+        assert_eq!(bytes, "caf\u{e9}");
+    }
+
+    #[cfg(feature = "charset")]
+    #[test]
+    fn fails_on_bytes_malformed_in_the_charset() {
+        let charset = charset(Some("application/json; charset=shift_jis"))
+            .ok()
+            .unwrap();
+        assert!(charset.decode(Bytes::from_static(b"\x81")).is_err());
+    }
+
+    #[cfg(not(feature = "charset"))]
+    #[test]
+    fn rejects_charsets_other_than_utf_8_without_the_charset_feature() {
+        let rejection = charset(Some("application/json; charset=iso-8859-1"))
+            .err()
+            .unwrap();
+        assert!(matches!(
+            rejection,
+            rejection::ApiRejection::CharsetUnsupported(_)
+        ));
+    }
+}
@@ -0,0 +1,236 @@
+use super::*;
+use hyper::body::Bytes;
+use hyper::http::request::Parts;
+use hyper::{header, Body, HeaderMap};
+use response::ApiResponseContentBase;
+use screw_components::dyn_fn::DFnOnce;
+use screw_components::dyn_result::DResult;
+use screw_core::request::Request;
+use screw_core::response::Response;
+use screw_core::routing::middleware::Middleware;
+use screw_core::routing::router::RoutedRequest;
+
+pub trait ApiBodyDecoder<Data> {
+    fn decode(&self, bytes: Bytes) -> DResult<Data>;
+}
+
+impl<F, Data> ApiBodyDecoder<Data> for F
+where
+    F: Fn(Bytes) -> DResult<Data>,
+{
+    fn decode(&self, bytes: Bytes) -> DResult<Data> {
+        self(bytes)
+    }
+}
+
+pub trait ApiBodyEncoder<Success, Failure>
+where
+    Success: response::ApiResponseContentSuccess,
+    Failure: response::ApiResponseContentFailure,
+{
+    fn encode(&self, content: &response::ApiResponseContent<Success, Failure>) -> DResult<Bytes>;
+}
+
+impl<F, Success, Failure> ApiBodyEncoder<Success, Failure> for F
+where
+    F: Fn(&response::ApiResponseContent<Success, Failure>) -> DResult<Bytes>,
+    Success: response::ApiResponseContentSuccess,
+    Failure: response::ApiResponseContentFailure,
+{
+    fn encode(&self, content: &response::ApiResponseContent<Success, Failure>) -> DResult<Bytes> {
+        self(content)
+    }
+}
+
+pub struct CodecApiMiddlewareConverter<Decoder, Encoder> {
+    content_type: &'static str,
+    decoder: Decoder,
+    encoder: Encoder,
+    /// Limits on request bodies of unknown length, see `ChunkedBodyLimits`. Unlimited by
+    /// default.
+    pub chunked_limits: request::ChunkedBodyLimits,
+    /// Whether `api request phases` events are emitted, as with
+    /// `JsonApiMiddlewareConfig::phase_timings`. Defaults to `false`.
+    pub phase_timings: bool,
+}
+
+impl<Decoder, Encoder> CodecApiMiddlewareConverter<Decoder, Encoder> {
+    pub fn with_codec(content_type: &'static str, decoder: Decoder, encoder: Encoder) -> Self {
+        Self {
+            content_type,
+            decoder,
+            encoder,
+            chunked_limits: request::ChunkedBodyLimits::default(),
+            phase_timings: false,
+        }
+    }
+
+    async fn convert<Data>(
+        &self,
+        parts: &Parts,
+        body: Body,
+        trailers: &mut Option<HeaderMap>,
+    ) -> DResult<Data>
+    where
+        Decoder: ApiBodyDecoder<Data>,
+    {
+        let content_type = match parts.headers.get(header::CONTENT_TYPE) {
+            Some(header_value) => Some(header_value.to_str()?),
+            None => None,
+        };
+        match content_type.map(media_type) {
+            Some("") | None => Err(rejection::ApiRejection::ContentTypeMissed),
+            Some(request_media_type)
+                if request_media_type.eq_ignore_ascii_case(media_type(self.content_type)) =>
+            {
+                Ok(())
+            }
+            Some(_) => Err(rejection::ApiRejection::ContentTypeIncorrect),
+        }?;
+        let (bytes, body_trailers) = self.chunked_limits.read(body).await?;
+        *trailers = body_trailers;
+        self.decoder.decode(bytes)
+    }
+
+    fn convert_response<Success, Failure>(
+        &self,
+        api_response: response::ApiResponse<Success, Failure>,
+    ) -> Response
+    where
+        Encoder: ApiBodyEncoder<Success, Failure>,
+        Success: response::ApiResponseContentSuccess,
+        Failure: response::ApiResponseContentFailure,
+    {
+        api_response.content.into_response(|content| {
+            let bytes = self.encoder.encode(content)?;
+            Ok((*content.status_code(), self.content_type, bytes))
+        })
+    }
+}
+
+/// Media type of `content_type`, without its parameters such as `charset`.
+fn media_type(content_type: &str) -> &str {
+    content_type
+        .split_once(';')
+        .map_or(content_type, |(media_type, _)| media_type)
+        .trim()
+}
+
+#[async_trait]
+impl<Decoder, Encoder, Data> request::ApiRequestConverter<Data>
+    for CodecApiMiddlewareConverter<Decoder, Encoder>
+where
+    Decoder: ApiBodyDecoder<Data> + Sync + Send,
+    Encoder: Sync + Send,
+    Data: Send + 'static,
+{
+    const NAME: &'static str = "codec";
+
+    fn phase_timings(&self) -> bool {
+        self.phase_timings
+    }
+
+    async fn convert_request(
+        &self,
+        http_parts: &Parts,
+        http_body: Body,
+    ) -> Result<(DResult<Data>, Option<HeaderMap>), rejection::ApiRejection> {
+        let mut trailers = None;
+        let data_result = self.convert(http_parts, http_body, &mut trailers).await;
+        Ok((data_result, trailers))
+    }
+}
+
+#[async_trait]
+impl<Decoder, Encoder, RqContent, Extensions, RsContentSuccess, RsContentFailure>
+    Middleware<
+        request::ApiRequest<RqContent, Extensions>,
+        response::ApiResponse<RsContentSuccess, RsContentFailure>,
+    > for CodecApiMiddlewareConverter<Decoder, Encoder>
+where
+    Decoder: ApiBodyDecoder<<RqContent as request::ApiRequestContent<Extensions>>::Data>
+        + Sync
+        + Send
+        + 'static,
+    Encoder: ApiBodyEncoder<RsContentSuccess, RsContentFailure> + Sync + Send + 'static,
+    RqContent: request::ApiRequestContent<Extensions> + Send + 'static,
+    <RqContent as request::ApiRequestContent<Extensions>>::Data: Sync + Send + 'static,
+    Extensions: Sync + Send + 'static,
+    RsContentSuccess: response::ApiResponseContentSuccess + Send + 'static,
+    RsContentFailure: response::ApiResponseContentFailure + Send + 'static,
+{
+    type Request = RoutedRequest<Request<Extensions>>;
+    type Response = Response;
+    async fn respond(
+        &self,
+        routed_request: RoutedRequest<Request<Extensions>>,
+        next: DFnOnce<
+            request::ApiRequest<RqContent, Extensions>,
+            response::ApiResponse<RsContentSuccess, RsContentFailure>,
+        >,
+    ) -> Response {
+        request::respond(self, routed_request, next, |api_response| {
+            self.convert_response(api_response)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn convert(content_type: Option<&str>) -> DResult<Bytes> {
+        let converter = CodecApiMiddlewareConverter::with_codec(
+            "application/x-msgpack",
+            |bytes: Bytes| -> DResult<Bytes> { Ok(bytes) },
+            (),
+        );
+        let mut http_request = hyper::Request::builder();
+        if let Some(content_type) = content_type {
+            http_request = http_request.header(header::CONTENT_TYPE, content_type);
+        }
+        let parts = http_request.body(()).unwrap().into_parts().0;
+        converter
+            .convert(&parts, Body::from("data"), &mut None)
+            .await
+    }
+
+    fn rejection(result: DResult<Bytes>) -> Option<rejection::ApiRejection> {
+        result
+            .err()
+            .and_then(|error| error.downcast::<rejection::ApiRejection>().ok())
+            .map(|rejection| *rejection)
+    }
+
+    #[tokio::test]
+    async fn accepts_the_media_type_with_parameters() {
+        for content_type in [
+            "application/x-msgpack",
+            "Application/X-MsgPack",
+            " application/x-msgpack ; charset=binary",
+        ] {
+            assert_eq!(convert(Some(content_type)).await.unwrap(), "data");
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_other_media_types() {
+        assert!(matches!(
+            rejection(convert(Some("application/json")).await),
+            Some(rejection::ApiRejection::ContentTypeIncorrect)
+        ));
+        assert!(matches!(
+            rejection(convert(Some("application/x-msgpack-extended")).await),
+            Some(rejection::ApiRejection::ContentTypeIncorrect)
+        ));
+        assert!(matches!(
+            rejection(convert(Some(" ; charset=utf-8")).await),
+            Some(rejection::ApiRejection::ContentTypeMissed)
+        ));
+        assert!(matches!(
+            rejection(convert(None).await),
+            Some(rejection::ApiRejection::ContentTypeMissed)
+        ));
+    }
+}
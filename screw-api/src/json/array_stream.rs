@@ -0,0 +1,79 @@
+use super::super::*;
+use super::JsonApiMiddlewareConverter;
+use futures::stream::{self, Stream, StreamExt};
+use hyper::body::Bytes;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::StatusCode;
+use response::ApiResponseRaw;
+use screw_components::dyn_result::{DError, DResult};
+use screw_core::response::ResponseBody;
+use serde::Serialize;
+use std::future::ready;
+
+impl JsonApiMiddlewareConverter {
+    /// Responds the items of `stream` as a JSON array, written as they arrive without
+    /// collecting them, so the body stays valid JSON for clients parsing it as a whole, unlike
+    /// newline-delimited JSON. An empty stream is responded as `[]`. Items are serialized with
+    /// `pretty_printed` and `rename_case` applied. When an item fails to serialize, the failure
+    /// is logged and the body is cut short, aborting the response.
+    ///
+    /// Returned from an API handler with `ApiResponse::raw`, or responded by the plain router
+    /// with `ApiResponseRaw::into_response`.
+    pub fn array_stream<S>(&self, stream: S) -> ApiResponseRaw
+    where
+        S: Stream + Send + 'static,
+        S::Item: Serialize,
+    {
+        let converter = *self;
+        let items = stream.enumerate().map(move |(index, item)| {
+            let mut json_bytes = if index == 0 { Vec::new() } else { vec![b','] };
+            match converter.serialize_item(&item) {
+                Ok(item_bytes) => {
+                    json_bytes.extend(item_bytes);
+                    Ok(Bytes::from(json_bytes))
+                }
+                Err(error) => {
+                    tracing::error!(
+                        target: "screw::api",
+                        converter = "json",
+                        %error,
+                        "failed to serialize JSON array item"
+                    );
+                    Err(error)
+                }
+            }
+        });
+        let body = stream::once(ready(Ok::<_, DError>(Bytes::from_static(b"["))))
+            .chain(items)
+            .chain(stream::once(ready(Ok(Bytes::from_static(b"]")))))
+            .scan(false, |failed, chunk| {
+                if *failed {
+                    return ready(None);
+                }
+                *failed = chunk.is_err();
+                ready(Some(chunk))
+            });
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        ApiResponseRaw {
+            status_code: &StatusCode::OK,
+            headers,
+            body: ResponseBody::wrap_stream(body),
+        }
+    }
+
+    fn serialize_item<T: Serialize>(&self, item: &T) -> DResult<Vec<u8>> {
+        let json_bytes = match self.config.rename_case {
+            Some(rename_case) => {
+                let value = rename_case.rename_outgoing(serde_json::to_value(item)?);
+                self.serialize_value(&value)?
+            }
+            None => self.serialize_value(item)?,
+        };
+        Ok(json_bytes)
+    }
+}
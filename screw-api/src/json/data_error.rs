@@ -0,0 +1,88 @@
+use super::super::*;
+use hyper::StatusCode;
+use response::{ApiResponseContentBase, ApiResponseContentFailure};
+use std::error::Error;
+use std::fmt;
+
+/// Characters of the offending line kept on each side of the error column in the snippet.
+const SNIPPET_RADIUS: usize = 20;
+
+/// Request data failing to parse as JSON, given to `ApiRequestContent::create` in place of the
+/// `serde_json::Error` when `JsonApiMiddlewareConfig::data_error_position` is set, so a
+/// failure can tell the client where its body is malformed.
+#[derive(Debug)]
+pub struct JsonDataError {
+    error: serde_json::Error,
+    snippet: Option<String>,
+}
+
+impl JsonDataError {
+    pub(super) fn with_input(error: serde_json::Error, json_bytes: &[u8]) -> Self {
+        let snippet = snippet(json_bytes, error.line(), error.column());
+        Self { error, snippet }
+    }
+
+    /// One-based line of the error, `0` when the error has no position in the input.
+    pub fn line(&self) -> usize {
+        self.error.line()
+    }
+
+    /// One-based column of the error, `0` when the error has no position in the input.
+    pub fn column(&self) -> usize {
+        self.error.column()
+    }
+
+    /// Input around the error position, at most 20 characters on each side of it.
+    pub fn snippet(&self) -> Option<&str> {
+        self.snippet.as_deref()
+    }
+
+    pub fn error(&self) -> &serde_json::Error {
+        &self.error
+    }
+}
+
+fn snippet(json_bytes: &[u8], line: usize, column: usize) -> Option<String> {
+    if line == 0 {
+        return None;
+    }
+    let line_bytes = json_bytes.split(|byte| *byte == b'\n').nth(line - 1)?;
+    let line = String::from_utf8_lossy(line_bytes);
+    let chars: Vec<char> = line.trim_end_matches('\r').chars().collect();
+    let position = column.saturating_sub(1).min(chars.len());
+    let start = position.saturating_sub(SNIPPET_RADIUS);
+    let end = (position + SNIPPET_RADIUS).min(chars.len());
+    let snippet: String = chars[start..end].iter().collect();
+    (!snippet.trim().is_empty()).then_some(snippet)
+}
+
+impl fmt::Display for JsonDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request data is invalid: {}", self.error)?;
+        if let Some(snippet) = &self.snippet {
+            write!(f, " near `{}`", snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for JsonDataError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl ApiResponseContentBase for JsonDataError {
+    fn status_code(&self) -> &'static StatusCode {
+        &StatusCode::BAD_REQUEST
+    }
+}
+
+impl ApiResponseContentFailure for JsonDataError {
+    fn identifier(&self) -> &'static str {
+        "request_data_invalid"
+    }
+    fn reason(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
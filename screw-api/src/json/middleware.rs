@@ -1,18 +1,574 @@
 use super::super::*;
+use super::{JsonDataError, JsonRenameCase};
+use hyper::body::Bytes;
 use hyper::http::request::Parts;
-use hyper::{header, Body, StatusCode};
-use response::ApiResponseContentBase;
+use hyper::{header, Body, HeaderMap, Method, StatusCode};
+use problem::ApiProblem;
+use response::{ApiResponseContentBase, ApiResponseContentFailure, ApiResponseConverter};
 use screw_components::dyn_fn::DFnOnce;
-use screw_components::dyn_result::DResult;
+use screw_components::dyn_result::{DError, DResult};
 use screw_core::request::Request;
-use screw_core::response::Response;
+use screw_core::response::{Response, ResponseBody};
 use screw_core::routing::middleware::Middleware;
 use screw_core::routing::router::RoutedRequest;
-use serde::Deserialize;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Map, Value};
 
+/// Envelope `JsonApiMiddlewareConfig::envelope` wraps response bodies in.
 #[derive(Clone, Copy, Debug)]
-pub struct JsonApiMiddlewareConverter {
+pub struct JsonEnvelope {
+    /// Key the data of successes is responded under, `"data"` by default.
+    pub data_key: &'static str,
+    /// Key failures are responded under, `"error"` by default.
+    pub error_key: &'static str,
+}
+
+impl Default for JsonEnvelope {
+    fn default() -> Self {
+        Self {
+            data_key: "data",
+            error_key: "error",
+        }
+    }
+}
+
+impl JsonEnvelope {
+    fn wrap<Success, Failure>(
+        &self,
+        content: &response::ApiResponseContent<Success, Failure>,
+    ) -> DResult<Value>
+    where
+        Success: response::ApiResponseContentSuccess,
+        Success::Data: Serialize,
+        Failure: response::ApiResponseContentFailure,
+    {
+        match content {
+            response::ApiResponseContent::Success(success) => {
+                Ok(self.wrap_data(serde_json::to_value(success.data())?))
+            }
+            response::ApiResponseContent::Failure(failure) => Ok(self.wrap_error(json!({
+                "identifier": failure.identifier(),
+                "reason": failure.reason(),
+            }))),
+            response::ApiResponseContent::Raw(_) => {
+                Err("raw api response content can not be serialized".into())
+            }
+        }
+    }
+
+    fn wrap_data(&self, data: Value) -> Value {
+        Value::Object(Map::from_iter([(self.data_key.to_owned(), data)]))
+    }
+
+    fn wrap_error(&self, error: Value) -> Value {
+        Value::Object(Map::from_iter([(self.error_key.to_owned(), error)]))
+    }
+}
+
+/// Options of `JsonApiMiddlewareConverter`, built from `default()` with the `and_*` methods.
+#[derive(Clone, Copy, Debug, Default)]
+#[non_exhaustive]
+pub struct JsonApiMiddlewareConfig {
+    /// Whether responses are indented. Defaults to `false`.
     pub pretty_printed: bool,
+    /// Case object keys are renamed to on the wire, see `JsonRenameCase`. Defaults to `None`.
+    pub rename_case: Option<JsonRenameCase>,
+    /// Whether an empty body, with or without a content type, is deserialized as `null`
+    /// (falling back to `{}`) instead of failing. This yields `None` for `Option` data and
+    /// defaults for `#[serde(default)]` structs. Defaults to `false`.
+    pub allow_empty_body: bool,
+    /// Whether anything but whitespace after the JSON document, such as concatenated documents,
+    /// is rejected with `400 Bad Request` before the handler is called. When `false`, trailing
+    /// data only makes the request data fail to deserialize. Defaults to `false`.
+    pub reject_trailing_data: bool,
+    /// Responds rejections made before the handler is called in place of the default
+    /// `{"failure": {...}}` JSON body. Defaults to `None`.
+    pub rejection_response: Option<fn(rejection::ApiRejection) -> Response>,
+    /// Whether data of `GET`, `HEAD` and `DELETE` requests is deserialized from the query
+    /// string instead of the body. Not applied by `JsonSchemaApiMiddlewareConverter`, which
+    /// always validates the body. Defaults to `false`.
+    pub query_for_bodyless_methods: bool,
+    /// Media types accepted besides `application/json`, such as `application/vnd.myapi+json`.
+    /// Compared case-insensitively, with parameters like `charset` ignored. Only media types
+    /// with the `+json` suffix are accepted, others such as `application/vnd.myapi+xml` being
+    /// rejected like any non-JSON media type. Defaults to empty.
+    pub accepted_media_types: &'static [&'static str],
+    /// Whether failures without a problem of their own, rejections included, are responded
+    /// as `application/problem+json` documents built by `ApiProblem::from_failure`. Failures
+    /// returning a problem are responded so regardless. Defaults to `false`.
+    pub problem_details: bool,
+    /// Envelope successes and failures, rejections included, are responded in, such as
+    /// `{"data": ...}` and `{"error": {...}}`, instead of the default
+    /// `{"success": {...}}` and `{"failure": {...}}` bodies. Successes only carry their data.
+    /// Problem details documents are responded as is. Defaults to `None`.
+    pub envelope: Option<JsonEnvelope>,
+    /// Whether a body failing to parse as JSON gives `ApiRequestContent::create` a
+    /// `JsonDataError`, carrying the line, the column and a snippet of the input around them,
+    /// in place of the bare `serde_json::Error`. The snippet echoes what the client sent, so
+    /// its failure reason should only be responded to clients trusted with it. Defaults to
+    /// `false`.
+    pub data_error_position: bool,
+    /// Limits on request bodies of unknown length, see `ChunkedBodyLimits`. Unlimited by
+    /// default.
+    pub chunked_limits: request::ChunkedBodyLimits,
+    /// Whether request data with fields its type does not have, such as a misspelled optional
+    /// field, is rejected with `400 Bad Request` naming them before the handler is called,
+    /// as `#[serde(deny_unknown_fields)]` would for every type. Fields are named after
+    /// `rename_case` applies, and the query string of `query_for_bodyless_methods` is not
+    /// checked. Defaults to `false`.
+    pub deny_unknown_fields: bool,
+    /// Whether the durations of converting the request, running the handler and serializing
+    /// the response are emitted as an `api request phases` event. Defaults to `false`.
+    pub phase_timings: bool,
+}
+
+impl JsonApiMiddlewareConfig {
+    pub fn and_pretty_printed(mut self, pretty_printed: bool) -> Self {
+        self.pretty_printed = pretty_printed;
+        self
+    }
+
+    pub fn and_rename_case(mut self, rename_case: Option<JsonRenameCase>) -> Self {
+        self.rename_case = rename_case;
+        self
+    }
+
+    pub fn and_allow_empty_body(mut self, allow_empty_body: bool) -> Self {
+        self.allow_empty_body = allow_empty_body;
+        self
+    }
+
+    pub fn and_reject_trailing_data(mut self, reject_trailing_data: bool) -> Self {
+        self.reject_trailing_data = reject_trailing_data;
+        self
+    }
+
+    pub fn and_rejection_response(
+        mut self,
+        rejection_response: Option<fn(rejection::ApiRejection) -> Response>,
+    ) -> Self {
+        self.rejection_response = rejection_response;
+        self
+    }
+
+    pub fn and_query_for_bodyless_methods(mut self, query_for_bodyless_methods: bool) -> Self {
+        self.query_for_bodyless_methods = query_for_bodyless_methods;
+        self
+    }
+
+    pub fn and_accepted_media_types(
+        mut self,
+        accepted_media_types: &'static [&'static str],
+    ) -> Self {
+        self.accepted_media_types = accepted_media_types;
+        self
+    }
+
+    pub fn and_problem_details(mut self, problem_details: bool) -> Self {
+        self.problem_details = problem_details;
+        self
+    }
+
+    pub fn and_envelope(mut self, envelope: Option<JsonEnvelope>) -> Self {
+        self.envelope = envelope;
+        self
+    }
+
+    pub fn and_data_error_position(mut self, data_error_position: bool) -> Self {
+        self.data_error_position = data_error_position;
+        self
+    }
+
+    pub fn and_chunked_limits(mut self, chunked_limits: request::ChunkedBodyLimits) -> Self {
+        self.chunked_limits = chunked_limits;
+        self
+    }
+
+    pub fn and_deny_unknown_fields(mut self, deny_unknown_fields: bool) -> Self {
+        self.deny_unknown_fields = deny_unknown_fields;
+        self
+    }
+
+    pub fn and_phase_timings(mut self, phase_timings: bool) -> Self {
+        self.phase_timings = phase_timings;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonApiMiddlewareConverter {
+    pub(super) config: JsonApiMiddlewareConfig,
+}
+
+impl JsonApiMiddlewareConverter {
+    pub fn with_config(config: JsonApiMiddlewareConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &JsonApiMiddlewareConfig {
+        &self.config
+    }
+}
+
+impl JsonApiMiddlewareConverter {
+    pub fn serialize<Success, Failure>(
+        &self,
+        content: &response::ApiResponseContent<Success, Failure>,
+    ) -> DResult<(StatusCode, Bytes)>
+    where
+        Success: response::ApiResponseContentSuccess,
+        Success::Data: Serialize,
+        Failure: response::ApiResponseContentFailure,
+    {
+        let status_code = *content.status_code();
+        let json_bytes = match (self.config.envelope, self.config.rename_case) {
+            (None, None) => self.serialize_value(content)?,
+            (envelope, rename_case) => {
+                let value = match envelope {
+                    Some(envelope) => envelope.wrap(content)?,
+                    None => serde_json::to_value(content)?,
+                };
+                let value = match rename_case {
+                    Some(rename_case) => rename_case.rename_outgoing(value),
+                    None => value,
+                };
+                self.serialize_value(&value)?
+            }
+        };
+        Ok((status_code, Bytes::from(json_bytes)))
+    }
+
+    pub(super) fn serialize_value<T: Serialize + ?Sized>(
+        &self,
+        value: &T,
+    ) -> serde_json::Result<Vec<u8>> {
+        if self.config.pretty_printed {
+            serde_json::to_vec_pretty(value)
+        } else {
+            serde_json::to_vec(value)
+        }
+    }
+
+    pub(super) async fn read_body(
+        &self,
+        parts: &Parts,
+        body: Body,
+    ) -> DResult<(Bytes, Option<HeaderMap>)> {
+        let content_type = match parts.headers.get(header::CONTENT_TYPE) {
+            Some(header_value) => Some(header_value.to_str()?),
+            None => None,
+        };
+        let media_type = content_type.map(|content_type| {
+            content_type
+                .split_once(';')
+                .map_or(content_type, |(media_type, _)| media_type)
+                .trim()
+        });
+        let content_type_result = match media_type {
+            Some("") | None => Err(rejection::ApiRejection::ContentTypeMissed),
+            Some(media_type) if self.is_accepted_media_type(media_type) => Ok(()),
+            Some(_) => Err(rejection::ApiRejection::ContentTypeIncorrect),
+        };
+        match content_type_result {
+            Err(rejection::ApiRejection::ContentTypeMissed) if self.config.allow_empty_body => {
+                let (json_bytes, trailers) = self.config.chunked_limits.read(body).await?;
+                if json_bytes.is_empty() {
+                    Ok((json_bytes, trailers))
+                } else {
+                    Err(rejection::ApiRejection::ContentTypeMissed.into())
+                }
+            }
+            content_type_result => {
+                content_type_result?;
+                self.config.chunked_limits.read(body).await
+            }
+        }
+    }
+
+    fn is_accepted_media_type(&self, media_type: &str) -> bool {
+        media_type.eq_ignore_ascii_case("application/json")
+            || self
+                .config
+                .accepted_media_types
+                .iter()
+                .any(|accepted_media_type| {
+                    media_type.eq_ignore_ascii_case(accepted_media_type)
+                        && media_type
+                            .get(media_type.len().saturating_sub(5)..)
+                            .is_some_and(|suffix| suffix.eq_ignore_ascii_case("+json"))
+                })
+    }
+
+    pub(super) fn deserialize_empty<Data>() -> DResult<Data>
+    where
+        for<'de> Data: Deserialize<'de>,
+    {
+        let data = serde_json::from_value(Value::Null)
+            .or_else(|_| serde_json::from_value(Value::Object(Default::default())))?;
+        Ok(data)
+    }
+
+    /// Deserializes `value`, along with the paths of its unknown fields when
+    /// `deny_unknown_fields` is set.
+    pub(super) fn deserialize_value<Data>(&self, value: Value) -> DResult<(Data, Vec<String>)>
+    where
+        for<'de> Data: Deserialize<'de>,
+    {
+        let value = match self.config.rename_case {
+            Some(rename_case) => rename_case.rename_incoming(value),
+            None => value,
+        };
+        Ok(self.deserialize_tracked(value)?)
+    }
+
+    fn deserialize_tracked<'de, D, Data>(
+        &self,
+        deserializer: D,
+    ) -> Result<(Data, Vec<String>), D::Error>
+    where
+        D: Deserializer<'de>,
+        Data: Deserialize<'de>,
+    {
+        if !self.config.deny_unknown_fields {
+            return Data::deserialize(deserializer).map(|data| (data, Vec::new()));
+        }
+        let mut unknown_fields = Vec::new();
+        let data = serde_ignored::deserialize(deserializer, |path| {
+            unknown_fields.push(field_path(&path))
+        })?;
+        Ok((data, unknown_fields))
+    }
+
+    pub(super) fn reject_trailing_data(
+        &self,
+        json_bytes: &[u8],
+    ) -> Result<(), rejection::ApiRejection> {
+        if !self.config.reject_trailing_data {
+            return Ok(());
+        }
+        let mut stream = serde_json::Deserializer::from_slice(json_bytes).into_iter::<IgnoredAny>();
+        match stream.next() {
+            Some(Ok(_)) => {}
+            _ => return Ok(()),
+        }
+        let trailing_data = &json_bytes[stream.byte_offset()..];
+        if trailing_data.iter().all(u8::is_ascii_whitespace) {
+            return Ok(());
+        }
+        Err(rejection::ApiRejection::TrailingData)
+    }
+
+    pub(super) fn reject(&self, rejection: rejection::ApiRejection) -> Response {
+        let errors = match &rejection {
+            rejection::ApiRejection::SchemaViolation(violations) => Some(json!(violations)),
+            _ => None,
+        };
+        let (content, content_type) = match self.problem(&rejection) {
+            Some(problem) => {
+                let mut problem = json!(problem);
+                if let Some(errors) = errors {
+                    problem["errors"] = errors;
+                }
+                (problem, "application/problem+json")
+            }
+            None => {
+                let mut failure = json!({
+                    "identifier": rejection.identifier(),
+                    "reason": rejection.reason(),
+                });
+                if let Some(errors) = errors {
+                    failure["errors"] = errors;
+                }
+                let content = match self.config.envelope {
+                    Some(envelope) => envelope.wrap_error(failure),
+                    None => json!({ "failure": failure }),
+                };
+                (content, "application/json")
+            }
+        };
+        let status_code = *rejection.status_code();
+
+        let http_response = self
+            .serialize_value(&content)
+            .map_err(DError::from)
+            .and_then(|json_bytes| {
+                Ok(hyper::Response::builder()
+                    .status(status_code)
+                    .header(header::CONTENT_TYPE, content_type)
+                    .body(ResponseBody::from(json_bytes))?)
+            })
+            .unwrap_or_else(|_| {
+                hyper::Response::builder()
+                    .status(status_code)
+                    .body(ResponseBody::empty())
+                    .unwrap()
+            });
+        Response {
+            http: http_response,
+        }
+    }
+
+    fn problem<Failure: ApiResponseContentFailure>(&self, failure: &Failure) -> Option<ApiProblem> {
+        failure.problem().or_else(|| {
+            self.config
+                .problem_details
+                .then(|| ApiProblem::from_failure(failure))
+        })
+    }
+
+    fn is_query_source(&self, parts: &Parts) -> bool {
+        self.config.query_for_bodyless_methods
+            && matches!(parts.method, Method::GET | Method::HEAD | Method::DELETE)
+    }
+
+    fn deserialize_query<Data>(&self, query: &str) -> DResult<Data>
+    where
+        for<'de> Data: Deserialize<'de>,
+    {
+        let data = match self.config.rename_case {
+            Some(rename_case) => {
+                let pairs: Vec<(String, String)> = serde_urlencoded::from_str(query)?;
+                let renamed_pairs: Vec<(String, String)> = pairs
+                    .into_iter()
+                    .map(|(key, value)| (rename_case.rename_to_snake_case(&key), value))
+                    .collect();
+                serde_urlencoded::from_str(&serde_urlencoded::to_string(renamed_pairs)?)?
+            }
+            None => serde_urlencoded::from_str(query)?,
+        };
+        Ok(data)
+    }
+
+    fn convert<Data>(&self, json_bytes: &[u8]) -> DResult<(Data, Vec<String>)>
+    where
+        for<'de> Data: Deserialize<'de>,
+    {
+        if self.config.allow_empty_body && json_bytes.is_empty() {
+            return Ok((Self::deserialize_empty()?, Vec::new()));
+        }
+        match self.config.rename_case {
+            Some(_) => {
+                let value = serde_json::from_slice(json_bytes)
+                    .map_err(|error| self.data_error(error, json_bytes))?;
+                self.deserialize_value(value)
+            }
+            None => {
+                let mut deserializer = serde_json::Deserializer::from_slice(json_bytes);
+                let data = self
+                    .deserialize_tracked(&mut deserializer)
+                    .and_then(|data| deserializer.end().map(|()| data))
+                    .map_err(|error| self.data_error(error, json_bytes))?;
+                Ok(data)
+            }
+        }
+    }
+
+    pub(super) fn data_error(&self, error: serde_json::Error, json_bytes: &[u8]) -> DError {
+        if self.config.data_error_position {
+            JsonDataError::with_input(error, json_bytes).into()
+        } else {
+            error.into()
+        }
+    }
+}
+
+/// Joins the keys and indices of `path` with dots, such as `items.0.nmae`, leaving out the
+/// `?` serde_ignored writes for options and newtypes.
+fn field_path(path: &serde_ignored::Path) -> String {
+    let (parent, segment) = match path {
+        serde_ignored::Path::Root => return String::new(),
+        serde_ignored::Path::Seq { parent, index } => (parent, index.to_string()),
+        serde_ignored::Path::Map { parent, key } => (parent, key.clone()),
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => return field_path(parent),
+    };
+    match field_path(parent) {
+        parent if parent.is_empty() => segment,
+        parent => format!("{}.{}", parent, segment),
+    }
+}
+
+impl ApiResponseConverter for JsonApiMiddlewareConverter {
+    fn convert_response<Success, Failure>(
+        &self,
+        api_response: response::ApiResponse<Success, Failure>,
+    ) -> Response
+    where
+        Success: response::ApiResponseContentSuccess,
+        Success::Data: Serialize,
+        Failure: response::ApiResponseContentFailure,
+    {
+        api_response.content.into_response(|content| {
+            let problem = match content {
+                response::ApiResponseContent::Failure(failure) => self.problem(failure),
+                _ => None,
+            };
+            match problem {
+                Some(problem) => Ok((
+                    *problem.status_code,
+                    "application/problem+json",
+                    Bytes::from(self.serialize_value(&problem)?),
+                )),
+                None => {
+                    let (status_code, json_bytes) = self.serialize(content)?;
+                    Ok((status_code, "application/json", json_bytes))
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl<Data> request::ApiRequestConverter<Data> for JsonApiMiddlewareConverter
+where
+    for<'de> Data: Deserialize<'de> + Send + 'static,
+{
+    const NAME: &'static str = "json";
+
+    fn phase_timings(&self) -> bool {
+        self.config.phase_timings
+    }
+
+    fn rejection_response(&self) -> Option<fn(rejection::ApiRejection) -> Response> {
+        self.config.rejection_response
+    }
+
+    fn respond_rejection(&self, rejection: rejection::ApiRejection) -> Response {
+        self.reject(rejection)
+    }
+
+    async fn convert_request(
+        &self,
+        http_parts: &Parts,
+        http_body: Body,
+    ) -> Result<(DResult<Data>, Option<HeaderMap>), rejection::ApiRejection> {
+        if self.is_query_source(http_parts) {
+            let data_result = self.deserialize_query(http_parts.uri.query().unwrap_or_default());
+            return Ok((data_result, None));
+        }
+        let charset = charset::RequestCharset::from_parts(http_parts)?;
+        let mut trailers = None;
+        let data_result = match self.read_body(http_parts, http_body).await.and_then(
+            |(json_bytes, body_trailers)| {
+                trailers = body_trailers;
+                charset.decode(json_bytes)
+            },
+        ) {
+            Ok(json_bytes) => {
+                self.reject_trailing_data(&json_bytes)?;
+                match self.convert(&json_bytes) {
+                    Ok((_, unknown_fields)) if !unknown_fields.is_empty() => {
+                        return Err(rejection::ApiRejection::UnknownFields(unknown_fields));
+                    }
+                    data_result => data_result.map(|(data, _)| data),
+                }
+            }
+            Err(error) => Err(error),
+        };
+        Ok((data_result, trailers))
+    }
 }
 
 #[async_trait]
@@ -23,9 +579,11 @@ impl<RqContent, Extensions, RsContentSuccess, RsContentFailure>
     > for JsonApiMiddlewareConverter
 where
     RqContent: request::ApiRequestContent<Extensions> + Send + 'static,
-    <RqContent as request::ApiRequestContent<Extensions>>::Data: Sync + Send + 'static,
+    <RqContent as request::ApiRequestContent<Extensions>>::Data:
+        for<'de> Deserialize<'de> + Sync + Send + 'static,
     Extensions: Sync + Send + 'static,
     RsContentSuccess: response::ApiResponseContentSuccess + Send + 'static,
+    <RsContentSuccess as response::ApiResponseContentSuccess>::Data: Serialize,
     RsContentFailure: response::ApiResponseContentFailure + Send + 'static,
 {
     type Request = RoutedRequest<Request<Extensions>>;
@@ -38,70 +596,90 @@ where
             response::ApiResponse<RsContentSuccess, RsContentFailure>,
         >,
     ) -> Response {
-        async fn convert<Data>(parts: &Parts, body: Body) -> DResult<Data>
-        where
-            for<'de> Data: Deserialize<'de>,
-        {
-            let content_type = match parts.headers.get(header::CONTENT_TYPE) {
-                Some(header_value) => Some(header_value.to_str()?),
-                None => None,
-            };
-            match content_type {
-                Some("application/json") => Ok(()),
-                Some("") | None => Err(ApiRequestContentTypeError::Missed),
-                Some(_) => Err(ApiRequestContentTypeError::Incorrect),
-            }?;
-            let json_bytes = hyper::body::to_bytes(body).await?;
-            let data = serde_json::from_slice(&json_bytes)?;
-            Ok(data)
-        }
-
-        let (http_parts, http_body) = routed_request.origin.http.into_parts();
-        let data_result = convert(&http_parts, http_body).await;
-
-        let request_content = RqContent::create(request::ApiRequestOriginContent {
-            path: routed_request.path,
-            query: routed_request.query,
-            http_parts,
-            remote_addr: routed_request.origin.remote_addr,
-            extensions: routed_request.origin.extensions,
-            data_result,
-        });
+        request::respond(self, routed_request, next, |api_response| {
+            self.convert_response(api_response)
+        })
+        .await
+    }
+}
 
-        let api_request = request::ApiRequest {
-            content: request_content,
-            _p_e: Default::default(),
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read_body_with_content_type(
+        converter: &JsonApiMiddlewareConverter,
+        content_type: &str,
+    ) -> DResult<(Bytes, Option<HeaderMap>)> {
+        let (parts, _) = hyper::Request::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .body(())
+            .unwrap()
+            .into_parts();
+        converter.read_body(&parts, Body::from("{}")).await
+    }
 
-        let api_response = next(api_request).await;
+    fn is_content_type_incorrect(result: DResult<(Bytes, Option<HeaderMap>)>) -> bool {
+        matches!(
+            result.unwrap_err().downcast_ref(),
+            Some(rejection::ApiRejection::ContentTypeIncorrect)
+        )
+    }
 
-        let http_response_result: DResult<hyper::Response<Body>> = (|| {
-            let content = api_response.content;
+    #[tokio::test]
+    async fn accepts_json_with_parameters() {
+        let converter = JsonApiMiddlewareConverter::default();
 
-            let status_code = content.status_code();
-            let json_bytes = if self.pretty_printed {
-                serde_json::to_vec_pretty(&content)
-            } else {
-                serde_json::to_vec(&content)
-            }?;
+        for content_type in [
+            "application/json",
+            "application/json; charset=utf-8",
+            "Application/JSON;charset=UTF-8",
+        ] {
+            let (bytes, _) = read_body_with_content_type(&converter, content_type)
+                .await
+                .unwrap_or_else(|error| panic!("{content_type} is rejected: {error}"));
+            assert_eq!(bytes, "{}");
+        }
+    }
 
-            let response = hyper::Response::builder()
-                .status(status_code)
-                .header(header::CONTENT_TYPE, "application/json")
-                .body(Body::from(json_bytes))?;
+    #[tokio::test]
+    async fn accepts_configured_json_vendor_types() {
+        let converter = JsonApiMiddlewareConverter::with_config(
+            JsonApiMiddlewareConfig::default()
+                .and_accepted_media_types(&["application/vnd.foo+json"]),
+        );
 
-            Ok(response)
-        })();
+        for content_type in [
+            "application/vnd.foo+json",
+            "application/vnd.foo+json; charset=utf-8",
+            "application/VND.FOO+JSON",
+        ] {
+            assert!(
+                read_body_with_content_type(&converter, content_type)
+                    .await
+                    .is_ok(),
+                "{content_type} is rejected"
+            );
+        }
+        assert!(is_content_type_incorrect(
+            read_body_with_content_type(&converter, "application/vnd.bar+json").await
+        ));
+    }
 
-        let http_response = http_response_result.unwrap_or_else(|_| {
-            hyper::Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Body::empty())
-                .unwrap()
-        });
+    #[tokio::test]
+    async fn rejects_non_json_vendor_types() {
+        let converter = JsonApiMiddlewareConverter::with_config(
+            JsonApiMiddlewareConfig::default()
+                .and_accepted_media_types(&["application/vnd.foo+xml"]),
+        );
 
-        Response {
-            http: http_response,
+        for content_type in ["application/vnd.foo+xml", "application/xml", "text/json"] {
+            assert!(
+                is_content_type_incorrect(
+                    read_body_with_content_type(&converter, content_type).await
+                ),
+                "{content_type} is accepted"
+            );
         }
     }
 }
@@ -1,7 +1,20 @@
+mod array_stream;
+mod data_error;
 mod middleware;
-#[cfg(feature = "ws")]
-mod stream_converter;
+mod patch;
+mod rename_case;
+#[cfg(feature = "json-schema")]
+mod schema;
+mod wrapper;
 
+pub use data_error::*;
 pub use middleware::*;
+pub use patch::*;
+pub use rename_case::*;
+#[cfg(feature = "json-schema")]
+pub use schema::*;
+pub use wrapper::*;
+
 #[cfg(feature = "ws")]
-pub use stream_converter::*;
+#[deprecated(note = "moved to `ws::JsonApiStreamConverter`")]
+pub type JsonApiStreamConverter = super::ws::JsonApiStreamConverter;
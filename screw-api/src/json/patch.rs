@@ -0,0 +1,67 @@
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchField {
+    Missing,
+    Null,
+    Present,
+}
+
+/// JSON object data for `PATCH` requests which keeps track of the fields sent by the client,
+/// so a missing field can be told apart from one explicitly set to `null`.
+///
+/// Field names are the keys as deserialized, after any rename case has been applied.
+#[derive(Clone, Debug)]
+pub struct Patch<T> {
+    fields: HashMap<String, PatchField>,
+    value: T,
+}
+
+impl<T> Patch<T> {
+    pub fn field(&self, name: &str) -> PatchField {
+        self.fields
+            .get(name)
+            .copied()
+            .unwrap_or(PatchField::Missing)
+    }
+
+    pub fn is_provided(&self, name: &str) -> bool {
+        self.field(name) != PatchField::Missing
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Patch<T>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let object = Map::<String, Value>::deserialize(deserializer)?;
+        let fields = object
+            .iter()
+            .map(|(key, value)| {
+                let field = if value.is_null() {
+                    PatchField::Null
+                } else {
+                    PatchField::Present
+                };
+                (key.clone(), field)
+            })
+            .collect();
+        let value = T::deserialize(Value::Object(object)).map_err(D::Error::custom)?;
+        Ok(Self { fields, value })
+    }
+}
@@ -0,0 +1,130 @@
+use serde_json::{Map, Value};
+
+/// Case applied to object keys on the wire while Rust types keep `snake_case` fields.
+///
+/// Every object key is renamed, including keys of maps, so map-typed data with
+/// meaningful keys should not be combined with a rename case. Incoming keys with acronyms
+/// are read as one word, `userID` becoming `user_id`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JsonRenameCase {
+    CamelCase,
+    PascalCase,
+    KebabCase,
+}
+
+impl JsonRenameCase {
+    fn rename_from_snake_case(&self, key: &str) -> String {
+        match self {
+            JsonRenameCase::CamelCase | JsonRenameCase::PascalCase => {
+                let mut renamed = String::with_capacity(key.len());
+                let mut capitalize = *self == JsonRenameCase::PascalCase;
+                for c in key.chars() {
+                    if c == '_' {
+                        capitalize = !renamed.is_empty();
+                    } else if capitalize {
+                        renamed.extend(c.to_uppercase());
+                        capitalize = false;
+                    } else {
+                        renamed.push(c);
+                    }
+                }
+                renamed
+            }
+            JsonRenameCase::KebabCase => key.replace('_', "-"),
+        }
+    }
+
+    pub(super) fn rename_to_snake_case(&self, key: &str) -> String {
+        match self {
+            JsonRenameCase::CamelCase | JsonRenameCase::PascalCase => {
+                // A run of capitals is one word, such as `ID` in `userID`, its last capital
+                // starting the next word when followed by a lowercase letter, as in `HTTPServer`
+                let chars: Vec<char> = key.chars().collect();
+                let mut renamed = String::with_capacity(key.len() + 4);
+                for (index, &c) in chars.iter().enumerate() {
+                    if c.is_uppercase() {
+                        let starts_word = index > 0
+                            && (!chars[index - 1].is_uppercase()
+                                || chars.get(index + 1).is_some_and(|next| next.is_lowercase()));
+                        if starts_word {
+                            renamed.push('_');
+                        }
+                        renamed.extend(c.to_lowercase());
+                    } else {
+                        renamed.push(c);
+                    }
+                }
+                renamed
+            }
+            JsonRenameCase::KebabCase => key.replace('-', "_"),
+        }
+    }
+
+    pub(crate) fn rename_outgoing(&self, value: Value) -> Value {
+        rename_keys(value, &|key| self.rename_from_snake_case(key))
+    }
+
+    pub(crate) fn rename_incoming(&self, value: Value) -> Value {
+        rename_keys(value, &|key| self.rename_to_snake_case(key))
+    }
+}
+
+fn rename_keys(value: Value, rename: &dyn Fn(&str) -> String) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| (rename(key.as_str()), rename_keys(value, rename)))
+                .collect::<Map<String, Value>>(),
+        ),
+        Value::Array(array) => Value::Array(
+            array
+                .into_iter()
+                .map(|value| rename_keys(value, rename))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renames_camel_case_to_snake_case() {
+        let cases = [
+            ("userName", "user_name"),
+            ("UserName", "user_name"),
+            ("userID", "user_id"),
+            ("ID", "id"),
+            ("HTTPServer", "http_server"),
+            ("getHTTPResponseCode", "get_http_response_code"),
+            ("address2Line", "address2_line"),
+            ("name", "name"),
+        ];
+        for (key, expected) in cases {
+            assert_eq!(
+                JsonRenameCase::CamelCase.rename_to_snake_case(key),
+                expected,
+                "{key}"
+            );
+        }
+    }
+
+    #[test]
+    fn renames_snake_case_to_camel_case() {
+        assert_eq!(
+            JsonRenameCase::CamelCase.rename_from_snake_case("user_id"),
+            "userId"
+        );
+        assert_eq!(
+            JsonRenameCase::PascalCase.rename_from_snake_case("user_id"),
+            "UserId"
+        );
+        assert_eq!(
+            JsonRenameCase::KebabCase.rename_from_snake_case("user_id"),
+            "user-id"
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use super::super::*;
+use super::JsonApiMiddlewareConverter;
+use hyper::http::request::Parts;
+use hyper::{Body, HeaderMap};
+use jsonschema::JSONSchema;
+use response::ApiResponseConverter;
+use screw_components::dyn_fn::DFnOnce;
+use screw_components::dyn_result::{DError, DResult};
+use screw_core::request::Request;
+use screw_core::response::Response;
+use screw_core::routing::middleware::Middleware;
+use screw_core::routing::router::RoutedRequest;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub struct JsonSchemaApiMiddlewareConverter {
+    converter: JsonApiMiddlewareConverter,
+    schema: JSONSchema,
+}
+
+impl JsonSchemaApiMiddlewareConverter {
+    pub fn with_schema(schema: &Value) -> DResult<Self> {
+        let schema = JSONSchema::compile(schema).map_err(|e| DError::from(e.to_string()))?;
+        Ok(Self {
+            converter: JsonApiMiddlewareConverter::default(),
+            schema,
+        })
+    }
+
+    /// Converter reading the body and responding, whose options apply to this converter, its
+    /// `rejection_response` responding schema violations as well. Defaults to
+    /// `JsonApiMiddlewareConverter::default()`.
+    pub fn and_converter(mut self, converter: JsonApiMiddlewareConverter) -> Self {
+        self.converter = converter;
+        self
+    }
+
+    fn validate(&self, value: &Value) -> Result<(), rejection::ApiRejection> {
+        self.schema.validate(value).map_err(|errors| {
+            rejection::ApiRejection::SchemaViolation(
+                errors
+                    .map(|error| rejection::ApiRejectionViolation {
+                        path: error.instance_path.to_string(),
+                        message: error.to_string(),
+                    })
+                    .collect(),
+            )
+        })
+    }
+}
+
+#[async_trait]
+impl<Data> request::ApiRequestConverter<Data> for JsonSchemaApiMiddlewareConverter
+where
+    for<'de> Data: Deserialize<'de> + Send + 'static,
+{
+    const NAME: &'static str = "json_schema";
+
+    fn phase_timings(&self) -> bool {
+        self.converter.config.phase_timings
+    }
+
+    fn rejection_response(&self) -> Option<fn(rejection::ApiRejection) -> Response> {
+        self.converter.config.rejection_response
+    }
+
+    fn respond_rejection(&self, rejection: rejection::ApiRejection) -> Response {
+        self.converter.reject(rejection)
+    }
+
+    async fn convert_request(
+        &self,
+        http_parts: &Parts,
+        http_body: Body,
+    ) -> Result<(DResult<Data>, Option<HeaderMap>), rejection::ApiRejection> {
+        let charset = charset::RequestCharset::from_parts(http_parts)?;
+        let mut trailers = None;
+        let value_result: DResult<Option<Value>> = match self
+            .converter
+            .read_body(http_parts, http_body)
+            .await
+            .and_then(|(json_bytes, body_trailers)| {
+                trailers = body_trailers;
+                charset.decode(json_bytes)
+            }) {
+            Ok(json_bytes) if self.converter.config.allow_empty_body && json_bytes.is_empty() => {
+                Ok(None)
+            }
+            Ok(json_bytes) => {
+                self.converter.reject_trailing_data(&json_bytes)?;
+                serde_json::from_slice::<Value>(&json_bytes)
+                    .map(Some)
+                    .map_err(|error| self.converter.data_error(error, &json_bytes))
+            }
+            Err(error) => Err(error),
+        };
+        let data_result = match value_result {
+            Ok(Some(value)) => {
+                self.validate(&value)?;
+                match self.converter.deserialize_value(value) {
+                    Ok((_, unknown_fields)) if !unknown_fields.is_empty() => {
+                        return Err(rejection::ApiRejection::UnknownFields(unknown_fields));
+                    }
+                    data_result => data_result.map(|(data, _)| data),
+                }
+            }
+            Ok(None) => JsonApiMiddlewareConverter::deserialize_empty(),
+            Err(error) => Err(error),
+        };
+        Ok((data_result, trailers))
+    }
+}
+
+#[async_trait]
+impl<RqContent, Extensions, RsContentSuccess, RsContentFailure>
+    Middleware<
+        request::ApiRequest<RqContent, Extensions>,
+        response::ApiResponse<RsContentSuccess, RsContentFailure>,
+    > for JsonSchemaApiMiddlewareConverter
+where
+    RqContent: request::ApiRequestContent<Extensions> + Send + 'static,
+    <RqContent as request::ApiRequestContent<Extensions>>::Data:
+        for<'de> Deserialize<'de> + Sync + Send + 'static,
+    Extensions: Sync + Send + 'static,
+    RsContentSuccess: response::ApiResponseContentSuccess + Send + 'static,
+    <RsContentSuccess as response::ApiResponseContentSuccess>::Data: Serialize,
+    RsContentFailure: response::ApiResponseContentFailure + Send + 'static,
+{
+    type Request = RoutedRequest<Request<Extensions>>;
+    type Response = Response;
+    async fn respond(
+        &self,
+        routed_request: RoutedRequest<Request<Extensions>>,
+        next: DFnOnce<
+            request::ApiRequest<RqContent, Extensions>,
+            response::ApiResponse<RsContentSuccess, RsContentFailure>,
+        >,
+    ) -> Response {
+        request::respond(self, routed_request, next, |api_response| {
+            self.converter.convert_response(api_response)
+        })
+        .await
+    }
+}
@@ -0,0 +1,141 @@
+use super::super::*;
+use hyper::{header, StatusCode};
+use response::{ApiResponseContentBase, ApiResponseContentFailure};
+use screw_core::request::Request;
+use screw_core::response::{Response, ResponseBody};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::json;
+use std::error::Error;
+use std::fmt;
+
+/// JSON body for handlers of the plain router, outside of `JsonApiMiddlewareConverter`.
+///
+/// Returned from a handler, it converts into a `Response` carrying `T` as `application/json`,
+/// or an empty `500 Internal Server Error` when `T` fails to serialize. Requests are read
+/// with `Json::from_request`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Json<T>(pub T);
+
+impl<T> Json<T>
+where
+    T: DeserializeOwned,
+{
+    /// Reads the body of `request` as JSON, requiring the `application/json` content type.
+    /// The body is taken out of the request.
+    pub async fn from_request<Extensions>(
+        request: &mut Request<Extensions>,
+    ) -> Result<Self, JsonRejection> {
+        let media_type = request
+            .http
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|header_value| header_value.to_str().ok())
+            .and_then(|content_type| content_type.split(';').next())
+            .map(str::trim);
+        match media_type {
+            Some("") | None => {
+                return Err(JsonRejection::Rejected(
+                    rejection::ApiRejection::ContentTypeMissed,
+                ))
+            }
+            Some(media_type) if media_type.eq_ignore_ascii_case("application/json") => {}
+            Some(_) => {
+                return Err(JsonRejection::Rejected(
+                    rejection::ApiRejection::ContentTypeIncorrect,
+                ))
+            }
+        }
+        let json_bytes = hyper::body::to_bytes(request.take_body())
+            .await
+            .map_err(JsonRejection::Body)?;
+        let data = serde_json::from_slice(&json_bytes).map_err(JsonRejection::Data)?;
+        Ok(Self(data))
+    }
+}
+
+impl<T> From<Json<T>> for Response
+where
+    T: Serialize,
+{
+    fn from(json: Json<T>) -> Self {
+        let http = match serde_json::to_vec(&json.0) {
+            Ok(json_bytes) => hyper::Response::builder()
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(ResponseBody::from(json_bytes))
+                .unwrap(),
+            Err(error) => {
+                tracing::error!(target: "screw::api", %error, "failed to serialize JSON response");
+                hyper::Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(ResponseBody::empty())
+                    .unwrap()
+            }
+        };
+        Response { http }
+    }
+}
+
+/// Failure of `Json::from_request`, responded as a `{"failure": {...}}` JSON body.
+#[derive(Debug)]
+pub enum JsonRejection {
+    Rejected(rejection::ApiRejection),
+    Body(hyper::Error),
+    Data(serde_json::Error),
+}
+
+impl fmt::Display for JsonRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonRejection::Rejected(rejection) => rejection.fmt(f),
+            JsonRejection::Body(error) => write!(f, "request body failed: {}", error),
+            JsonRejection::Data(error) => write!(f, "request data is invalid: {}", error),
+        }
+    }
+}
+
+impl Error for JsonRejection {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JsonRejection::Rejected(rejection) => Some(rejection),
+            JsonRejection::Body(error) => Some(error),
+            JsonRejection::Data(error) => Some(error),
+        }
+    }
+}
+
+impl ApiResponseContentBase for JsonRejection {
+    fn status_code(&self) -> &'static StatusCode {
+        match self {
+            JsonRejection::Rejected(rejection) => rejection.status_code(),
+            JsonRejection::Body(_) | JsonRejection::Data(_) => &StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl ApiResponseContentFailure for JsonRejection {
+    fn identifier(&self) -> &'static str {
+        match self {
+            JsonRejection::Rejected(rejection) => rejection.identifier(),
+            JsonRejection::Body(_) => "request_body_failed",
+            JsonRejection::Data(_) => "request_data_invalid",
+        }
+    }
+    fn reason(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
+impl From<JsonRejection> for Response {
+    fn from(rejection: JsonRejection) -> Self {
+        let content = json!({
+            "failure": {
+                "identifier": rejection.identifier(),
+                "reason": rejection.reason(),
+            }
+        });
+        let mut response = Response::from(Json(content));
+        *response.http.status_mut() = *rejection.status_code();
+        response
+    }
+}
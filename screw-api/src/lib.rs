@@ -1,19 +1,205 @@
-#[cfg(feature = "ws")]
-pub mod channel;
+//! Internal events are emitted through `tracing` under the `screw::api` target: durations of
+//! the conversion, handler and serialization phases of a request for converters with
+//! `phase_timings` enabled, with the converter named by the `converter` field, and
+//! `json::Json` responses or JSON array stream items failing to serialize.
+
+#[cfg(any(feature = "json", feature = "xml"))]
+mod charset;
+#[cfg(feature = "codec")]
+pub mod codec;
+pub mod problem;
+pub mod rejection;
 pub mod request;
 pub mod response;
+#[cfg(any(feature = "json", feature = "xml"))]
+pub mod stream;
 
 #[cfg(feature = "json")]
 pub mod json;
+#[cfg(feature = "ws")]
+pub mod ws;
 #[cfg(feature = "xml")]
 pub mod xml;
-#[cfg(any(feature = "json", feature = "xml"))]
-#[derive(derive_error::Error, Debug)]
-enum ApiRequestContentTypeError {
-    Missed,
-    Incorrect,
+
+#[cfg(feature = "ws")]
+#[deprecated(note = "moved to `ws::channel`")]
+pub mod channel {
+    pub use super::ws::channel::*;
 }
 
-#[cfg(any(feature = "json", feature = "xml"))]
+#[cfg(feature = "derive")]
+pub use screw_api_macros::{api_enum, ApiFailure, ApiRequestContent};
+
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __private {
+    use super::response::{ApiResponseContentBase, ApiResponseContentFailure};
+    use screw_core::response::ResponseBody;
+
+    pub use hyper::StatusCode;
+    pub use screw_core::response::Response;
+
+    pub fn plain_rejection(rejection: super::rejection::ApiRejection) -> Response {
+        Response {
+            http: hyper::Response::builder()
+                .status(*rejection.status_code())
+                .header(hyper::header::CONTENT_TYPE, "text/plain")
+                .body(ResponseBody::from(rejection.reason().unwrap_or_default()))
+                .unwrap(),
+        }
+    }
+}
+
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
 #[macro_use]
 extern crate async_trait;
+
+// Lets tests derive with macros expanding to `::screw_api` paths
+#[cfg(all(test, feature = "derive"))]
+extern crate self as screw_api;
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use super::request::{ApiRequestContent, ApiRequestOriginContent};
+    use super::response::{ApiResponseContentBase, ApiResponseContentFailure};
+    use super::{ApiFailure, ApiRequestContent};
+    use hyper::StatusCode;
+    use screw_components::dyn_result::DResult;
+    use screw_core::routing::actix::Path;
+    use serde::Deserialize;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    #[derive(ApiFailure)]
+    #[status(400)]
+    enum UserFailure {
+        #[status(404)]
+        #[message("user {0} not found")]
+        NotFound(u64),
+        #[message("name is {reason}, at most {max_len} characters")]
+        InvalidName { reason: String, max_len: usize },
+        #[status(409)]
+        #[identifier("user_exists")]
+        #[message("user {1:?} of {{team}} {0} exists")]
+        AlreadyExists(u64, String),
+        #[status(503)]
+        HTTPUnavailable,
+    }
+
+    #[test]
+    fn derives_api_failure() {
+        let cases = [
+            (
+                UserFailure::NotFound(7),
+                StatusCode::NOT_FOUND,
+                "not_found",
+                Some("user 7 not found"),
+            ),
+            (
+                UserFailure::InvalidName {
+                    reason: "too long".to_owned(),
+                    max_len: 32,
+                },
+                StatusCode::BAD_REQUEST,
+                "invalid_name",
+                Some("name is too long, at most 32 characters"),
+            ),
+            (
+                UserFailure::AlreadyExists(7, "alice".to_owned()),
+                StatusCode::CONFLICT,
+                "user_exists",
+                Some("user \"alice\" of {team} 7 exists"),
+            ),
+            (
+                UserFailure::HTTPUnavailable,
+                StatusCode::SERVICE_UNAVAILABLE,
+                "http_unavailable",
+                None,
+            ),
+        ];
+        for (failure, status_code, identifier, reason) in cases {
+            assert_eq!(*failure.status_code(), status_code);
+            assert_eq!(failure.identifier(), identifier);
+            assert_eq!(failure.reason().as_deref(), reason);
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct UserPath {
+        id: u64,
+    }
+
+    #[derive(Deserialize)]
+    struct UpdateOptions {
+        notify: bool,
+    }
+
+    struct UserData {
+        name: String,
+    }
+
+    #[derive(ApiRequestContent)]
+    struct UpdateUser {
+        #[api_request(path)]
+        path: UserPath,
+        #[api_request(query)]
+        query: UpdateOptions,
+        #[api_request(body)]
+        user: UserData,
+    }
+
+    fn origin_content(
+        id: &'static str,
+        query: &str,
+        data_result: DResult<UserData>,
+    ) -> ApiRequestOriginContent<UserData, ()> {
+        let mut path = Path::new(format!("/users/{}", id));
+        path.add_static("id", id);
+        let http_request = hyper::Request::builder()
+            .uri(format!("/users/{}?{}", id, query))
+            .body(())
+            .unwrap();
+        ApiRequestOriginContent {
+            path,
+            query: Default::default(),
+            http_parts: http_request.into_parts().0,
+            remote_addr: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            extensions: Arc::new(()),
+            data_result,
+            trailers: None,
+        }
+    }
+
+    fn rejection_status(origin_content: ApiRequestOriginContent<UserData, ()>) -> StatusCode {
+        match UpdateUser::create(origin_content) {
+            Ok(_) => panic!("request content created"),
+            Err(response) => response.http.status(),
+        }
+    }
+
+    #[test]
+    fn derives_api_request_content() {
+        let user_data = UserData {
+            name: "alice".to_owned(),
+        };
+        let update_user = UpdateUser::create(origin_content("7", "notify=true", Ok(user_data)))
+            .ok()
+            .unwrap();
+        assert_eq!(update_user.path.id, 7);
+        assert!(update_user.query.notify);
+        assert_eq!(update_user.user.name, "alice");
+    }
+
+    #[test]
+    fn rejects_invalid_request_content() {
+        let user_data = || UserData {
+            name: "alice".to_owned(),
+        };
+        let status = rejection_status(origin_content("seven", "notify=true", Ok(user_data())));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let status = rejection_status(origin_content("7", "notify=maybe", Ok(user_data())));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let status = rejection_status(origin_content("7", "notify=true", Err("invalid".into())));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
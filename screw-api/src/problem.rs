@@ -0,0 +1,87 @@
+use super::response::{ApiResponseContentBase, ApiResponseContentFailure};
+use hyper::StatusCode;
+use serde::{Serialize, Serializer};
+
+/// Problem details document of RFC 7807, responded as `application/problem+json` by the JSON
+/// converter for failures returning it from `ApiResponseContentFailure::problem`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ApiProblem {
+    #[serde(rename = "type")]
+    pub type_uri: String,
+    pub title: String,
+    #[serde(rename = "status", serialize_with = "serialize_status_code")]
+    pub status_code: &'static StatusCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+}
+
+impl ApiProblem {
+    /// Problem of type `about:blank`, titled with the reason phrase of `status_code`.
+    pub fn with_status_code(status_code: &'static StatusCode) -> Self {
+        Self {
+            type_uri: "about:blank".to_owned(),
+            title: status_code
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_owned(),
+            status_code,
+            detail: None,
+            instance: None,
+        }
+    }
+
+    /// Problem of type `identifier` with `reason` as its detail, used for failures without
+    /// a problem of their own.
+    pub fn from_failure<Failure: ApiResponseContentFailure + ?Sized>(failure: &Failure) -> Self {
+        Self::with_status_code(failure.status_code())
+            .and_type(failure.identifier())
+            .and_detail(failure.reason())
+    }
+
+    pub fn and_type<T: Into<String>>(mut self, type_uri: T) -> Self {
+        self.type_uri = type_uri.into();
+        self
+    }
+
+    pub fn and_title<T: Into<String>>(mut self, title: T) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn and_detail<D: Into<String>>(mut self, detail: Option<D>) -> Self {
+        self.detail = detail.map(Into::into);
+        self
+    }
+
+    pub fn and_instance<I: Into<String>>(mut self, instance: Option<I>) -> Self {
+        self.instance = instance.map(Into::into);
+        self
+    }
+}
+
+fn serialize_status_code<S: Serializer>(
+    status_code: &&'static StatusCode,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u16(status_code.as_u16())
+}
+
+impl ApiResponseContentBase for ApiProblem {
+    fn status_code(&self) -> &'static StatusCode {
+        self.status_code
+    }
+}
+
+impl ApiResponseContentFailure for ApiProblem {
+    fn identifier(&self) -> &'static str {
+        "problem"
+    }
+    fn reason(&self) -> Option<String> {
+        self.detail.clone()
+    }
+    fn problem(&self) -> Option<ApiProblem> {
+        Some(self.clone())
+    }
+}
@@ -0,0 +1,123 @@
+use super::response::{ApiResponseContentBase, ApiResponseContentFailure};
+use hyper::StatusCode;
+use screw_components::dyn_result::DError;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ApiRejectionViolation {
+    pub path: String,
+    pub message: String,
+}
+
+/// Failure of a converter to turn a request into request data.
+///
+/// Content type and body failures, chunked body limits included, reach
+/// `ApiRequestContent::create` as the `data_result` error and can be recovered with
+/// `downcast_ref::<ApiRejection>()`, while unsupported charsets, trailing data, schema
+/// violations and unknown fields are responded by the converter before the handler is called.
+#[derive(Clone, Debug)]
+pub enum ApiRejection {
+    ContentTypeMissed,
+    ContentTypeIncorrect,
+    CharsetUnsupported(String),
+    TrailingData,
+    SchemaViolation(Vec<ApiRejectionViolation>),
+    TooManyChunks,
+    BodyTooLarge,
+    /// Body of unknown length not fitting in the server body budget while being read, see
+    /// `ServerBuilder::and_body_budget`.
+    BodyBudgetExhausted,
+    /// Fields of the request data the data type does not have, by their path such as
+    /// `user.nmae`, see `JsonApiMiddlewareConfig::deny_unknown_fields`.
+    UnknownFields(Vec<String>),
+    /// Path parameters failing to deserialize, see `ApiRequestOriginContent::path_params`.
+    PathInvalid(String),
+    /// Query string failing to deserialize, see `ApiRequestOriginContent::query_params`.
+    QueryInvalid(String),
+    /// Body failing to read or deserialize for another reason than a rejection.
+    DataInvalid(String),
+}
+
+impl ApiRejection {
+    /// Recovers the rejection of a failed `data_result`, describing any other error as
+    /// `ApiRejection::DataInvalid`.
+    pub fn from_data_error(error: DError) -> Self {
+        match error.downcast::<ApiRejection>() {
+            Ok(rejection) => *rejection,
+            Err(error) => ApiRejection::DataInvalid(error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ApiRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiRejection::ContentTypeMissed => write!(f, "request content type is missed"),
+            ApiRejection::ContentTypeIncorrect => write!(f, "request content type is incorrect"),
+            ApiRejection::CharsetUnsupported(charset) => {
+                write!(f, "request charset {} is unsupported", charset)
+            }
+            ApiRejection::TrailingData => {
+                write!(f, "request data has trailing data after the document")
+            }
+            ApiRejection::SchemaViolation(_) => write!(f, "request data does not match schema"),
+            ApiRejection::TooManyChunks => write!(f, "request body has too many chunks"),
+            ApiRejection::BodyTooLarge => write!(f, "request body is too large"),
+            ApiRejection::BodyBudgetExhausted => write!(f, "request body budget is exhausted"),
+            ApiRejection::UnknownFields(paths) => {
+                write!(f, "request data has unknown fields: {}", paths.join(", "))
+            }
+            ApiRejection::PathInvalid(error) => {
+                write!(f, "request path parameters are invalid: {}", error)
+            }
+            ApiRejection::QueryInvalid(error) => write!(f, "request query is invalid: {}", error),
+            ApiRejection::DataInvalid(error) => write!(f, "request data is invalid: {}", error),
+        }
+    }
+}
+
+impl Error for ApiRejection {}
+
+impl ApiResponseContentBase for ApiRejection {
+    fn status_code(&self) -> &'static StatusCode {
+        match self {
+            ApiRejection::ContentTypeMissed
+            | ApiRejection::ContentTypeIncorrect
+            | ApiRejection::CharsetUnsupported(_) => &StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiRejection::TrailingData
+            | ApiRejection::SchemaViolation(_)
+            | ApiRejection::UnknownFields(_)
+            | ApiRejection::PathInvalid(_)
+            | ApiRejection::QueryInvalid(_)
+            | ApiRejection::DataInvalid(_) => &StatusCode::BAD_REQUEST,
+            ApiRejection::TooManyChunks | ApiRejection::BodyTooLarge => {
+                &StatusCode::PAYLOAD_TOO_LARGE
+            }
+            ApiRejection::BodyBudgetExhausted => &StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+impl ApiResponseContentFailure for ApiRejection {
+    fn identifier(&self) -> &'static str {
+        match self {
+            ApiRejection::ContentTypeMissed => "content_type_missed",
+            ApiRejection::ContentTypeIncorrect => "content_type_incorrect",
+            ApiRejection::CharsetUnsupported(_) => "charset_unsupported",
+            ApiRejection::TrailingData => "trailing_data",
+            ApiRejection::SchemaViolation(_) => "schema_violation",
+            ApiRejection::TooManyChunks => "too_many_chunks",
+            ApiRejection::BodyTooLarge => "body_too_large",
+            ApiRejection::BodyBudgetExhausted => "body_budget_exhausted",
+            ApiRejection::UnknownFields(_) => "unknown_fields",
+            ApiRejection::PathInvalid(_) => "path_invalid",
+            ApiRejection::QueryInvalid(_) => "query_invalid",
+            ApiRejection::DataInvalid(_) => "data_invalid",
+        }
+    }
+    fn reason(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
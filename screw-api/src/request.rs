@@ -1,32 +1,132 @@
+use super::rejection::ApiRejection;
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+use super::response::{
+    ApiResponse, ApiResponseContentBase, ApiResponseContentFailure, ApiResponseContentSuccess,
+};
+use hyper::body::{Bytes, HttpBody};
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+use hyper::header;
 use hyper::http::request::Parts;
-use screw_components::dyn_result::DResult;
+use hyper::{Body, HeaderMap};
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+use screw_components::dyn_fn::DFnOnce;
+use screw_components::dyn_result::{DError, DResult};
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+use screw_core::phase_timer::PhaseTimer;
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+use screw_core::request::Request;
+use screw_core::response::Response;
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+use screw_core::response::ResponseBody;
 use screw_core::routing::actix::Path;
-use serde::Deserialize;
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+use screw_core::routing::router::RoutedRequest;
+use screw_core::server::BodyBudgetExhausted;
+#[cfg(any(feature = "json", feature = "derive"))]
+use serde::de::DeserializeOwned;
 use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
-pub struct ApiRequestOriginContent<Data, Extensions>
-where
-    Data: for<'de> Deserialize<'de>,
-{
+pub struct ApiRequestOriginContent<Data, Extensions> {
     pub path: Path<String>,
     pub query: HashMap<String, String>,
     pub http_parts: Parts,
     pub remote_addr: SocketAddr,
     pub extensions: Arc<Extensions>,
     pub data_result: DResult<Data>,
+    /// Trailers sent after the body, such as a checksum of an upload, `None` when the body
+    /// failed to read or came without trailers. Only HTTP/2 requests carry trailers, as hyper
+    /// discards those of chunked HTTP/1 bodies.
+    pub trailers: Option<HeaderMap>,
+}
+
+#[cfg(any(feature = "json", feature = "derive"))]
+impl<Data, Extensions> ApiRequestOriginContent<Data, Extensions> {
+    /// Deserializes the path parameters of the matched route into `T`, e.g. a struct with an
+    /// `id` field for `/users/{id}`. Values are parsed like those of a query string, so
+    /// numbers and booleans deserialize from their text.
+    pub fn path_params<T: DeserializeOwned>(&self) -> Result<T, ApiRejection> {
+        serde_urlencoded::to_string(self.path.iter().collect::<Vec<_>>())
+            .map_err(|error| error.to_string())
+            .and_then(|encoded| {
+                serde_urlencoded::from_str(&encoded).map_err(|error| error.to_string())
+            })
+            .map_err(ApiRejection::PathInvalid)
+    }
+
+    /// Deserializes the query string into `T`, an absent one deserializing as empty.
+    pub fn query_params<T: DeserializeOwned>(&self) -> Result<T, ApiRejection> {
+        serde_urlencoded::from_str(self.http_parts.uri.query().unwrap_or_default())
+            .map_err(|error| ApiRejection::QueryInvalid(error.to_string()))
+    }
+}
+
+/// Limits on request bodies of unknown length, sent with `Transfer-Encoding: chunked` or as
+/// HTTP/2 frames without `Content-Length`, enforced by converters while reading the body.
+/// A body exceeding them fails to read with `ApiRejection::TooManyChunks` or
+/// `ApiRejection::BodyTooLarge`, and one exhausting the server body budget with
+/// `ApiRejection::BodyBudgetExhausted`. Bodies of known length are not limited.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChunkedBodyLimits {
+    /// Maximum number of chunks, counted as they are read off the connection, so a chunk
+    /// arriving in several reads counts more than once. Unlimited by default.
+    pub max_chunks: Option<usize>,
+    /// Maximum size of the body in bytes. Unlimited by default.
+    pub max_size: Option<usize>,
+}
+
+impl ChunkedBodyLimits {
+    /// Reads `body` to the end within the limits, along with its trailers.
+    pub async fn read(&self, mut body: Body) -> DResult<(Bytes, Option<HeaderMap>)> {
+        let is_limited = body.size_hint().exact().is_none();
+        let mut bytes = Vec::new();
+        let mut chunks = 0;
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.map_err(|error| {
+                if BodyBudgetExhausted::is_source_of(&error) {
+                    ApiRejection::BodyBudgetExhausted.into()
+                } else {
+                    DError::from(error)
+                }
+            })?;
+            chunks += 1;
+            if is_limited {
+                if self
+                    .max_chunks
+                    .is_some_and(|max_chunks| chunks > max_chunks)
+                {
+                    return Err(ApiRejection::TooManyChunks.into());
+                }
+                if self
+                    .max_size
+                    .is_some_and(|max_size| bytes.len() + chunk.len() > max_size)
+                {
+                    return Err(ApiRejection::BodyTooLarge.into());
+                }
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        let trailers = body.trailers().await?;
+        Ok((Bytes::from(bytes), trailers))
+    }
 }
 
-pub trait ApiRequestContent<Extensions> {
-    type Data: for<'de> Deserialize<'de>;
-    fn create(origin_content: ApiRequestOriginContent<Self::Data, Extensions>) -> Self;
+pub trait ApiRequestContent<Extensions>: Sized {
+    type Data;
+    fn create(
+        origin_content: ApiRequestOriginContent<Self::Data, Extensions>,
+    ) -> Result<Self, Box<Response>>;
 }
 
 impl<Extensions> ApiRequestContent<Extensions> for () {
     type Data = ();
-    fn create(_origin_content: ApiRequestOriginContent<Self::Data, Extensions>) -> Self {}
+    fn create(
+        _origin_content: ApiRequestOriginContent<Self::Data, Extensions>,
+    ) -> Result<Self, Box<Response>> {
+        Ok(())
+    }
 }
 
 pub struct ApiRequest<Content, Extensions>
@@ -45,3 +145,184 @@ where
         (value.content,)
     }
 }
+
+pub struct ApiStreamRequestOriginContent<Extensions> {
+    pub path: Path<String>,
+    pub query: HashMap<String, String>,
+    pub http_parts: Parts,
+    pub remote_addr: SocketAddr,
+    pub extensions: Arc<Extensions>,
+    pub body: Body,
+}
+
+pub trait ApiStreamRequestContent<Extensions>: Sized {
+    fn create(
+        origin_content: ApiStreamRequestOriginContent<Extensions>,
+    ) -> Result<Self, Box<Response>>;
+}
+
+pub struct ApiStreamRequest<Content, Extensions>
+where
+    Content: ApiStreamRequestContent<Extensions>,
+{
+    pub content: Content,
+    pub(super) _p_e: PhantomData<Extensions>,
+}
+
+impl<Content, Extensions> From<ApiStreamRequest<Content, Extensions>> for (Content,)
+where
+    Content: ApiStreamRequestContent<Extensions>,
+{
+    fn from(value: ApiStreamRequest<Content, Extensions>) -> Self {
+        (value.content,)
+    }
+}
+
+/// Conversion of request bodies into request data, letting converters share `respond`.
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+#[async_trait]
+pub(crate) trait ApiRequestConverter<Data> {
+    /// Name of the converter in the `converter` field of `api request phases` events.
+    const NAME: &'static str;
+
+    /// Whether `api request phases` events are emitted.
+    fn phase_timings(&self) -> bool;
+
+    /// Responds rejections of `convert_request` in place of `respond_rejection`, as set by the
+    /// `rejection_response` option of the converter.
+    fn rejection_response(&self) -> Option<fn(ApiRejection) -> Response> {
+        None
+    }
+
+    /// Responds a rejection of `convert_request` in the format of the converter, as plain text
+    /// by default.
+    fn respond_rejection(&self, rejection: ApiRejection) -> Response {
+        Response {
+            http: hyper::Response::builder()
+                .status(*rejection.status_code())
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(ResponseBody::from(rejection.to_string()))
+                .unwrap(),
+        }
+    }
+
+    /// Reads the request data and the trailers of the body, or gives a rejection to respond
+    /// before the handler is called.
+    async fn convert_request(
+        &self,
+        http_parts: &Parts,
+        http_body: Body,
+    ) -> Result<(DResult<Data>, Option<HeaderMap>), ApiRejection>;
+}
+
+/// Times the conversion, handler and serialization phases of a request, emitting them as an
+/// `api request phases` event once the response is converted, when enabled.
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+pub(crate) struct ApiPhases {
+    converter: &'static str,
+    phase_timer: PhaseTimer,
+}
+
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+impl ApiPhases {
+    pub(crate) fn start(converter: &'static str, enabled: bool) -> Self {
+        Self {
+            converter,
+            phase_timer: PhaseTimer::start(enabled),
+        }
+    }
+
+    /// Ends the conversion, handing `api_request` to `next` and its API response to
+    /// `convert_response`.
+    pub(crate) async fn respond<ApiRq, ApiRs>(
+        mut self,
+        api_request: ApiRq,
+        next: DFnOnce<ApiRq, ApiRs>,
+        convert_response: impl FnOnce(ApiRs) -> Response,
+    ) -> Response {
+        let conversion_elapsed = self.phase_timer.lap();
+        let api_response = next(api_request).await;
+        let handler_elapsed = self.phase_timer.lap();
+        let response = convert_response(api_response);
+        if self.phase_timer.is_enabled() {
+            tracing::debug!(
+                target: "screw::api",
+                converter = self.converter,
+                conversion = ?conversion_elapsed,
+                handler = ?handler_elapsed,
+                serialization = ?self.phase_timer.lap(),
+                "api request phases"
+            );
+        }
+
+        response
+    }
+}
+
+/// Converts `routed_request` into the API request `next` handles with `converter`, and the API
+/// response of `next` with `convert_response`.
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+pub(crate) async fn respond<Converter, Content, Extensions, Success, Failure>(
+    converter: &Converter,
+    routed_request: RoutedRequest<Request<Extensions>>,
+    next: DFnOnce<ApiRequest<Content, Extensions>, ApiResponse<Success, Failure>>,
+    convert_response: impl FnOnce(ApiResponse<Success, Failure>) -> Response,
+) -> Response
+where
+    Converter: ApiRequestConverter<Content::Data>,
+    Content: ApiRequestContent<Extensions>,
+    Success: ApiResponseContentSuccess,
+    Failure: ApiResponseContentFailure,
+{
+    let phases = ApiPhases::start(Converter::NAME, converter.phase_timings());
+
+    let (http_parts, http_body) = routed_request.origin.http.into_parts();
+    let (data_result, trailers) = match converter.convert_request(&http_parts, http_body).await {
+        Ok(converted) => converted,
+        Err(rejection) => {
+            return match converter.rejection_response() {
+                Some(rejection_response) => rejection_response(rejection),
+                None => converter.respond_rejection(rejection),
+            }
+        }
+    };
+
+    let content = match Content::create(ApiRequestOriginContent {
+        path: routed_request.path,
+        query: routed_request.query,
+        http_parts,
+        remote_addr: routed_request.origin.remote_addr,
+        extensions: routed_request.origin.extensions,
+        data_result,
+        trailers,
+    }) {
+        Ok(content) => content,
+        Err(response) => return *response,
+    };
+
+    let api_request = ApiRequest {
+        content,
+        _p_e: Default::default(),
+    };
+    phases.respond(api_request, next, convert_response).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::response::ApiResponseContentBase;
+    use super::*;
+
+    #[tokio::test]
+    async fn budget_exhausted_body_is_rejected_as_unavailable() {
+        let body = Body::wrap_stream(futures::stream::iter([
+            Ok(Bytes::from_static(b"abc")),
+            Err(BodyBudgetExhausted),
+        ]));
+        let error = ChunkedBodyLimits::default().read(body).await.unwrap_err();
+        let rejection = ApiRejection::from_data_error(error);
+        assert_eq!(
+            rejection.status_code(),
+            &hyper::StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+}
@@ -1,5 +1,13 @@
-use hyper::StatusCode;
-use serde::ser::SerializeStructVariant;
+use super::problem::ApiProblem;
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+use hyper::body::Bytes;
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+use hyper::header;
+use hyper::{HeaderMap, StatusCode};
+#[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+use screw_components::dyn_result::DResult;
+use screw_core::response::{Response, ResponseBody};
+use serde::ser::{Error, SerializeStructVariant};
 use serde::{Serialize, Serializer};
 use std::convert::Infallible;
 
@@ -14,10 +22,13 @@ impl ApiResponseContentBase for Infallible {
 }
 
 pub trait ApiResponseContentSuccess: ApiResponseContentBase {
-    type Data: Serialize;
+    type Data;
     fn identifier(&self) -> &'static str;
     fn description(&self) -> Option<String>;
     fn data(&self) -> &Self::Data;
+    fn headers(&self) -> HeaderMap {
+        HeaderMap::new()
+    }
 }
 
 impl ApiResponseContentSuccess for Infallible {
@@ -33,9 +44,36 @@ impl ApiResponseContentSuccess for Infallible {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct ApiResponseNoContent;
+
+impl ApiResponseContentBase for ApiResponseNoContent {
+    fn status_code(&self) -> &'static StatusCode {
+        &StatusCode::NO_CONTENT
+    }
+}
+
+impl ApiResponseContentSuccess for ApiResponseNoContent {
+    type Data = ();
+    fn identifier(&self) -> &'static str {
+        "no_content"
+    }
+    fn description(&self) -> Option<String> {
+        None
+    }
+    fn data(&self) -> &Self::Data {
+        &()
+    }
+}
+
 pub trait ApiResponseContentFailure: ApiResponseContentBase {
     fn identifier(&self) -> &'static str;
     fn reason(&self) -> Option<String>;
+    /// Problem details document the failure is responded as by converters supporting
+    /// `application/problem+json`, see `ApiProblem`.
+    fn problem(&self) -> Option<ApiProblem> {
+        None
+    }
 }
 
 impl ApiResponseContentFailure for Infallible {
@@ -47,6 +85,23 @@ impl ApiResponseContentFailure for Infallible {
     }
 }
 
+/// Status, headers and body responded as they are, bypassing serialization, see
+/// `ApiResponse::raw`.
+pub struct ApiResponseRaw {
+    pub status_code: &'static StatusCode,
+    pub headers: HeaderMap,
+    pub body: ResponseBody,
+}
+
+impl ApiResponseRaw {
+    pub fn into_response(self) -> Response {
+        let mut http = hyper::Response::new(self.body);
+        *http.status_mut() = *self.status_code;
+        *http.headers_mut() = self.headers;
+        Response { http }
+    }
+}
+
 pub enum ApiResponseContent<Success, Failure>
 where
     Success: ApiResponseContentSuccess,
@@ -54,6 +109,7 @@ where
 {
     Success(Success),
     Failure(Failure),
+    Raw(ApiResponseRaw),
 }
 
 impl<Success, Failure> ApiResponseContentBase for ApiResponseContent<Success, Failure>
@@ -65,6 +121,65 @@ where
         match self {
             ApiResponseContent::Success(success) => success.status_code(),
             ApiResponseContent::Failure(failure) => failure.status_code(),
+            ApiResponseContent::Raw(raw) => raw.status_code,
+        }
+    }
+}
+
+impl<Success, Failure> ApiResponseContent<Success, Failure>
+where
+    Success: ApiResponseContentSuccess,
+    Failure: ApiResponseContentFailure,
+{
+    pub fn headers(&self) -> HeaderMap {
+        match self {
+            ApiResponseContent::Success(success) => success.headers(),
+            ApiResponseContent::Failure(_) => HeaderMap::new(),
+            ApiResponseContent::Raw(raw) => raw.headers.clone(),
+        }
+    }
+
+    /// Responds the content with the status code, content type and body `serialize` gives
+    /// for it, the way every converter does: raw content is responded as it is and
+    /// `204 No Content` without a body, the headers of the content are added, and a failure to
+    /// serialize is responded as a bodyless `500 Internal Server Error`.
+    #[cfg(any(feature = "codec", feature = "json", feature = "xml"))]
+    pub(crate) fn into_response<F>(self, serialize: F) -> Response
+    where
+        F: FnOnce(&Self) -> DResult<(StatusCode, &'static str, Bytes)>,
+    {
+        let content = match self {
+            ApiResponseContent::Raw(raw) => return raw.into_response(),
+            content => content,
+        };
+
+        let http_response_result: DResult<hyper::Response<ResponseBody>> = (|| {
+            let mut response = if *content.status_code() == StatusCode::NO_CONTENT {
+                hyper::Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(ResponseBody::empty())?
+            } else {
+                let (status_code, content_type, bytes) = serialize(&content)?;
+
+                hyper::Response::builder()
+                    .status(status_code)
+                    .header(header::CONTENT_TYPE, content_type)
+                    .body(ResponseBody::from(bytes))?
+            };
+            response.headers_mut().extend(content.headers());
+
+            Ok(response)
+        })();
+
+        let http_response = http_response_result.unwrap_or_else(|_| {
+            hyper::Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(ResponseBody::empty())
+                .unwrap()
+        });
+
+        Response {
+            http: http_response,
         }
     }
 }
@@ -72,6 +187,7 @@ where
 impl<Success, Failure> Serialize for ApiResponseContent<Success, Failure>
 where
     Success: ApiResponseContentSuccess,
+    Success::Data: Serialize,
     Failure: ApiResponseContentFailure,
 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -94,6 +210,9 @@ where
                 state.serialize_field("reason", &failure.reason())?;
                 state.end()
             }
+            ApiResponseContent::Raw(_) => Err(S::Error::custom(
+                "raw api response content can not be serialized",
+            )),
         }
     }
 }
@@ -135,6 +254,22 @@ where
             content: ApiResponseContent::Failure(content_failure),
         }
     }
+
+    /// Escape hatch responding `status_code`, `headers` and `body` untouched by the converter,
+    /// e.g. for redirects or binary payloads from otherwise typed handlers.
+    pub fn raw<B: Into<ResponseBody>>(
+        status_code: &'static StatusCode,
+        headers: HeaderMap,
+        body: B,
+    ) -> Self {
+        Self {
+            content: ApiResponseContent::Raw(ApiResponseRaw {
+                status_code,
+                headers,
+                body: body.into(),
+            }),
+        }
+    }
 }
 
 impl<Success, Failure> From<Result<Success, Failure>> for ApiResponse<Success, Failure>
@@ -148,3 +283,14 @@ where
         }
     }
 }
+
+pub trait ApiResponseConverter {
+    fn convert_response<Success, Failure>(
+        &self,
+        api_response: ApiResponse<Success, Failure>,
+    ) -> Response
+    where
+        Success: ApiResponseContentSuccess,
+        Success::Data: Serialize,
+        Failure: ApiResponseContentFailure;
+}
@@ -0,0 +1,84 @@
+use super::*;
+use response::ApiResponseConverter;
+use screw_components::dyn_fn::DFnOnce;
+use screw_core::request::Request;
+use screw_core::response::Response;
+use screw_core::routing::middleware::Middleware;
+use screw_core::routing::router::RoutedRequest;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug)]
+pub struct StreamApiMiddlewareConverter<ResponseConverter>
+where
+    ResponseConverter: ApiResponseConverter + Sync + Send + 'static,
+{
+    response_converter: ResponseConverter,
+    /// Whether `api request phases` events are emitted, as with
+    /// `JsonApiMiddlewareConfig::phase_timings`. Defaults to `false`.
+    pub phase_timings: bool,
+}
+
+impl<ResponseConverter> StreamApiMiddlewareConverter<ResponseConverter>
+where
+    ResponseConverter: ApiResponseConverter + Sync + Send + 'static,
+{
+    pub fn with_response_converter(response_converter: ResponseConverter) -> Self {
+        Self {
+            response_converter,
+            phase_timings: false,
+        }
+    }
+}
+
+#[async_trait]
+impl<ResponseConverter, RqContent, Extensions, RsContentSuccess, RsContentFailure>
+    Middleware<
+        request::ApiStreamRequest<RqContent, Extensions>,
+        response::ApiResponse<RsContentSuccess, RsContentFailure>,
+    > for StreamApiMiddlewareConverter<ResponseConverter>
+where
+    ResponseConverter: ApiResponseConverter + Sync + Send + 'static,
+    RqContent: request::ApiStreamRequestContent<Extensions> + Send + 'static,
+    Extensions: Sync + Send + 'static,
+    RsContentSuccess: response::ApiResponseContentSuccess + Send + 'static,
+    <RsContentSuccess as response::ApiResponseContentSuccess>::Data: Serialize,
+    RsContentFailure: response::ApiResponseContentFailure + Send + 'static,
+{
+    type Request = RoutedRequest<Request<Extensions>>;
+    type Response = Response;
+    async fn respond(
+        &self,
+        routed_request: RoutedRequest<Request<Extensions>>,
+        next: DFnOnce<
+            request::ApiStreamRequest<RqContent, Extensions>,
+            response::ApiResponse<RsContentSuccess, RsContentFailure>,
+        >,
+    ) -> Response {
+        let phases = request::ApiPhases::start("stream", self.phase_timings);
+
+        let (http_parts, http_body) = routed_request.origin.http.into_parts();
+
+        let request_content = match RqContent::create(request::ApiStreamRequestOriginContent {
+            path: routed_request.path,
+            query: routed_request.query,
+            http_parts,
+            remote_addr: routed_request.origin.remote_addr,
+            extensions: routed_request.origin.extensions,
+            body: http_body,
+        }) {
+            Ok(request_content) => request_content,
+            Err(response) => return *response,
+        };
+
+        let api_request = request::ApiStreamRequest {
+            content: request_content,
+            _p_e: Default::default(),
+        };
+
+        phases
+            .respond(api_request, next, |api_response| {
+                self.response_converter.convert_response(api_response)
+            })
+            .await
+    }
+}
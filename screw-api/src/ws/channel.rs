@@ -0,0 +1,803 @@
+use futures::stream::SplitStream;
+use futures::{SinkExt, Stream, StreamExt};
+use hyper::http::request::Parts;
+use hyper::http::Extensions;
+use screw_components::dyn_fn::DFn;
+use screw_components::dyn_result::DError;
+use screw_ws::tokio_tungstenite::tungstenite::Error;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+pub struct ApiChannelOriginContent {
+    pub http_parts: Parts,
+    pub remote_addr: SocketAddr,
+    pub extensions: Arc<Extensions>,
+}
+
+pub trait ApiChannelContent {
+    fn create(origin_content: ApiChannelOriginContent) -> Self;
+}
+
+pub struct ApiChannel<Send, Receive>
+where
+    Send: Serialize + std::marker::Send + 'static,
+    Receive: for<'de> Deserialize<'de> + std::marker::Send + 'static,
+{
+    pub sender: second::ApiChannelSender<Send>,
+    pub receiver: second::ApiChannelReceiver<Receive>,
+}
+
+impl<Send, Receive> ApiChannel<Send, Receive>
+where
+    Send: Serialize + std::marker::Send + 'static,
+    Receive: for<'de> Deserialize<'de> + std::marker::Send + 'static,
+{
+    /// Queues the sender, see `ApiChannelSender::into_queued`, and answers every received
+    /// message `pong_fn` returns a pong for with that pong, hiding the ping from the receiver,
+    /// see `ApiChannelReceiver::and_heartbeat`.
+    pub fn into_heartbeat<HFn>(
+        self,
+        max_pending_messages: usize,
+        pong_fn: HFn,
+    ) -> (
+        second::ApiChannelQueuedSender<Send>,
+        second::ApiChannelReceiver<Receive>,
+    )
+    where
+        HFn: Fn(&Receive) -> Option<Send> + std::marker::Send + Sync + 'static,
+    {
+        let sender = self.sender.into_queued(max_pending_messages);
+        let receiver = self.receiver.and_heartbeat(sender.clone(), pong_fn);
+        (sender, receiver)
+    }
+}
+
+pub enum ApiChannelSenderError {
+    Convert(DError),
+    Tungstenite(Error),
+    Stream(DError),
+    /// The message of `size` bytes was not written, exceeding `max_size`.
+    MessageTooLarge {
+        size: usize,
+        max_size: usize,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiChannelQueuedSenderError {
+    PendingWriteLimitExceeded,
+    Closed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiChannelMessageKind {
+    Text,
+    Binary,
+}
+
+pub enum ApiChannelReceiverError {
+    Convert(DError),
+    Tungstenite(Error),
+    NoMessage,
+    UnsupportedMessage,
+    UnexpectedMessageKind(ApiChannelMessageKind),
+    Closed,
+}
+
+pub mod first {
+    use super::*;
+    use futures::stream::SplitSink;
+    use screw_components::dyn_fn::AsDynFn;
+    use screw_components::dyn_result::DResult;
+    use screw_ws::tokio_tungstenite::tungstenite::Message;
+    use screw_ws::tokio_tungstenite::WebSocketStream;
+    use screw_ws::WebSocketIo;
+    use serde::Serialize;
+    use std::future::Future;
+
+    pub struct ApiChannelSender {
+        sink: SplitSink<WebSocketStream<WebSocketIo>, Message>,
+        max_message_size: Option<usize>,
+    }
+
+    impl ApiChannelSender {
+        pub fn with_sink(sink: SplitSink<WebSocketStream<WebSocketIo>, Message>) -> Self {
+            Self {
+                sink,
+                max_message_size: None,
+            }
+        }
+
+        /// Makes sending a message larger than `max_message_size` bytes fail with
+        /// `MessageTooLarge` instead of writing it, see `max_message_size_of`.
+        pub fn and_max_message_size(mut self, max_message_size: Option<usize>) -> Self {
+            self.max_message_size = max_message_size;
+            self
+        }
+
+        pub fn and_convert_typed_message_fn<Send, HFn, HFut>(
+            self,
+            convert_typed_message_fn: HFn,
+        ) -> second::ApiChannelSender<Send>
+        where
+            Send: Serialize + std::marker::Send + 'static,
+            HFn: Fn(Send) -> HFut + std::marker::Send + Sync + 'static,
+            HFut: Future<Output = DResult<String>> + std::marker::Send + 'static,
+        {
+            second::ApiChannelSender {
+                sink: self.sink,
+                max_message_size: self.max_message_size,
+                pending_conversion: None,
+                pending_message: None,
+                convert_typed_message_fn: second::ConvertTypedMessageFn::Text(
+                    convert_typed_message_fn.to_dyn_fn(),
+                ),
+            }
+        }
+
+        pub fn and_convert_typed_binary_message_fn<Send, HFn, HFut>(
+            self,
+            convert_typed_binary_message_fn: HFn,
+        ) -> second::ApiChannelSender<Send>
+        where
+            Send: Serialize + std::marker::Send + 'static,
+            HFn: Fn(Send) -> HFut + std::marker::Send + Sync + 'static,
+            HFut: Future<Output = DResult<Vec<u8>>> + std::marker::Send + 'static,
+        {
+            second::ApiChannelSender {
+                sink: self.sink,
+                max_message_size: self.max_message_size,
+                pending_conversion: None,
+                pending_message: None,
+                convert_typed_message_fn: second::ConvertTypedMessageFn::Binary(
+                    convert_typed_binary_message_fn.to_dyn_fn(),
+                ),
+            }
+        }
+    }
+
+    /// Largest message the stream is configured for, sent unfragmented and so bound by both
+    /// `max_frame_size` and `max_message_size` of its `WebSocketConfig`.
+    pub fn max_message_size_of(stream: &WebSocketStream<WebSocketIo>) -> Option<usize> {
+        let config = stream.get_config();
+        match (config.max_frame_size, config.max_message_size) {
+            (Some(max_frame_size), Some(max_message_size)) => {
+                Some(max_frame_size.min(max_message_size))
+            }
+            (max_frame_size, max_message_size) => max_frame_size.or(max_message_size),
+        }
+    }
+
+    pub struct ApiChannelReceiver {
+        stream: SplitStream<WebSocketStream<WebSocketIo>>,
+    }
+
+    impl ApiChannelReceiver {
+        pub fn with_stream(stream: SplitStream<WebSocketStream<WebSocketIo>>) -> Self {
+            Self { stream }
+        }
+
+        pub fn and_convert_generic_message_fn<Receive, HFn, HFut>(
+            self,
+            convert_generic_message_fn: HFn,
+        ) -> second::ApiChannelReceiver<Receive>
+        where
+            for<'de> Receive: Deserialize<'de> + std::marker::Send + 'static,
+            HFn: Fn(String) -> HFut + std::marker::Send + Sync + 'static,
+            HFut: Future<Output = DResult<Receive>> + std::marker::Send + 'static,
+        {
+            second::ApiChannelReceiver {
+                stream: self.stream,
+                pending_conversion: None,
+                heartbeat: None,
+                convert_generic_message_fn: second::ConvertGenericMessageFn::Text(
+                    convert_generic_message_fn.to_dyn_fn(),
+                ),
+            }
+        }
+
+        pub fn and_convert_generic_binary_message_fn<Receive, HFn, HFut>(
+            self,
+            convert_generic_binary_message_fn: HFn,
+        ) -> second::ApiChannelReceiver<Receive>
+        where
+            for<'de> Receive: Deserialize<'de> + std::marker::Send + 'static,
+            HFn: Fn(Vec<u8>) -> HFut + std::marker::Send + Sync + 'static,
+            HFut: Future<Output = DResult<Receive>> + std::marker::Send + 'static,
+        {
+            second::ApiChannelReceiver {
+                stream: self.stream,
+                pending_conversion: None,
+                heartbeat: None,
+                convert_generic_message_fn: second::ConvertGenericMessageFn::Binary(
+                    convert_generic_binary_message_fn.to_dyn_fn(),
+                ),
+            }
+        }
+    }
+}
+
+pub mod second {
+    use super::*;
+    use futures::channel::mpsc;
+    use futures::lock::Mutex;
+    use futures::stream::SplitSink;
+    use futures::{future, ready, Sink};
+    use screw_components::dyn_fn::DFuture;
+    use screw_components::dyn_result::DResult;
+    use screw_ws::tokio_tungstenite::tungstenite::Message;
+    use screw_ws::tokio_tungstenite::WebSocketStream;
+    use screw_ws::WebSocketIo;
+    use serde::Serialize;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll};
+
+    pub(super) enum ConvertTypedMessageFn<Send> {
+        Text(DFn<Send, DResult<String>>),
+        Binary(DFn<Send, DResult<Vec<u8>>>),
+    }
+
+    /// Besides its own methods, the sender is a `Sink` of typed messages, failing with the
+    /// same errors as `send`.
+    pub struct ApiChannelSender<Send>
+    where
+        Send: Serialize + std::marker::Send + 'static,
+    {
+        pub(super) sink: SplitSink<WebSocketStream<WebSocketIo>, Message>,
+        pub(super) max_message_size: Option<usize>,
+        // Behind a mutex only to keep the sender `Sync`, it is accessed through `get_mut`
+        pub(super) pending_conversion: Option<Mutex<DFuture<DResult<Message>>>>,
+        pub(super) pending_message: Option<Message>,
+        pub(super) convert_typed_message_fn: ConvertTypedMessageFn<Send>,
+    }
+
+    impl<Send> ApiChannelSender<Send>
+    where
+        Send: Serialize + std::marker::Send + 'static,
+    {
+        pub async fn send(&mut self, typed_message: Send) -> Result<(), ApiChannelSenderError> {
+            SinkExt::send(self, typed_message).await
+        }
+
+        /// Sends every chunk of `stream` as a binary message, split into messages of at most
+        /// `chunk_size` bytes when given, waiting for the sink before pulling the next chunk.
+        /// Stops at the first chunk failing to be produced or sent, or exceeding the maximum
+        /// message size.
+        pub async fn send_stream<S, B, E>(
+            &mut self,
+            stream: S,
+            chunk_size: Option<usize>,
+        ) -> Result<(), ApiChannelSenderError>
+        where
+            S: Stream<Item = Result<B, E>>,
+            B: Into<Vec<u8>>,
+            E: Into<DError>,
+        {
+            let mut stream = std::pin::pin!(stream);
+            while let Some(chunk) = stream.next().await {
+                let chunk: Vec<u8> = chunk
+                    .map_err(|error| ApiChannelSenderError::Stream(error.into()))?
+                    .into();
+                match chunk_size {
+                    Some(chunk_size) if chunk_size > 0 && chunk.len() > chunk_size => {
+                        for part in chunk.chunks(chunk_size) {
+                            self.feed(Message::Binary(part.to_vec())).await?;
+                        }
+                    }
+                    _ => self.feed(Message::Binary(chunk)).await?,
+                }
+            }
+            self.sink
+                .flush()
+                .await
+                .map_err(ApiChannelSenderError::Tungstenite)
+        }
+
+        pub async fn close(&mut self) -> Result<(), ApiChannelSenderError> {
+            self.sink
+                .send(Message::Close(None))
+                .await
+                .map_err(ApiChannelSenderError::Tungstenite)
+        }
+
+        async fn feed(&mut self, generic_message: Message) -> Result<(), ApiChannelSenderError> {
+            future::poll_fn(|cx| self.poll_pending(cx)).await?;
+            let size = generic_message.len();
+            match self.max_message_size {
+                Some(max_size) if size > max_size => {
+                    Err(ApiChannelSenderError::MessageTooLarge { size, max_size })
+                }
+                _ => self
+                    .sink
+                    .feed(generic_message)
+                    .await
+                    .map_err(ApiChannelSenderError::Tungstenite),
+            }
+        }
+    }
+
+    impl<Send> ApiChannelSender<Send>
+    where
+        Send: Serialize + std::marker::Send + 'static,
+    {
+        /// Drives the conversion of a message given to `start_send` and writes it to the sink
+        /// once ready.
+        fn poll_pending(
+            &mut self,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), ApiChannelSenderError>> {
+            if let Some(pending_conversion) = &mut self.pending_conversion {
+                let generic_message_result = ready!(pending_conversion.get_mut().as_mut().poll(cx));
+                self.pending_conversion = None;
+                let generic_message =
+                    generic_message_result.map_err(ApiChannelSenderError::Convert)?;
+                let size = generic_message.len();
+                if let Some(max_size) = self.max_message_size.filter(|max_size| size > *max_size) {
+                    return Poll::Ready(Err(ApiChannelSenderError::MessageTooLarge {
+                        size,
+                        max_size,
+                    }));
+                }
+                self.pending_message = Some(generic_message);
+            }
+            if self.pending_message.is_some() {
+                ready!(self.sink.poll_ready_unpin(cx))
+                    .map_err(ApiChannelSenderError::Tungstenite)?;
+                if let Some(generic_message) = self.pending_message.take() {
+                    self.sink
+                        .start_send_unpin(generic_message)
+                        .map_err(ApiChannelSenderError::Tungstenite)?;
+                }
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<Send> Sink<Send> for ApiChannelSender<Send>
+    where
+        Send: Serialize + std::marker::Send + 'static,
+    {
+        type Error = ApiChannelSenderError;
+
+        fn poll_ready(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            ready!(self.poll_pending(cx))?;
+            self.sink
+                .poll_ready_unpin(cx)
+                .map_err(ApiChannelSenderError::Tungstenite)
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, typed_message: Send) -> Result<(), Self::Error> {
+            let pending_conversion: DFuture<DResult<Message>> = match &self.convert_typed_message_fn
+            {
+                ConvertTypedMessageFn::Text(convert_typed_message_fn) => {
+                    let conversion = convert_typed_message_fn(typed_message);
+                    Box::pin(async move { conversion.await.map(Message::Text) })
+                }
+                ConvertTypedMessageFn::Binary(convert_typed_message_fn) => {
+                    let conversion = convert_typed_message_fn(typed_message);
+                    Box::pin(async move { conversion.await.map(Message::Binary) })
+                }
+            };
+            self.pending_conversion = Some(Mutex::new(pending_conversion));
+            Ok(())
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            ready!(self.poll_pending(cx))?;
+            self.sink
+                .poll_flush_unpin(cx)
+                .map_err(ApiChannelSenderError::Tungstenite)
+        }
+
+        fn poll_close(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            ready!(self.poll_pending(cx))?;
+            self.sink
+                .poll_close_unpin(cx)
+                .map_err(ApiChannelSenderError::Tungstenite)
+        }
+    }
+
+    impl<Send> ApiChannelSender<Send>
+    where
+        Send: Serialize + std::marker::Send + 'static,
+    {
+        /// Moves writing to a background task, returning a sender which queues messages
+        /// without waiting for them to be written. Once `max_pending_messages` messages are
+        /// queued, sending fails with `PendingWriteLimitExceeded` instead of buffering more.
+        /// The limit counts messages whatever their size, so the memory it bounds depends on
+        /// the largest message sent, see `and_max_message_size` to bound that too.
+        /// After a write fails the task stops and sending fails with `Closed`.
+        pub fn into_queued(mut self, max_pending_messages: usize) -> ApiChannelQueuedSender<Send> {
+            let (sender, mut receiver) = mpsc::unbounded::<Send>();
+            let pending_messages = Arc::new(AtomicUsize::new(0));
+            let task_pending_messages = pending_messages.clone();
+            tokio::spawn(async move {
+                while let Some(typed_message) = receiver.next().await {
+                    let result = self.send(typed_message).await;
+                    task_pending_messages.fetch_sub(1, Ordering::AcqRel);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+            });
+            ApiChannelQueuedSender {
+                sender,
+                pending_messages,
+                max_pending_messages,
+            }
+        }
+    }
+
+    pub struct ApiChannelQueuedSender<Send> {
+        sender: mpsc::UnboundedSender<Send>,
+        pending_messages: Arc<AtomicUsize>,
+        max_pending_messages: usize,
+    }
+
+    impl<Send> Clone for ApiChannelQueuedSender<Send> {
+        fn clone(&self) -> Self {
+            Self {
+                sender: self.sender.clone(),
+                pending_messages: self.pending_messages.clone(),
+                max_pending_messages: self.max_pending_messages,
+            }
+        }
+    }
+
+    impl<Send> ApiChannelQueuedSender<Send> {
+        pub fn send(&self, typed_message: Send) -> Result<(), ApiChannelQueuedSenderError> {
+            self.pending_messages
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |pending_messages| {
+                    (pending_messages < self.max_pending_messages).then_some(pending_messages + 1)
+                })
+                .map_err(|_| ApiChannelQueuedSenderError::PendingWriteLimitExceeded)?;
+            self.sender.unbounded_send(typed_message).map_err(|_| {
+                self.pending_messages.fetch_sub(1, Ordering::AcqRel);
+                ApiChannelQueuedSenderError::Closed
+            })
+        }
+
+        pub fn pending_messages(&self) -> usize {
+            self.pending_messages.load(Ordering::Acquire)
+        }
+    }
+
+    type Heartbeat<Receive> = Box<dyn Fn(&Receive) -> bool + std::marker::Send + Sync>;
+
+    pub(super) enum ConvertGenericMessageFn<Receive> {
+        Text(DFn<String, DResult<Receive>>),
+        Binary(DFn<Vec<u8>, DResult<Receive>>),
+    }
+
+    /// Besides `receive`, the receiver is a `Stream` of received messages, each failing on its
+    /// own with the errors of `receive` except `NoMessage` and `Closed`, which end it instead.
+    /// Ping and pong messages are skipped, tungstenite answering pings itself.
+    pub struct ApiChannelReceiver<Receive>
+    where
+        for<'de> Receive: Deserialize<'de> + std::marker::Send + 'static,
+    {
+        pub(super) stream: SplitStream<WebSocketStream<WebSocketIo>>,
+        // Behind a mutex only to keep the receiver `Sync`, it is accessed through `get_mut`
+        pub(super) pending_conversion: Option<Mutex<DFuture<DResult<Receive>>>>,
+        pub(super) heartbeat: Option<Heartbeat<Receive>>,
+        pub(super) convert_generic_message_fn: ConvertGenericMessageFn<Receive>,
+    }
+
+    impl<Receive> ApiChannelReceiver<Receive>
+    where
+        for<'de> Receive: Deserialize<'de> + std::marker::Send + 'static,
+    {
+        pub async fn receive(&mut self) -> Result<Receive, ApiChannelReceiverError> {
+            future::poll_fn(|cx| self.poll_receive(cx)).await
+        }
+
+        /// Answers every received message `pong_fn` returns a pong for by queuing that pong on
+        /// `sender`, skipping the message instead of returning it, so handlers only see real
+        /// messages. A pong failing to be queued is dropped, the connection being closed or
+        /// congested.
+        pub fn and_heartbeat<Send, HFn>(
+            mut self,
+            sender: ApiChannelQueuedSender<Send>,
+            pong_fn: HFn,
+        ) -> Self
+        where
+            Send: std::marker::Send + 'static,
+            HFn: Fn(&Receive) -> Option<Send> + std::marker::Send + Sync + 'static,
+        {
+            self.heartbeat = Some(Box::new(move |typed_message| {
+                match pong_fn(typed_message) {
+                    Some(pong) => {
+                        let _ = sender.send(pong);
+                        true
+                    }
+                    None => false,
+                }
+            }));
+            self
+        }
+
+        fn poll_receive(
+            &mut self,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<Receive, ApiChannelReceiverError>> {
+            loop {
+                let typed_message_result = ready!(self.poll_receive_any(cx));
+                match (&typed_message_result, &self.heartbeat) {
+                    (Ok(typed_message), Some(heartbeat)) if heartbeat(typed_message) => continue,
+                    _ => return Poll::Ready(typed_message_result),
+                }
+            }
+        }
+
+        fn poll_receive_any(
+            &mut self,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<Receive, ApiChannelReceiverError>> {
+            while self.pending_conversion.is_none() {
+                let message_type_result = ready!(self.stream.poll_next_unpin(cx))
+                    .ok_or(ApiChannelReceiverError::NoMessage)?;
+                let message_type =
+                    message_type_result.map_err(ApiChannelReceiverError::Tungstenite)?;
+                let pending_conversion = match (message_type, &self.convert_generic_message_fn) {
+                    (Message::Text(generic_message), ConvertGenericMessageFn::Text(convert_fn)) => {
+                        convert_fn(generic_message)
+                    }
+                    (
+                        Message::Binary(generic_message),
+                        ConvertGenericMessageFn::Binary(convert_fn),
+                    ) => convert_fn(generic_message),
+                    (Message::Text(_), ConvertGenericMessageFn::Binary(_)) => {
+                        return Poll::Ready(Err(ApiChannelReceiverError::UnexpectedMessageKind(
+                            ApiChannelMessageKind::Text,
+                        )))
+                    }
+                    (Message::Binary(_), ConvertGenericMessageFn::Text(_)) => {
+                        return Poll::Ready(Err(ApiChannelReceiverError::UnexpectedMessageKind(
+                            ApiChannelMessageKind::Binary,
+                        )))
+                    }
+                    // Answered by tungstenite or sent in reply to the pings of `WebSocketIo`
+                    (Message::Ping(_) | Message::Pong(_), _) => continue,
+                    (Message::Frame(_), _) => {
+                        return Poll::Ready(Err(ApiChannelReceiverError::UnsupportedMessage))
+                    }
+                    (Message::Close(_), _) => {
+                        return Poll::Ready(Err(ApiChannelReceiverError::Closed))
+                    }
+                };
+                self.pending_conversion = Some(Mutex::new(pending_conversion));
+            }
+            let pending_conversion = self.pending_conversion.as_mut().unwrap();
+            let typed_message_result = ready!(pending_conversion.get_mut().as_mut().poll(cx));
+            self.pending_conversion = None;
+            Poll::Ready(typed_message_result.map_err(ApiChannelReceiverError::Convert))
+        }
+    }
+
+    impl<Receive> Stream for ApiChannelReceiver<Receive>
+    where
+        for<'de> Receive: Deserialize<'de> + std::marker::Send + 'static,
+    {
+        type Item = Result<Receive, ApiChannelReceiverError>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            match ready!(self.poll_receive(cx)) {
+                Err(ApiChannelReceiverError::NoMessage | ApiChannelReceiverError::Closed) => {
+                    Poll::Ready(None)
+                }
+                typed_message_result => Poll::Ready(Some(typed_message_result)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use screw_core::test_support::TestClock;
+    use screw_ws::test_support::web_socket_pair;
+    use screw_ws::tokio_tungstenite::tungstenite::Message;
+    use screw_ws::tokio_tungstenite::WebSocketStream;
+    use screw_ws::WebSocketIo;
+    use std::convert::Infallible;
+    use std::io;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    /// Channel of text messages over `stream`, failing to convert the message `"unconvertible"`.
+    fn channel(
+        stream: WebSocketStream<WebSocketIo>,
+        max_message_size: Option<usize>,
+    ) -> ApiChannel<String, String> {
+        let (sink, stream) = stream.split();
+        ApiChannel {
+            sender: first::ApiChannelSender::with_sink(sink)
+                .and_max_message_size(max_message_size)
+                .and_convert_typed_message_fn(|typed_message: String| async move {
+                    match typed_message.as_str() {
+                        "unconvertible" => Err("unconvertible message".into()),
+                        _ => Ok(typed_message),
+                    }
+                }),
+            receiver: first::ApiChannelReceiver::with_stream(stream)
+                .and_convert_generic_message_fn(
+                    |generic_message| async move { Ok(generic_message) },
+                ),
+        }
+    }
+
+    /// Next message the client end of a connection receives.
+    async fn next_message<S>(stream: &mut WebSocketStream<S>) -> Message
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        stream.next().await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn heartbeat_answers_pings_and_skips_pongs() {
+        let clock = TestClock::new();
+        let (mut client, server) =
+            web_socket_pair(None, Some(Duration::from_secs(10)), &clock).await;
+        let (sender, mut receiver) = channel(server, None).into_heartbeat(8, |typed_message| {
+            (typed_message == "ping").then(|| "pong".to_owned())
+        });
+        let (received_sender, mut received_receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(typed_message_result) = receiver.next().await {
+                let _ = received_sender.send(typed_message_result.ok());
+            }
+        });
+
+        client.send(Message::Text("ping".to_owned())).await.unwrap();
+        assert_eq!(
+            next_message(&mut client).await,
+            Message::Text("pong".to_owned())
+        );
+
+        // The pong the client answers the ping of `WebSocketIo` with is skipped as well
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(next_message(&mut client).await, Message::Ping(Vec::new()));
+        client
+            .send(Message::Text("message".to_owned()))
+            .await
+            .unwrap();
+        assert_eq!(
+            received_receiver.recv().await,
+            Some(Some("message".to_owned()))
+        );
+        assert_eq!(sender.pending_messages(), 0);
+    }
+
+    #[tokio::test]
+    async fn queued_sender_limits_pending_messages() {
+        let clock = TestClock::new();
+        let (mut client, server) = web_socket_pair(None, None, &clock).await;
+        let sender = channel(server, None).sender.into_queued(2);
+
+        assert_eq!(sender.send("first".to_owned()), Ok(()));
+        assert_eq!(sender.send("second".to_owned()), Ok(()));
+        assert_eq!(
+            sender.send("third".to_owned()),
+            Err(ApiChannelQueuedSenderError::PendingWriteLimitExceeded)
+        );
+        assert_eq!(sender.pending_messages(), 2);
+        for text in ["first", "second"] {
+            assert_eq!(
+                next_message(&mut client).await,
+                Message::Text(text.to_owned())
+            );
+        }
+
+        assert_eq!(sender.send("unconvertible".to_owned()), Ok(()));
+        while sender.pending_messages() > 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            sender.send("fourth".to_owned()),
+            Err(ApiChannelQueuedSenderError::Closed)
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_streams_in_chunks() {
+        let clock = TestClock::new();
+        let (mut client, server) = web_socket_pair(None, None, &clock).await;
+        let mut sender = channel(server, None).sender;
+
+        let chunks = stream::iter([Ok::<_, Infallible>(b"abcde".to_vec()), Ok(b"f".to_vec())]);
+        assert!(matches!(sender.send_stream(chunks, Some(2)).await, Ok(())));
+        for chunk in ["ab", "cd", "e", "f"] {
+            assert_eq!(
+                next_message(&mut client).await,
+                Message::Binary(chunk.as_bytes().to_vec())
+            );
+        }
+
+        let chunks = stream::iter([
+            Ok(b"g".to_vec()),
+            Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+            Ok(b"h".to_vec()),
+        ]);
+        assert!(matches!(
+            sender.send_stream(chunks, None).await,
+            Err(ApiChannelSenderError::Stream(_))
+        ));
+        assert!(matches!(sender.close().await, Ok(())));
+        assert_eq!(
+            next_message(&mut client).await,
+            Message::Binary(b"g".to_vec())
+        );
+        assert!(matches!(next_message(&mut client).await, Message::Close(_)));
+    }
+
+    #[tokio::test]
+    async fn refuses_messages_too_large() {
+        let clock = TestClock::new();
+        let (mut client, server) = web_socket_pair(None, None, &clock).await;
+        let mut sender = channel(server, Some(3)).sender;
+
+        assert!(matches!(
+            sender.send("abcd".to_owned()).await,
+            Err(ApiChannelSenderError::MessageTooLarge {
+                size: 4,
+                max_size: 3
+            })
+        ));
+        let chunks = stream::iter([Ok::<_, Infallible>(b"abcd".to_vec())]);
+        assert!(matches!(
+            sender.send_stream(chunks, None).await,
+            Err(ApiChannelSenderError::MessageTooLarge {
+                size: 4,
+                max_size: 3
+            })
+        ));
+        assert!(matches!(sender.send("abc".to_owned()).await, Ok(())));
+        assert_eq!(
+            next_message(&mut client).await,
+            Message::Text("abc".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn sender_is_sink_and_receiver_is_stream() {
+        let clock = TestClock::new();
+        let (mut client, server) = web_socket_pair(None, None, &clock).await;
+        let ApiChannel {
+            mut sender,
+            mut receiver,
+        } = channel(server, None);
+
+        let mut typed_messages = stream::iter(["first".to_owned(), "second".to_owned()]).map(Ok);
+        assert!(matches!(sender.send_all(&mut typed_messages).await, Ok(())));
+        for text in ["first", "second"] {
+            assert_eq!(
+                next_message(&mut client).await,
+                Message::Text(text.to_owned())
+            );
+        }
+
+        client
+            .send(Message::Text("third".to_owned()))
+            .await
+            .unwrap();
+        client.send(Message::Close(None)).await.unwrap();
+        assert!(matches!(
+            receiver.next().await,
+            Some(Ok(typed_message)) if typed_message == "third"
+        ));
+        assert!(receiver.next().await.is_none());
+    }
+}
@@ -0,0 +1,120 @@
+use super::super::json::JsonRenameCase;
+use super::channel;
+use futures::{future, StreamExt};
+use screw_ws::tokio_tungstenite::WebSocketStream;
+use screw_ws::{WebSocketHandshake, WebSocketIo, WebSocketStreamConverter};
+use serde::Deserialize;
+use serde::Serialize;
+
+fn to_string<T: Serialize + ?Sized>(value: &T, pretty_printed: bool) -> serde_json::Result<String> {
+    if pretty_printed {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonApiStreamConverter {
+    pub pretty_printed: bool,
+    pub rename_case: Option<JsonRenameCase>,
+}
+
+#[async_trait]
+impl<Send, Receive> WebSocketStreamConverter<channel::ApiChannel<Send, Receive>>
+    for JsonApiStreamConverter
+where
+    Send: Serialize + std::marker::Send + 'static,
+    Receive: for<'de> Deserialize<'de> + std::marker::Send + 'static,
+{
+    async fn convert_stream(
+        &self,
+        _handshake: WebSocketHandshake,
+        stream: WebSocketStream<WebSocketIo>,
+    ) -> channel::ApiChannel<Send, Receive> {
+        let max_message_size = channel::first::max_message_size_of(&stream);
+        let (sink, stream) = stream.split();
+        let pretty_printed = self.pretty_printed;
+        let rename_case = self.rename_case;
+
+        let sender = channel::first::ApiChannelSender::with_sink(sink)
+            .and_max_message_size(max_message_size)
+            .and_convert_typed_message_fn(move |typed_message| {
+                let generic_message_result = match rename_case {
+                    Some(rename_case) => serde_json::to_value(&typed_message)
+                        .map(|value| rename_case.rename_outgoing(value))
+                        .and_then(|value| to_string(&value, pretty_printed)),
+                    None => to_string(&typed_message, pretty_printed),
+                };
+                future::ready(generic_message_result.map_err(|e| e.into()))
+            });
+
+        let receiver = channel::first::ApiChannelReceiver::with_stream(stream)
+            .and_convert_generic_message_fn(move |generic_message| {
+                let typed_message_result = match rename_case {
+                    Some(rename_case) => serde_json::from_str(generic_message.as_str())
+                        .map(|value| rename_case.rename_incoming(value))
+                        .and_then(serde_json::from_value),
+                    None => serde_json::from_str(generic_message.as_str()),
+                };
+                future::ready(typed_message_result.map_err(|e| e.into()))
+            });
+
+        channel::ApiChannel { sender, receiver }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::SinkExt;
+    use screw_core::test_support::TestClock;
+    use screw_ws::test_support::web_socket_pair;
+    use screw_ws::tokio_tungstenite::tungstenite::Message;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Data {
+        user_id: u32,
+    }
+
+    /// Text the client receives for `Data` sent through a channel of `converter`, and the
+    /// `Data` the channel receives for the text sent back.
+    async fn exchange(converter: JsonApiStreamConverter, text: &str) -> (String, Data) {
+        let (mut client, server) = web_socket_pair(None, None, &TestClock::new()).await;
+        let handshake = WebSocketHandshake {
+            uri: Default::default(),
+            headers: Default::default(),
+            remote_addr: ([127, 0, 0, 1], 0).into(),
+            subprotocol: None,
+        };
+        let channel::ApiChannel::<Data, Data> {
+            mut sender,
+            mut receiver,
+        } = converter.convert_stream(handshake, server).await;
+        assert!(matches!(sender.send(Data { user_id: 1 }).await, Ok(())));
+        let sent = client.next().await.unwrap().unwrap().into_text().unwrap();
+        client.send(Message::Text(text.to_owned())).await.unwrap();
+        let received = match receiver.receive().await {
+            Ok(data) => data,
+            Err(_) => panic!("message not received"),
+        };
+        (sent, received)
+    }
+
+    #[tokio::test]
+    async fn converts_messages() {
+        assert_eq!(
+            exchange(JsonApiStreamConverter::default(), r#"{"user_id":2}"#).await,
+            (r#"{"user_id":1}"#.to_owned(), Data { user_id: 2 })
+        );
+        let converter = JsonApiStreamConverter {
+            pretty_printed: true,
+            rename_case: Some(JsonRenameCase::CamelCase),
+        };
+        assert_eq!(
+            exchange(converter, r#"{"userId":2}"#).await,
+            ("{\n  \"userId\": 1\n}".to_owned(), Data { user_id: 2 })
+        );
+    }
+}
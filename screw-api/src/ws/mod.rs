@@ -0,0 +1,32 @@
+pub mod channel;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "xml")]
+mod xml;
+
+#[cfg(feature = "json")]
+pub use json::*;
+pub use screw_ws::{
+    select_subprotocol, tokio_tungstenite, validate_handshake, validate_handshake_with,
+    WebSocketConnections, WebSocketContent, WebSocketHandshake, WebSocketIo,
+    WebSocketMiddlewareConfig, WebSocketMiddlewareConverter, WebSocketOriginContent,
+    WebSocketRequest, WebSocketResponse, WebSocketStreamConverter, WebSocketUpgrade,
+};
+#[cfg(feature = "xml")]
+pub use xml::*;
+
+#[cfg(test)]
+#[allow(deprecated)]
+mod tests {
+    #[test]
+    fn deprecated_paths_still_resolve() {
+        let _ = super::super::channel::ApiChannelMessageKind::Text;
+        #[cfg(feature = "json")]
+        let _: super::JsonApiStreamConverter = super::super::json::JsonApiStreamConverter {
+            pretty_printed: false,
+            rename_case: None,
+        };
+        #[cfg(feature = "xml")]
+        let _: super::XmlApiStreamConverter = super::super::xml::XmlApiWebSocketConverter;
+    }
+}
@@ -1,28 +1,30 @@
-use super::super::*;
+use super::channel;
 use futures::{future, StreamExt};
-use hyper::upgrade::Upgraded;
-use screw_ws::WebSocketStreamConverter;
+use screw_ws::tokio_tungstenite::WebSocketStream;
+use screw_ws::{WebSocketHandshake, WebSocketIo, WebSocketStreamConverter};
 use serde::Deserialize;
 use serde::Serialize;
-use tokio_tungstenite::WebSocketStream;
 
 #[derive(Clone, Copy, Debug)]
-pub struct XmlApiWebSocketConverter;
+pub struct XmlApiStreamConverter;
 
 #[async_trait]
 impl<Send, Receive> WebSocketStreamConverter<channel::ApiChannel<Send, Receive>>
-    for XmlApiWebSocketConverter
+    for XmlApiStreamConverter
 where
     Send: Serialize + std::marker::Send + 'static,
     Receive: for<'de> Deserialize<'de> + std::marker::Send + 'static,
 {
     async fn convert_stream(
         &self,
-        stream: WebSocketStream<Upgraded>,
+        _handshake: WebSocketHandshake,
+        stream: WebSocketStream<WebSocketIo>,
     ) -> channel::ApiChannel<Send, Receive> {
+        let max_message_size = channel::first::max_message_size_of(&stream);
         let (sink, stream) = stream.split();
 
         let sender = channel::first::ApiChannelSender::with_sink(sink)
+            .and_max_message_size(max_message_size)
             .and_convert_typed_message_fn(move |typed_message| {
                 let generic_message_result = quick_xml::se::to_string(&typed_message);
                 future::ready(generic_message_result.map_err(|e| e.into()))
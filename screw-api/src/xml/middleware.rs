@@ -1,17 +1,133 @@
 use super::super::*;
+use hyper::body::Bytes;
 use hyper::http::request::Parts;
-use hyper::{body, header, Body, StatusCode};
-use response::ApiResponseContentBase;
+use hyper::{header, Body, HeaderMap, StatusCode};
+use response::{ApiResponseContentBase, ApiResponseConverter};
 use screw_components::dyn_fn::DFnOnce;
 use screw_components::dyn_result::DResult;
 use screw_core::request::Request;
 use screw_core::response::Response;
 use screw_core::routing::middleware::Middleware;
 use screw_core::routing::router::RoutedRequest;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
 
-#[derive(Clone, Copy, Debug)]
-pub struct XmlApiMiddlewareConverter;
+#[derive(Clone, Copy, Debug, Default)]
+pub struct XmlApiMiddlewareConverter {
+    /// Responds rejections made before the handler is called, such as unsupported charsets,
+    /// in place of the default `<failure>` XML body. Defaults to `None`.
+    pub rejection_response: Option<fn(rejection::ApiRejection) -> Response>,
+    /// Limits on request bodies of unknown length, see `ChunkedBodyLimits`. Unlimited by
+    /// default.
+    pub chunked_limits: request::ChunkedBodyLimits,
+    /// Whether `api request phases` events are emitted, as with
+    /// `JsonApiMiddlewareConfig::phase_timings`. Defaults to `false`.
+    pub phase_timings: bool,
+}
+
+impl XmlApiMiddlewareConverter {
+    pub fn serialize<Success, Failure>(
+        &self,
+        content: &response::ApiResponseContent<Success, Failure>,
+    ) -> DResult<(StatusCode, Bytes)>
+    where
+        Success: response::ApiResponseContentSuccess,
+        Success::Data: Serialize,
+        Failure: response::ApiResponseContentFailure,
+    {
+        let status_code = *content.status_code();
+        let xml_string = quick_xml::se::to_string(content)?;
+        Ok((status_code, Bytes::from(xml_string)))
+    }
+}
+
+impl ApiResponseConverter for XmlApiMiddlewareConverter {
+    fn convert_response<Success, Failure>(
+        &self,
+        api_response: response::ApiResponse<Success, Failure>,
+    ) -> Response
+    where
+        Success: response::ApiResponseContentSuccess,
+        Success::Data: Serialize,
+        Failure: response::ApiResponseContentFailure,
+    {
+        api_response.content.into_response(|content| {
+            let (status_code, xml_bytes) = self.serialize(content)?;
+            Ok((status_code, "application/xml", xml_bytes))
+        })
+    }
+}
+
+#[async_trait]
+impl<Data> request::ApiRequestConverter<Data> for XmlApiMiddlewareConverter
+where
+    for<'de> Data: Deserialize<'de> + Send + 'static,
+{
+    const NAME: &'static str = "xml";
+
+    fn phase_timings(&self) -> bool {
+        self.phase_timings
+    }
+
+    fn rejection_response(&self) -> Option<fn(rejection::ApiRejection) -> Response> {
+        self.rejection_response
+    }
+
+    fn respond_rejection(&self, rejection: rejection::ApiRejection) -> Response {
+        self.convert_response(response::ApiResponse::<Infallible, _>::failure(rejection))
+    }
+
+    async fn convert_request(
+        &self,
+        http_parts: &Parts,
+        http_body: Body,
+    ) -> Result<(DResult<Data>, Option<HeaderMap>), rejection::ApiRejection> {
+        async fn convert<Data>(
+            parts: &Parts,
+            body: Body,
+            chunked_limits: request::ChunkedBodyLimits,
+            charset: charset::RequestCharset,
+            trailers: &mut Option<HeaderMap>,
+        ) -> DResult<Data>
+        where
+            for<'de> Data: Deserialize<'de>,
+        {
+            let content_type = match parts.headers.get(header::CONTENT_TYPE) {
+                Some(header_value) => Some(header_value.to_str()?),
+                None => None,
+            };
+            let media_type = content_type.map(|content_type| {
+                content_type
+                    .split_once(';')
+                    .map_or(content_type, |(media_type, _)| media_type)
+                    .trim()
+            });
+            match media_type {
+                Some("") | None => Err(rejection::ApiRejection::ContentTypeMissed),
+                Some(media_type) if media_type.eq_ignore_ascii_case("application/xml") => Ok(()),
+                Some(_) => Err(rejection::ApiRejection::ContentTypeIncorrect),
+            }?;
+            let (bytes, body_trailers) = chunked_limits.read(body).await?;
+            *trailers = body_trailers;
+            let bytes = charset.decode(bytes)?;
+            let xml_string = String::from_utf8(bytes.to_vec())?;
+            let data = quick_xml::de::from_str(xml_string.as_str())?;
+            Ok(data)
+        }
+
+        let charset = charset::RequestCharset::from_parts(http_parts)?;
+        let mut trailers = None;
+        let data_result = convert(
+            http_parts,
+            http_body,
+            self.chunked_limits,
+            charset,
+            &mut trailers,
+        )
+        .await;
+        Ok((data_result, trailers))
+    }
+}
 
 #[async_trait]
 impl<RqContent, Extensions, RsContentSuccess, RsContentFailure>
@@ -21,9 +137,11 @@ impl<RqContent, Extensions, RsContentSuccess, RsContentFailure>
     > for XmlApiMiddlewareConverter
 where
     RqContent: request::ApiRequestContent<Extensions> + Send + 'static,
-    <RqContent as request::ApiRequestContent<Extensions>>::Data: Sync + Send + 'static,
+    <RqContent as request::ApiRequestContent<Extensions>>::Data:
+        for<'de> Deserialize<'de> + Sync + Send + 'static,
     Extensions: Sync + Send + 'static,
     RsContentSuccess: response::ApiResponseContentSuccess + Send + 'static,
+    <RsContentSuccess as response::ApiResponseContentSuccess>::Data: Serialize,
     RsContentFailure: response::ApiResponseContentFailure + Send + 'static,
 {
     type Request = RoutedRequest<Request<Extensions>>;
@@ -36,67 +154,9 @@ where
             response::ApiResponse<RsContentSuccess, RsContentFailure>,
         >,
     ) -> Response {
-        async fn convert<Data>(parts: &Parts, body: Body) -> DResult<Data>
-        where
-            for<'de> Data: Deserialize<'de>,
-        {
-            let content_type = match parts.headers.get(header::CONTENT_TYPE) {
-                Some(header_value) => Some(header_value.to_str()?),
-                None => None,
-            };
-            match content_type {
-                Some("application/xml") => Ok(()),
-                Some("") | None => Err(ApiRequestContentTypeError::Missed),
-                Some(_) => Err(ApiRequestContentTypeError::Incorrect),
-            }?;
-            let bytes = body::to_bytes(body).await?;
-            let xml_string = String::from_utf8(bytes.to_vec())?;
-            let data = quick_xml::de::from_str(xml_string.as_str())?;
-            Ok(data)
-        }
-
-        let (http_parts, http_body) = routed_request.origin.http.into_parts();
-        let data_result = convert(&http_parts, http_body).await;
-
-        let request_content = RqContent::create(request::ApiRequestOriginContent {
-            path: routed_request.path,
-            query: routed_request.query,
-            http_parts,
-            remote_addr: routed_request.origin.remote_addr,
-            extensions: routed_request.origin.extensions,
-            data_result,
-        });
-
-        let api_request = request::ApiRequest {
-            content: request_content,
-            _p_e: Default::default(),
-        };
-
-        let api_response = next(api_request).await;
-
-        let http_response_result: DResult<hyper::Response<Body>> = (|| {
-            let content = api_response.content;
-
-            let status_code = content.status_code();
-            let xml_string = quick_xml::se::to_string(&content)?;
-
-            let response = hyper::Response::builder()
-                .status(status_code)
-                .header(header::CONTENT_TYPE, "application/xml")
-                .body(Body::from(xml_string))?;
-
-            Ok(response)
-        })();
-
-        let http_response = http_response_result.unwrap_or_else(|_| {
-            hyper::Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Body::empty())
-                .unwrap()
-        });
-
-        Response {
-            http: http_response,
-        }
+        request::respond(self, routed_request, next, |api_response| {
+            self.convert_response(api_response)
+        })
+        .await
     }
 }
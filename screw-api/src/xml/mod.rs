@@ -1,7 +1,13 @@
 mod middleware;
-#[cfg(feature = "ws")]
-mod stream_converter;
 
 pub use middleware::*;
+
+#[cfg(feature = "ws")]
+#[deprecated(note = "renamed to `ws::XmlApiStreamConverter`")]
+pub type XmlApiWebSocketConverter = super::ws::XmlApiStreamConverter;
+
 #[cfg(feature = "ws")]
-pub use stream_converter::*;
+#[deprecated(note = "renamed to `ws::XmlApiStreamConverter`")]
+#[allow(non_upper_case_globals)]
+pub const XmlApiWebSocketConverter: super::ws::XmlApiStreamConverter =
+    super::ws::XmlApiStreamConverter;
@@ -0,0 +1,156 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use hyper::{Body, Method, StatusCode};
+use screw_core::request::Request;
+use screw_core::responder_factory::ResponderFactory;
+use screw_core::response::Response;
+use screw_core::routing::route::first::Route;
+use screw_core::routing::router::{self, RoutedRequest};
+use screw_core::server::{Responder, ResponderFactory as _};
+use std::net::SocketAddr;
+use tokio::runtime::Runtime;
+
+const RESOURCES: [&str; 8] = [
+    "users",
+    "teams",
+    "projects",
+    "issues",
+    "comments",
+    "labels",
+    "milestones",
+    "webhooks",
+];
+
+async fn handler(_request: RoutedRequest<Request<()>>) -> Response {
+    Response {
+        http: hyper::Response::new(Default::default()),
+    }
+}
+
+async fn fallback_handler(request: RoutedRequest<Request<()>>) -> Response {
+    let status_code = request
+        .fallback_reason()
+        .map_or(StatusCode::NOT_FOUND, |fallback_reason| {
+            fallback_reason.status_code()
+        });
+    let mut http = hyper::Response::new(Default::default());
+    *http.status_mut() = status_code;
+    Response { http }
+}
+
+/// Builds a REST-like API of collection, item and nested routes for every resource, scoped
+/// under `/api/v1`, with a few top-level routes.
+fn router() -> router::second::Router<Request<()>, Response> {
+    router::first::Router::with_fallback_handler(fallback_handler).and_routes(|routes| {
+        routes
+            .route(
+                Route::with_method(&Method::GET)
+                    .and_path("/")
+                    .and_handler(handler),
+            )
+            .route(
+                Route::with_method(&Method::GET)
+                    .and_path("/healthz")
+                    .and_handler(handler),
+            )
+            .scoped("/api/v1", |mut routes| {
+                for resource in RESOURCES {
+                    routes = routes
+                        .route(
+                            Route::with_methods([&Method::GET, &Method::POST])
+                                .and_path(format!("/{resource}"))
+                                .and_handler(handler),
+                        )
+                        .route(
+                            Route::with_methods([&Method::GET, &Method::PUT, &Method::DELETE])
+                                .and_path(format!("/{resource}/{{id}}"))
+                                .and_handler(handler),
+                        )
+                        .route(
+                            Route::with_method(&Method::GET)
+                                .and_path(format!("/{resource}/{{id}}/events"))
+                                .and_handler(handler),
+                        );
+                }
+                routes
+            })
+    })
+}
+
+fn request(method: &Method, uri: &str) -> Request<()> {
+    Request::with_http(
+        hyper::Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap(),
+    )
+}
+
+fn bench_process(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let router = router();
+    let cases = [
+        ("hit_static", Method::GET, "/healthz"),
+        ("hit_first_resource", Method::GET, "/api/v1/users"),
+        (
+            "hit_last_resource",
+            Method::GET,
+            "/api/v1/webhooks/42/events",
+        ),
+        (
+            "hit_query",
+            Method::GET,
+            "/api/v1/issues/42?expand=labels&page=2",
+        ),
+        ("miss", Method::GET, "/api/v1/unknown/42"),
+        ("method_not_allowed", Method::PATCH, "/api/v1/webhooks/42"),
+    ];
+
+    let mut group = c.benchmark_group("router_process");
+    for (name, method, uri) in &cases {
+        group.bench_with_input(BenchmarkId::from_parameter(name), uri, |b, uri| {
+            b.to_async(&runtime)
+                .iter(|| router.process(request(method, uri)));
+        });
+    }
+    group.finish();
+
+    c.bench_function("router_process_mix", |b| {
+        b.to_async(&runtime).iter(|| async {
+            for (_, method, uri) in &cases {
+                router.process(request(method, uri)).await;
+            }
+        });
+    });
+}
+
+fn http_request(uri: &str) -> hyper::Request<Body> {
+    hyper::Request::builder()
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Same as `bench_process` through the responder the server drives, covering what it adds
+/// per connection and per request on top of routing.
+fn bench_responder(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let responder_factory = ResponderFactory::with_router(router()).and_extensions(());
+    let remote_addr = SocketAddr::from(([127, 0, 0, 1], 8080));
+
+    c.bench_function("responder_request", |b| {
+        let mut responder = responder_factory.make_responder(remote_addr);
+        b.to_async(&runtime)
+            .iter(|| responder.response(http_request("/api/v1/users")));
+    });
+
+    c.bench_function("responder_connection", |b| {
+        b.to_async(&runtime).iter(|| {
+            let mut responder = responder_factory.make_responder(remote_addr);
+            responder.response(http_request("/api/v1/users"))
+        });
+    });
+}
+
+criterion_group!(benches, bench_process, bench_responder);
+criterion_main!(benches);
@@ -0,0 +1,396 @@
+use super::*;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use hyper::body::{Bytes, Sender};
+use hyper::header::{self, HeaderValue};
+use hyper::StatusCode;
+use screw_components::dyn_result::DResult;
+use std::io::{self, Write};
+use std::time::SystemTime;
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+/// Version 2.0, the first with deflate and data descriptors.
+const VERSION: u16 = 20;
+/// Sizes and CRC follow the data in a data descriptor, and names are UTF-8.
+const FLAGS: u16 = 0x0008 | 0x0800;
+const METHOD_DEFLATE: u16 = 8;
+
+/// Builds a response streaming a zip archive, see `ZipSender`.
+#[derive(Default)]
+pub struct ZipResponseBuilder {
+    status: StatusCode,
+    file_name: Option<String>,
+}
+
+impl ZipResponseBuilder {
+    pub fn and_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Name the client saves the archive under, sent as `Content-Disposition: attachment`.
+    pub fn and_file_name<N: Into<String>>(mut self, file_name: N) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    /// Returns the response streaming the files sent through the returned sender, which should
+    /// be moved to a task writing files while the response is being returned.
+    pub fn build(self) -> (response::Response, ZipSender) {
+        let (sender, body) = response::ResponseBody::channel();
+        let mut http = hyper::Response::builder()
+            .status(self.status)
+            .header(header::CONTENT_TYPE, "application/zip");
+        if let Some(file_name) = self.file_name {
+            let content_disposition = format!(
+                "attachment; filename=\"{}\"",
+                file_name.replace('\\', "\\\\").replace('"', "\\\"")
+            );
+            if let Ok(content_disposition) = HeaderValue::from_str(&content_disposition) {
+                http = http.header(header::CONTENT_DISPOSITION, content_disposition);
+            }
+        }
+        (
+            response::Response {
+                http: http.body(body).unwrap(),
+            },
+            ZipSender {
+                sender: Some(sender),
+                offset: 0,
+                entries: Vec::new(),
+                file: None,
+            },
+        )
+    }
+}
+
+struct ZipEntry {
+    name: String,
+    modified: (u16, u16),
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+struct ZipFile {
+    entry: ZipEntry,
+    encoder: DeflateEncoder<Vec<u8>>,
+    crc: Crc,
+    size: u64,
+    compressed_size: u64,
+}
+
+/// Writes the files of a zip archive response, deflating them as they are written, each
+/// file being flushed once written. Sizes are only known once a file is written, so they
+/// follow its data in a data descriptor, which zip readers going through the central
+/// directory at the end of the archive all support.
+///
+/// Archives are limited to 4 GiB and 65535 files, as ZIP64 is not supported. Dropping the
+/// sender before `finish` aborts the response body, so a client never sees a truncated
+/// archive as a complete one.
+pub struct ZipSender {
+    sender: Option<Sender>,
+    offset: u64,
+    entries: Vec<ZipEntry>,
+    file: Option<ZipFile>,
+}
+
+impl ZipSender {
+    /// Writes a whole file named `name`, a `/` separated path within the archive.
+    pub async fn send_file<B: Into<Bytes>>(&mut self, name: &str, data: B) -> DResult<()> {
+        self.start_file(name).await?;
+        self.send_file_data(data).await?;
+        self.finish_file().await
+    }
+
+    /// Starts a file named `name` whose data is then written with `send_file_data`, for
+    /// files too large to hold in memory. The file ends when the next one starts or the
+    /// archive is finished.
+    pub async fn start_file(&mut self, name: &str) -> DResult<()> {
+        self.finish_file().await?;
+        if self.entries.len() >= u16::MAX as usize {
+            return Err(too_large_error());
+        }
+        let entry = ZipEntry {
+            name: name.to_owned(),
+            modified: dos_date_time(SystemTime::now()),
+            crc: 0,
+            compressed_size: 0,
+            size: 0,
+            offset: self.offset.try_into().map_err(|_| too_large_error())?,
+        };
+        let mut header = Vec::with_capacity(30 + entry.name.len());
+        put_u32(&mut header, LOCAL_FILE_HEADER_SIGNATURE);
+        put_u16(&mut header, VERSION);
+        put_u16(&mut header, FLAGS);
+        put_u16(&mut header, METHOD_DEFLATE);
+        put_u16(&mut header, entry.modified.1);
+        put_u16(&mut header, entry.modified.0);
+        // CRC, compressed size and size, given by the data descriptor
+        header.extend_from_slice(&[0; 12]);
+        put_u16(&mut header, name_len(&entry.name)?);
+        put_u16(&mut header, 0);
+        header.extend_from_slice(entry.name.as_bytes());
+        self.file = Some(ZipFile {
+            entry,
+            encoder: DeflateEncoder::new(header, Compression::default()),
+            crc: Crc::new(),
+            size: 0,
+            compressed_size: 0,
+        });
+        Ok(())
+    }
+
+    /// Writes `data` to the file started by `start_file`, sending what the encoder has
+    /// output so far.
+    pub async fn send_file_data<B: Into<Bytes>>(&mut self, data: B) -> DResult<()> {
+        let data = data.into();
+        let file = self.file.as_mut().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no zip file was started")
+        })?;
+        file.crc.update(&data);
+        file.size += data.len() as u64;
+        file.encoder.write_all(&data)?;
+        let output = std::mem::take(file.encoder.get_mut());
+        if output.is_empty() {
+            return Ok(());
+        }
+        file.compressed_size += output.len() as u64;
+        self.send(Bytes::from(output)).await
+    }
+
+    /// Writes the central directory after the last file, ending the archive.
+    pub async fn finish(mut self) -> DResult<()> {
+        self.finish_file().await?;
+        let central_directory_offset: u32 =
+            self.offset.try_into().map_err(|_| too_large_error())?;
+        let mut central_directory = Vec::new();
+        for entry in &self.entries {
+            put_u32(&mut central_directory, CENTRAL_DIRECTORY_HEADER_SIGNATURE);
+            put_u16(&mut central_directory, VERSION);
+            put_u16(&mut central_directory, VERSION);
+            put_u16(&mut central_directory, FLAGS);
+            put_u16(&mut central_directory, METHOD_DEFLATE);
+            put_u16(&mut central_directory, entry.modified.1);
+            put_u16(&mut central_directory, entry.modified.0);
+            put_u32(&mut central_directory, entry.crc);
+            put_u32(&mut central_directory, entry.compressed_size);
+            put_u32(&mut central_directory, entry.size);
+            put_u16(&mut central_directory, name_len(&entry.name)?);
+            // Extra field and comment lengths, disk number, internal and external attributes
+            central_directory.extend_from_slice(&[0; 12]);
+            put_u32(&mut central_directory, entry.offset);
+            central_directory.extend_from_slice(entry.name.as_bytes());
+        }
+        let central_directory_size: u32 = central_directory
+            .len()
+            .try_into()
+            .map_err(|_| too_large_error())?;
+        put_u32(&mut central_directory, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        // Number of this disk and of the disk with the central directory
+        put_u32(&mut central_directory, 0);
+        put_u16(&mut central_directory, self.entries.len() as u16);
+        put_u16(&mut central_directory, self.entries.len() as u16);
+        put_u32(&mut central_directory, central_directory_size);
+        put_u32(&mut central_directory, central_directory_offset);
+        put_u16(&mut central_directory, 0);
+        self.send(Bytes::from(central_directory)).await?;
+        self.sender.take();
+        Ok(())
+    }
+
+    async fn finish_file(&mut self) -> DResult<()> {
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        let mut entry = file.entry;
+        let mut output = file.encoder.finish()?;
+        let compressed_size = file.compressed_size + output.len() as u64;
+        entry.crc = file.crc.sum();
+        entry.size = file.size.try_into().map_err(|_| too_large_error())?;
+        // The local file header was output along with the first data
+        entry.compressed_size = (compressed_size - local_file_header_len(&entry))
+            .try_into()
+            .map_err(|_| too_large_error())?;
+        put_u32(&mut output, DATA_DESCRIPTOR_SIGNATURE);
+        put_u32(&mut output, entry.crc);
+        put_u32(&mut output, entry.compressed_size);
+        put_u32(&mut output, entry.size);
+        self.entries.push(entry);
+        self.send(Bytes::from(output)).await
+    }
+
+    async fn send(&mut self, chunk: Bytes) -> DResult<()> {
+        self.offset += chunk.len() as u64;
+        match &mut self.sender {
+            Some(sender) => Ok(sender.send_data(chunk).await?),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ZipSender {
+    fn drop(&mut self) {
+        if let Some(sender) = self.sender.take() {
+            sender.abort();
+        }
+    }
+}
+
+fn local_file_header_len(entry: &ZipEntry) -> u64 {
+    30 + entry.name.len() as u64
+}
+
+fn name_len(name: &str) -> DResult<u16> {
+    name.len().try_into().map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput, "zip file name is too long").into()
+    })
+}
+
+fn too_large_error() -> screw_components::dyn_result::DError {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "zip archive exceeds 4 GiB or 65535 files, ZIP64 is not supported",
+    )
+    .into()
+}
+
+fn put_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Returns the MS-DOS date and time of `time` in UTC, as zip headers store them, read off
+/// its HTTP date `Tue, 10 Oct 2000 13:55:36 GMT`.
+fn dos_date_time(time: SystemTime) -> (u16, u16) {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let http_date = httpdate::fmt_http_date(time);
+    let field = |range: std::ops::Range<usize>| http_date[range].parse::<u16>().unwrap_or(0);
+    let month = MONTHS
+        .iter()
+        .position(|month| *month == &http_date[8..11])
+        .map_or(1, |index| index as u16 + 1);
+    let date = (field(12..16).saturating_sub(1980) << 9) | (month << 5) | field(5..7);
+    let time = (field(17..19) << 11) | (field(20..22) << 5) | (field(23..25) / 2);
+    (date, time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::DeflateDecoder;
+    use std::io::Read;
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// Reads the files of `archive` through its central directory, checking their CRC.
+    fn read_archive(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(archive, end), END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        let count = u16_at(archive, end + 10) as usize;
+        let mut header = u32_at(archive, end + 16) as usize;
+        let mut files = Vec::new();
+        for _ in 0..count {
+            assert_eq!(u32_at(archive, header), CENTRAL_DIRECTORY_HEADER_SIGNATURE);
+            let crc = u32_at(archive, header + 16);
+            let compressed_size = u32_at(archive, header + 20) as usize;
+            let size = u32_at(archive, header + 24) as usize;
+            let name_len = u16_at(archive, header + 28) as usize;
+            let offset = u32_at(archive, header + 42) as usize;
+            let name =
+                String::from_utf8(archive[header + 46..header + 46 + name_len].to_vec()).unwrap();
+            assert_eq!(u32_at(archive, offset), LOCAL_FILE_HEADER_SIGNATURE);
+            let data_offset = offset + 30 + u16_at(archive, offset + 26) as usize;
+            let mut data = Vec::new();
+            DeflateDecoder::new(&archive[data_offset..data_offset + compressed_size])
+                .read_to_end(&mut data)
+                .unwrap();
+            assert_eq!(data.len(), size);
+            let mut data_crc = Crc::new();
+            data_crc.update(&data);
+            assert_eq!(data_crc.sum(), crc);
+            let descriptor = data_offset + compressed_size;
+            assert_eq!(u32_at(archive, descriptor), DATA_DESCRIPTOR_SIGNATURE);
+            assert_eq!(u32_at(archive, descriptor + 4), crc);
+            files.push((name, data));
+            header += 46 + name_len;
+        }
+        files
+    }
+
+    #[tokio::test]
+    async fn streams_a_readable_archive() {
+        let (response, mut sender) = ZipResponseBuilder::default()
+            .and_file_name("export.zip")
+            .build();
+        assert_eq!(
+            response.http.headers()[header::CONTENT_TYPE],
+            "application/zip"
+        );
+        assert_eq!(
+            response.http.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"export.zip\""
+        );
+        let large: Vec<u8> = (0..100_000u32).flat_map(u32::to_le_bytes).collect();
+        let expected_large = large.clone();
+        tokio::spawn(async move {
+            sender.send_file("hello.txt", "hello").await.unwrap();
+            sender.start_file("data/large.bin").await.unwrap();
+            for chunk in large.chunks(64 * 1024) {
+                sender.send_file_data(chunk.to_vec()).await.unwrap();
+            }
+            sender.send_file("empty.txt", "").await.unwrap();
+            sender.finish().await.unwrap();
+        });
+
+        let archive = hyper::body::to_bytes(response.http.into_body())
+            .await
+            .unwrap();
+
+        let files = read_archive(&archive);
+        assert_eq!(
+            files,
+            vec![
+                ("hello.txt".to_owned(), b"hello".to_vec()),
+                ("data/large.bin".to_owned(), expected_large),
+                ("empty.txt".to_owned(), Vec::new()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn aborts_the_body_when_dropped_before_finishing() {
+        let (response, mut sender) = ZipResponseBuilder::default().build();
+        tokio::spawn(async move {
+            sender.send_file("hello.txt", "hello").await.unwrap();
+        });
+        assert!(hyper::body::to_bytes(response.http.into_body())
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn converts_dates_to_ms_dos_format() {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(971185736);
+        assert_eq!(
+            dos_date_time(time),
+            ((20 << 9) | (10 << 5) | 10, (13 << 11) | (48 << 5) | 28)
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use hyper::header::HeaderName;
+use hyper::Body;
+use std::net::{IpAddr, SocketAddr};
+
+/// Identity of the client making a request, shared by middlewares keeping per-client state.
+pub trait ClientKey: Send + Sync + 'static {
+    fn client_key(
+        &self,
+        remote_addr: SocketAddr,
+        http_request: &hyper::Request<Body>,
+    ) -> Option<String>;
+
+    fn or<K: ClientKey>(self, other: K) -> OrClientKey<Self, K>
+    where
+        Self: Sized,
+    {
+        OrClientKey {
+            first: self,
+            second: other,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RemoteAddrClientKey;
+
+impl ClientKey for RemoteAddrClientKey {
+    fn client_key(
+        &self,
+        remote_addr: SocketAddr,
+        _http_request: &hyper::Request<Body>,
+    ) -> Option<String> {
+        Some(remote_addr.ip().to_string())
+    }
+}
+
+/// Client IP from `X-Forwarded-For`, trusted only when the request comes from one of
+/// `trusted_proxies`. The rightmost address not belonging to a trusted proxy is used, or the
+/// last trusted proxy when an entry that is not an address comes first.
+#[derive(Clone, Debug)]
+pub struct ForwardedForClientKey {
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl ForwardedForClientKey {
+    pub fn with_trusted_proxies<P: Into<Vec<IpAddr>>>(trusted_proxies: P) -> Self {
+        Self {
+            trusted_proxies: trusted_proxies.into(),
+        }
+    }
+}
+
+impl ClientKey for ForwardedForClientKey {
+    fn client_key(
+        &self,
+        remote_addr: SocketAddr,
+        http_request: &hyper::Request<Body>,
+    ) -> Option<String> {
+        let mut client_ip = remote_addr.ip();
+        if !self.trusted_proxies.contains(&client_ip) {
+            return Some(client_ip.to_string());
+        }
+        let forwarded_ips = http_request
+            .headers()
+            .get_all("X-Forwarded-For")
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|h| h.split(','))
+            .map(|ip| ip.trim().parse::<IpAddr>())
+            .collect::<Vec<_>>();
+        for forwarded_ip in forwarded_ips.into_iter().rev() {
+            match forwarded_ip {
+                Ok(forwarded_ip) => {
+                    client_ip = forwarded_ip;
+                    if !self.trusted_proxies.contains(&client_ip) {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        Some(client_ip.to_string())
+    }
+}
+
+/// Value of a request header, such as an API key. Requests without the header have no key.
+#[derive(Clone, Debug)]
+pub struct HeaderClientKey {
+    header_name: HeaderName,
+}
+
+impl HeaderClientKey {
+    pub fn with_header_name(header_name: HeaderName) -> Self {
+        Self { header_name }
+    }
+}
+
+impl ClientKey for HeaderClientKey {
+    fn client_key(
+        &self,
+        _remote_addr: SocketAddr,
+        http_request: &hyper::Request<Body>,
+    ) -> Option<String> {
+        http_request
+            .headers()
+            .get(&self.header_name)
+            .and_then(|h| h.to_str().ok())
+            .map(|h| format!("{}:{}", self.header_name, h))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct OrClientKey<F, S> {
+    first: F,
+    second: S,
+}
+
+impl<F, S> ClientKey for OrClientKey<F, S>
+where
+    F: ClientKey,
+    S: ClientKey,
+{
+    fn client_key(
+        &self,
+        remote_addr: SocketAddr,
+        http_request: &hyper::Request<Body>,
+    ) -> Option<String> {
+        self.first
+            .client_key(remote_addr, http_request)
+            .or_else(|| self.second.client_key(remote_addr, http_request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const PROXY: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const INNER_PROXY: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+
+    fn forwarded_for_key(remote_ip: Ipv4Addr, forwarded_for: &[&str]) -> Option<String> {
+        let mut http_request = hyper::Request::new(Body::empty());
+        for forwarded_for in forwarded_for {
+            http_request
+                .headers_mut()
+                .append("X-Forwarded-For", forwarded_for.parse().unwrap());
+        }
+        ForwardedForClientKey::with_trusted_proxies([IpAddr::V4(PROXY), IpAddr::V4(INNER_PROXY)])
+            .client_key(SocketAddr::from((remote_ip, 443)), &http_request)
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_from_untrusted_peers() {
+        let key = forwarded_for_key(Ipv4Addr::new(1, 1, 1, 1), &["2.2.2.2"]);
+        assert_eq!(key.as_deref(), Some("1.1.1.1"));
+    }
+
+    #[test]
+    fn forwarded_for_skips_trusted_hops_from_the_right() {
+        let key = forwarded_for_key(PROXY, &["3.3.3.3, 10.0.0.2"]);
+        assert_eq!(key.as_deref(), Some("3.3.3.3"));
+        let key = forwarded_for_key(PROXY, &["3.3.3.3", "10.0.0.2"]);
+        assert_eq!(key.as_deref(), Some("3.3.3.3"));
+        let key = forwarded_for_key(PROXY, &["10.0.0.2, 10.0.0.1"]);
+        assert_eq!(key.as_deref(), Some("10.0.0.2"));
+    }
+
+    #[test]
+    fn forwarded_for_ignores_spoofed_leftmost_entries() {
+        let key = forwarded_for_key(PROXY, &["6.6.6.6, 3.3.3.3"]);
+        assert_eq!(key.as_deref(), Some("3.3.3.3"));
+        let key = forwarded_for_key(PROXY, &["10.0.0.2, 3.3.3.3"]);
+        assert_eq!(key.as_deref(), Some("3.3.3.3"));
+    }
+
+    #[test]
+    fn forwarded_for_stops_at_malformed_entries() {
+        let key = forwarded_for_key(PROXY, &["3.3.3.3, unknown"]);
+        assert_eq!(key.as_deref(), Some("10.0.0.1"));
+        let key = forwarded_for_key(PROXY, &["3.3.3.3, not-an-ip, 10.0.0.2"]);
+        assert_eq!(key.as_deref(), Some("10.0.0.2"));
+        let key = forwarded_for_key(PROXY, &["unknown, 3.3.3.3"]);
+        assert_eq!(key.as_deref(), Some("3.3.3.3"));
+        let key = forwarded_for_key(PROXY, &[]);
+        assert_eq!(key.as_deref(), Some("10.0.0.1"));
+    }
+
+    #[test]
+    fn header_key_falls_back_to_the_remote_address() {
+        let client_key = HeaderClientKey::with_header_name(HeaderName::from_static("x-api-key"))
+            .or(RemoteAddrClientKey);
+        let remote_addr = SocketAddr::from((Ipv4Addr::new(1, 1, 1, 1), 443));
+        let mut http_request = hyper::Request::new(Body::empty());
+        assert_eq!(
+            client_key.client_key(remote_addr, &http_request).as_deref(),
+            Some("1.1.1.1")
+        );
+        http_request
+            .headers_mut()
+            .insert("x-api-key", "secret".parse().unwrap());
+        assert_eq!(
+            client_key.client_key(remote_addr, &http_request).as_deref(),
+            Some("x-api-key:secret")
+        );
+    }
+}
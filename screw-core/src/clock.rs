@@ -0,0 +1,30 @@
+use screw_components::dyn_fn::DFuture;
+use std::fmt;
+use tokio::time::Instant;
+
+/// Source of time of the time-dependent middlewares, such as `RateLimitMiddleware`, so tests
+/// can replace the clock with one they advance themselves, see `test_support::TestClock`.
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> Instant;
+    fn sleep_until(&self, deadline: Instant) -> DFuture<()>;
+}
+
+impl fmt::Debug for dyn Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Clock")
+    }
+}
+
+/// Clock of `tokio::time`, used by default. Pausing tokio time in tests applies to it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> DFuture<()> {
+        Box::pin(tokio::time::sleep_until(deadline))
+    }
+}
@@ -1,8 +1,32 @@
+//! Internal events are emitted through `tracing` under these targets, carrying the peer of a
+//! connection as `remote_addr` and failures as `error`:
+//!
+//! - `screw::server` accepting and serving connections, including client disconnects.
+//! - `screw::responder` handler panics caught by the responder.
+//! - `screw::router` durations of routing and handling a request when
+//!   `Router::and_phase_timings` is enabled, handler timeouts, paths
+//!   with too many segments, middlewares returning without calling `next` and requests
+//!   passing through `PassthroughLogMiddleware`.
+//! - `screw::body` request and response bodies logged at trace level by
+//!   `BodyInspectionMiddleware`.
+
+pub mod archive;
+pub mod client_key;
+pub mod clock;
+pub mod middlewares;
+pub mod multipart;
+mod panic;
+pub mod phase_timer;
+pub mod proxy;
 pub mod request;
 pub mod responder_factory;
 pub mod response;
 pub mod routing;
 pub mod server;
+pub mod static_files;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod upgrade;
 
 #[macro_use]
 extern crate async_trait;
@@ -0,0 +1,215 @@
+use super::super::*;
+use hyper::header::{self, HeaderMap};
+use routing::middleware::Middleware;
+use routing::router::RoutedRequest;
+use screw_components::dyn_fn::DFnOnce;
+use std::io::Write;
+use std::sync::mpsc;
+use std::thread;
+use std::time::SystemTime;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// `%h %l %u %t "%r" %>s %b`
+    Common,
+    /// Common Log Format followed by `"%{Referer}i" "%{User-agent}i"`.
+    #[default]
+    Combined,
+}
+
+/// Writes a Common or Combined Log Format line per request once its response body is
+/// written, so the byte count covers streamed bodies as well.
+pub struct AccessLogMiddleware {
+    lines: mpsc::Sender<String>,
+    format: AccessLogFormat,
+}
+
+impl AccessLogMiddleware {
+    /// Lines are handed to a thread owning `writer`, so blocking writes and flushes stay off
+    /// the runtime. The thread flushes whenever no more lines are waiting and ends once the
+    /// middleware and the bodies of its responses are dropped.
+    pub fn with_writer<W: Write + Send + 'static>(mut writer: W) -> Self {
+        let (lines, receiver) = mpsc::channel::<String>();
+        thread::Builder::new()
+            .name("screw-access-log".to_owned())
+            .spawn(move || {
+                while let Ok(line) = receiver.recv() {
+                    let _ = writer.write_all(line.as_bytes());
+                    for line in receiver.try_iter() {
+                        let _ = writer.write_all(line.as_bytes());
+                    }
+                    let _ = writer.flush();
+                }
+            })
+            .expect("failed to spawn the access log thread");
+        Self {
+            lines,
+            format: AccessLogFormat::default(),
+        }
+    }
+
+    pub fn and_format(mut self, format: AccessLogFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+#[async_trait]
+impl<Extensions> Middleware<RoutedRequest<request::Request<Extensions>>, response::Response>
+    for AccessLogMiddleware
+where
+    Extensions: Sync + Send + 'static,
+{
+    type Request = RoutedRequest<request::Request<Extensions>>;
+    type Response = response::Response;
+    async fn respond(
+        &self,
+        routed_request: RoutedRequest<request::Request<Extensions>>,
+        next: DFnOnce<RoutedRequest<request::Request<Extensions>>, response::Response>,
+    ) -> response::Response {
+        let http_request = &routed_request.origin.http;
+        let request_line = format!(
+            "{} {} {:?}",
+            http_request.method(),
+            http_request
+                .uri()
+                .path_and_query()
+                .map_or("/", |path_and_query| path_and_query.as_str()),
+            http_request.version()
+        );
+        let mut prefix = format!(
+            "{} - - [{}] \"{}\"",
+            routed_request.origin.remote_addr.ip(),
+            format_timestamp(SystemTime::now()),
+            escape(&request_line)
+        );
+        let mut suffix = String::new();
+        if self.format == AccessLogFormat::Combined {
+            let headers = http_request.headers();
+            suffix = format!(
+                " \"{}\" \"{}\"",
+                header_field(headers, header::REFERER),
+                header_field(headers, header::USER_AGENT)
+            );
+        }
+
+        let mut response = next(routed_request).await;
+
+        prefix.push_str(&format!(" {}", response.http.status().as_u16()));
+        let body = std::mem::take(response.http.body_mut());
+        let lines = self.lines.clone();
+        *response.http.body_mut() =
+            response::ResponseBody::new(response::CountingBody::new(body, move |count| {
+                let bytes = match count.bytes {
+                    0 => "-".to_owned(),
+                    bytes => bytes.to_string(),
+                };
+                let _ = lines.send(format!("{} {}{}\n", prefix, bytes, suffix));
+            }));
+        response
+    }
+}
+
+fn header_field(headers: &HeaderMap, name: header::HeaderName) -> String {
+    headers
+        .get(name)
+        .and_then(|header_value| header_value.to_str().ok())
+        .map_or_else(|| "-".to_owned(), escape)
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Formats `time` as `10/Oct/2000:13:55:36 +0000`, rearranging its HTTP date
+/// `Tue, 10 Oct 2000 13:55:36 GMT`.
+fn format_timestamp(time: SystemTime) -> String {
+    let http_date = httpdate::fmt_http_date(time);
+    format!(
+        "{}/{}/{}:{} +0000",
+        &http_date[5..7],
+        &http_date[8..11],
+        &http_date[12..16],
+        &http_date[17..25]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Body;
+    use routing::router;
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// Writer sending every line it is given to the test.
+    struct LineWriter(mpsc::Sender<String>);
+
+    impl Write for LineWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let _ = self.0.send(String::from_utf8_lossy(buf).into_owned());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_a_line_once_the_body_is_written() {
+        let (sender, receiver) = mpsc::channel();
+        let access_log = Arc::new(
+            AccessLogMiddleware::with_writer(LineWriter(sender))
+                .and_format(AccessLogFormat::Common),
+        );
+        let router = router::first::Router::with_fallback_handler(
+            move |routed_request: RoutedRequest<request::Request<()>>| {
+                let access_log = access_log.clone();
+                async move {
+                    access_log
+                        .respond(
+                            routed_request,
+                            Box::new(|_| {
+                                Box::pin(async {
+                                    response::Response {
+                                        http: hyper::Response::new(response::ResponseBody::from(
+                                            "hello",
+                                        )),
+                                    }
+                                })
+                            }),
+                        )
+                        .await
+                }
+            },
+        )
+        .and_routes(|routes| routes);
+        let request = request::Request::with_http(
+            hyper::Request::builder()
+                .uri("/users?page=2")
+                .body(Body::empty())
+                .unwrap(),
+        );
+
+        let response = router.process(request).await;
+        assert!(receiver.try_recv().is_err());
+        hyper::body::to_bytes(response.http.into_body())
+            .await
+            .unwrap();
+
+        let line = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        let (address, rest) = line.split_once(" - - [").unwrap();
+        assert_eq!(address, "127.0.0.1");
+        assert!(rest.ends_with("] \"GET /users?page=2 HTTP/1.1\" 200 5\n"));
+    }
+
+    #[test]
+    fn formats_timestamps_in_common_log_format() {
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(971185736)),
+            "10/Oct/2000:13:48:56 +0000"
+        );
+        assert_eq!(format_timestamp(UNIX_EPOCH), "01/Jan/1970:00:00:00 +0000");
+    }
+}
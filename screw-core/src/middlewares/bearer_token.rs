@@ -0,0 +1,250 @@
+use super::super::*;
+use hyper::header::{self, HeaderValue};
+use hyper::http::Extensions;
+use hyper::{Body, StatusCode};
+use routing::middleware::Middleware;
+use routing::router::RoutedRequest;
+use screw_components::dyn_fn::DFnOnce;
+
+/// Token of an `Authorization: Bearer <token>` header, see RFC 6750. The token is opaque, its
+/// validation is left to the handler or a later middleware.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BearerToken(pub String);
+
+impl BearerToken {
+    /// Extracts the token from the `Authorization` header of `http_request`, the scheme being
+    /// matched case-insensitively.
+    pub fn from_request(http_request: &hyper::Request<Body>) -> Result<Self, BearerTokenError> {
+        let mut authorizations = http_request.headers().get_all(header::AUTHORIZATION).iter();
+        let authorization = authorizations.next().ok_or(BearerTokenError::Missing)?;
+        if authorizations.next().is_some() {
+            return Err(BearerTokenError::Malformed);
+        }
+        let authorization = authorization
+            .to_str()
+            .map_err(|_| BearerTokenError::Malformed)?;
+        let (scheme, token) = authorization.split_once(' ').unwrap_or((authorization, ""));
+        if !scheme.eq_ignore_ascii_case("Bearer") {
+            return Err(BearerTokenError::InvalidScheme);
+        }
+        let token = token.trim_start_matches(' ');
+        if !is_b64token(token) {
+            return Err(BearerTokenError::Malformed);
+        }
+        Ok(Self(token.to_owned()))
+    }
+
+    /// Returns the token inserted by `BearerTokenMiddleware`.
+    pub fn from_extensions(extensions: &Extensions) -> Option<&Self> {
+        extensions.get::<Self>()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BearerTokenError {
+    /// The request has no `Authorization` header.
+    Missing,
+    /// The `Authorization` header uses another scheme, such as `Basic`.
+    InvalidScheme,
+    /// The `Authorization` header is repeated, or its token is empty or has characters a
+    /// bearer token cannot have.
+    Malformed,
+}
+
+impl BearerTokenError {
+    /// Returns the response RFC 6750 asks for: `401 Unauthorized` with a bare
+    /// `WWW-Authenticate: Bearer` challenge when no bearer token is given, `400 Bad Request`
+    /// with an `invalid_request` error when it is malformed.
+    pub fn response(&self) -> response::Response {
+        let (status, challenge) = match self {
+            BearerTokenError::Missing | BearerTokenError::InvalidScheme => {
+                (StatusCode::UNAUTHORIZED, "Bearer")
+            }
+            BearerTokenError::Malformed => {
+                (StatusCode::BAD_REQUEST, "Bearer error=\"invalid_request\"")
+            }
+        };
+        response::Response {
+            http: hyper::Response::builder()
+                .status(status)
+                .header(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static(challenge),
+                )
+                .body(response::ResponseBody::empty())
+                .unwrap(),
+        }
+    }
+}
+
+/// `b64token = 1*( ALPHA / DIGIT / "-" / "." / "_" / "~" / "+" / "/" ) *"="`
+fn is_b64token(token: &str) -> bool {
+    let token = token.trim_end_matches('=');
+    !token.is_empty()
+        && token
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"-._~+/".contains(&byte))
+}
+
+/// Inserts the `BearerToken` of requests into the extensions of the HTTP request, read with
+/// `BearerToken::from_extensions`.
+pub struct BearerTokenMiddleware {
+    required: bool,
+}
+
+impl BearerTokenMiddleware {
+    /// When `required`, requests without a valid bearer token are responded to with
+    /// `BearerTokenError::response` instead of reaching the handler. Otherwise they reach it
+    /// without a token in their extensions.
+    pub fn with_required(required: bool) -> Self {
+        Self { required }
+    }
+}
+
+#[async_trait]
+impl<Extensions> Middleware<RoutedRequest<request::Request<Extensions>>, response::Response>
+    for BearerTokenMiddleware
+where
+    Extensions: Sync + Send + 'static,
+{
+    type Request = RoutedRequest<request::Request<Extensions>>;
+    type Response = response::Response;
+    async fn respond(
+        &self,
+        mut routed_request: RoutedRequest<request::Request<Extensions>>,
+        next: DFnOnce<RoutedRequest<request::Request<Extensions>>, response::Response>,
+    ) -> response::Response {
+        match BearerToken::from_request(&routed_request.origin.http) {
+            Ok(bearer_token) => {
+                routed_request
+                    .origin
+                    .http
+                    .extensions_mut()
+                    .insert(bearer_token);
+            }
+            Err(error) if self.required => return error.response(),
+            Err(_) => {}
+        }
+        next(routed_request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use routing::router;
+    use std::sync::Arc;
+
+    /// Response of a router sending a request with `authorizations` through
+    /// `BearerTokenMiddleware::with_required(required)` to a handler responding the token it
+    /// received in a `token` header.
+    async fn respond(required: bool, authorizations: &[&'static str]) -> hyper::Response<Body> {
+        let middleware = Arc::new(BearerTokenMiddleware::with_required(required));
+        let router = router::first::Router::with_fallback_handler(
+            move |routed_request: RoutedRequest<request::Request<()>>| {
+                let middleware = middleware.clone();
+                async move {
+                    middleware
+                        .respond(
+                            routed_request,
+                            Box::new(|routed_request| {
+                                Box::pin(async move {
+                                    let mut http_response = hyper::Response::builder();
+                                    if let Some(bearer_token) = BearerToken::from_extensions(
+                                        routed_request.origin.http.extensions(),
+                                    ) {
+                                        http_response =
+                                            http_response.header("token", bearer_token.as_str());
+                                    }
+                                    response::Response {
+                                        http: http_response
+                                            .body(response::ResponseBody::empty())
+                                            .unwrap(),
+                                    }
+                                })
+                            }),
+                        )
+                        .await
+                }
+            },
+        )
+        .and_routes(|routes| routes);
+        let mut http_request = hyper::Request::builder();
+        for authorization in authorizations {
+            http_request = http_request.header(header::AUTHORIZATION, *authorization);
+        }
+        let http_request = http_request.body(Body::empty()).unwrap();
+        let response = router
+            .process(request::Request::with_http(http_request))
+            .await;
+        let (parts, _) = response.http.into_parts();
+        hyper::Response::from_parts(parts, Body::empty())
+    }
+
+    fn header_of<'a>(http_response: &'a hyper::Response<Body>, name: &str) -> Option<&'a str> {
+        http_response
+            .headers()
+            .get(name)
+            .map(|header_value| header_value.to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn passes_tokens_to_the_handler() {
+        for authorization in ["Bearer abc.DEF-_~+/==", "bearer  abc.DEF-_~+/=="] {
+            let http_response = respond(true, &[authorization]).await;
+            assert_eq!(http_response.status(), StatusCode::OK);
+            assert_eq!(header_of(&http_response, "token"), Some("abc.DEF-_~+/=="));
+        }
+    }
+
+    #[tokio::test]
+    async fn challenges_requests_without_a_bearer_token() {
+        for authorizations in [&[][..], &["Basic YWxhZGRpbjpvcGVuc2VzYW1l"]] {
+            let http_response = respond(true, authorizations).await;
+            assert_eq!(http_response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                header_of(&http_response, "www-authenticate"),
+                Some("Bearer")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_tokens() {
+        for authorizations in [
+            &["Bearer"][..],
+            &["Bearer "],
+            &["Bearer abc def"],
+            &["Bearer =abc"],
+            &["Bearer abc", "Bearer def"],
+        ] {
+            let http_response = respond(true, authorizations).await;
+            assert_eq!(
+                http_response.status(),
+                StatusCode::BAD_REQUEST,
+                "{authorizations:?} is accepted"
+            );
+            assert_eq!(
+                header_of(&http_response, "www-authenticate"),
+                Some("Bearer error=\"invalid_request\"")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn passes_requests_without_a_valid_token_unless_required() {
+        for authorizations in [
+            &[][..],
+            &["Basic YWxhZGRpbjpvcGVuc2VzYW1l"],
+            &["Bearer abc def"],
+        ] {
+            let http_response = respond(false, authorizations).await;
+            assert_eq!(http_response.status(), StatusCode::OK);
+            assert_eq!(header_of(&http_response, "token"), None);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use super::super::*;
+use futures_util::{stream, StreamExt};
+use hyper::body::{Bytes, HttpBody, SizeHint};
+use hyper::{Body, HeaderMap};
+use routing::middleware::Middleware;
+use routing::router::RoutedRequest;
+use screw_components::dyn_fn::DFnOnce;
+use screw_components::dyn_result::DError;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tracing::Level;
+
+/// Emits the request and response bodies of the routes it wraps as trace events under the
+/// `screw::body` target, the handler still receiving the whole request body and the client
+/// the whole response body. Bodies may carry credentials and personal data, so the middleware
+/// is meant for debugging selected routes only: it is disabled in release builds unless
+/// `and_enabled` says otherwise, does nothing while the target is not enabled at trace level,
+/// and logs at most `max_body_size` bytes of each body.
+pub struct BodyInspectionMiddleware {
+    max_body_size: usize,
+    enabled: bool,
+}
+
+impl BodyInspectionMiddleware {
+    /// The request body is buffered up to `max_body_size` bytes before the handler runs,
+    /// the response body is logged as it is written.
+    pub fn with_max_body_size(max_body_size: usize) -> Self {
+        Self {
+            max_body_size,
+            enabled: cfg!(debug_assertions),
+        }
+    }
+
+    /// Defaults to `true` in debug builds and `false` in release builds.
+    pub fn and_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    async fn inspect_request_body(&self, body: Body, path: &str) -> Body {
+        let mut body = body;
+        let mut buffer = Vec::new();
+        let mut error = None;
+        let mut ended = false;
+        while buffer.len() <= self.max_body_size {
+            match body.data().await {
+                Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                Some(Err(data_error)) => {
+                    error = Some(data_error);
+                    break;
+                }
+                None => {
+                    ended = true;
+                    break;
+                }
+            }
+        }
+        let truncated = buffer.len() > self.max_body_size;
+        tracing::trace!(
+            target: "screw::body",
+            path,
+            body = %String::from_utf8_lossy(&buffer[..buffer.len().min(self.max_body_size)]),
+            truncated,
+            error = error.as_ref().map(tracing::field::display),
+            "request body"
+        );
+        let buffer = Bytes::from(buffer);
+        match error {
+            Some(error) => Body::wrap_stream(stream::iter([Ok(buffer), Err(error)])),
+            None if ended => Body::from(buffer),
+            None => Body::wrap_stream(stream::once(async { Ok(buffer) }).chain(body)),
+        }
+    }
+}
+
+#[async_trait]
+impl<Extensions> Middleware<RoutedRequest<request::Request<Extensions>>, response::Response>
+    for BodyInspectionMiddleware
+where
+    Extensions: Sync + Send + 'static,
+{
+    type Request = RoutedRequest<request::Request<Extensions>>;
+    type Response = response::Response;
+    async fn respond(
+        &self,
+        mut routed_request: RoutedRequest<request::Request<Extensions>>,
+        next: DFnOnce<RoutedRequest<request::Request<Extensions>>, response::Response>,
+    ) -> response::Response {
+        if !self.enabled || !tracing::enabled!(target: "screw::body", Level::TRACE) {
+            return next(routed_request).await;
+        }
+        let path = routed_request.origin.http.uri().path().to_owned();
+        let body = routed_request.origin.take_body();
+        *routed_request.origin.http.body_mut() = self.inspect_request_body(body, &path).await;
+
+        let mut response = next(routed_request).await;
+
+        let body = std::mem::take(response.http.body_mut());
+        *response.http.body_mut() = response::ResponseBody::new(InspectedBody {
+            body,
+            buffer: Vec::new(),
+            max_body_size: self.max_body_size,
+            truncated: false,
+            path,
+        });
+        response
+    }
+}
+
+/// Keeps the first bytes of the response body, logged once the body is dropped, after it is
+/// written or abandoned.
+struct InspectedBody {
+    body: response::ResponseBody,
+    buffer: Vec<u8>,
+    max_body_size: usize,
+    truncated: bool,
+    path: String,
+}
+
+impl HttpBody for InspectedBody {
+    type Data = Bytes;
+    type Error = DError;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let poll = Pin::new(&mut self.body).poll_data(cx);
+        if let Poll::Ready(Some(Ok(data))) = &poll {
+            let remaining = self.max_body_size - self.buffer.len();
+            if data.len() > remaining {
+                self.truncated = true;
+            }
+            self.buffer
+                .extend_from_slice(&data[..data.len().min(remaining)]);
+        }
+        poll
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.body).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+impl Drop for InspectedBody {
+    fn drop(&mut self) {
+        tracing::trace!(
+            target: "screw::body",
+            path = self.path,
+            body = %String::from_utf8_lossy(&self.buffer),
+            truncated = self.truncated,
+            "response body"
+        );
+    }
+}
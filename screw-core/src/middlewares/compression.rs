@@ -0,0 +1,545 @@
+use super::super::*;
+use flate2::read::{DeflateDecoder, GzDecoder};
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression;
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Body, StatusCode};
+use routing::middleware::Middleware;
+use routing::router::RoutedRequest;
+use screw_components::dyn_fn::DFnOnce;
+use screw_components::dyn_result::DError;
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompressionAlgorithm {
+    Gzip,
+    Deflate,
+}
+
+impl CompressionAlgorithm {
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            CompressionAlgorithm::Gzip => "gzip",
+            CompressionAlgorithm::Deflate => "deflate",
+        }
+    }
+
+    fn encoder(&self) -> Encoder {
+        match self {
+            CompressionAlgorithm::Gzip => {
+                Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::default()))
+            }
+            CompressionAlgorithm::Deflate => {
+                Encoder::Deflate(DeflateEncoder::new(Vec::new(), Compression::default()))
+            }
+        }
+    }
+
+    fn decompress(&self, bytes: &[u8], max_size: Option<usize>) -> io::Result<Option<Vec<u8>>> {
+        let decoder: Box<dyn Read + '_> = match self {
+            CompressionAlgorithm::Gzip => Box::new(GzDecoder::new(bytes)),
+            CompressionAlgorithm::Deflate => Box::new(DeflateDecoder::new(bytes)),
+        };
+        let mut decompressed = Vec::new();
+        match max_size {
+            Some(max_size) => {
+                decoder
+                    .take(max_size as u64 + 1)
+                    .read_to_end(&mut decompressed)?;
+                if decompressed.len() > max_size {
+                    return Ok(None);
+                }
+            }
+            None => {
+                let mut decoder = decoder;
+                decoder.read_to_end(&mut decompressed)?;
+            }
+        }
+        Ok(Some(decompressed))
+    }
+}
+
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(DeflateEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    /// Compresses `data`, returning the compressed bytes the encoder has output so far.
+    fn write(&mut self, data: &[u8]) -> io::Result<Bytes> {
+        let output = match self {
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                encoder.get_mut()
+            }
+            Encoder::Deflate(encoder) => {
+                encoder.write_all(data)?;
+                encoder.get_mut()
+            }
+        };
+        Ok(Bytes::from(std::mem::take(output)))
+    }
+
+    /// Returns the compressed bytes left once all data is written.
+    fn finish(self) -> io::Result<Bytes> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish().map(Bytes::from),
+            Encoder::Deflate(encoder) => encoder.finish().map(Bytes::from),
+        }
+    }
+}
+
+/// Body compressing the data of `body` as it is read, passing its trailers through.
+struct CompressedBody {
+    body: response::ResponseBody,
+    encoder: Option<Encoder>,
+}
+
+impl HttpBody for CompressedBody {
+    type Data = Bytes;
+    type Error = DError;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = &mut *self;
+        loop {
+            let encoder = match this.encoder.as_mut() {
+                Some(encoder) => encoder,
+                None => return Poll::Ready(None),
+            };
+            match ready!(Pin::new(&mut this.body).poll_data(cx)) {
+                Some(Ok(data)) => match encoder.write(&data) {
+                    // The encoder holds on to small inputs until it has a block to output
+                    Ok(compressed) if compressed.is_empty() => continue,
+                    Ok(compressed) => return Poll::Ready(Some(Ok(compressed))),
+                    Err(error) => {
+                        this.encoder = None;
+                        return Poll::Ready(Some(Err(error.into())));
+                    }
+                },
+                Some(Err(error)) => {
+                    this.encoder = None;
+                    return Poll::Ready(Some(Err(error)));
+                }
+                None => {
+                    let encoder = this.encoder.take().unwrap();
+                    return Poll::Ready(Some(encoder.finish().map_err(Into::into)));
+                }
+            }
+        }
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.body).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.encoder.is_none() && self.body.is_end_stream()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CompressionConfig {
+    /// Encodings accepted in request `Content-Encoding`, other encodings are rejected with
+    /// `415 Unsupported Media Type`. Defaults to gzip and deflate.
+    pub request_algorithms: Vec<CompressionAlgorithm>,
+    /// Maximum size of a decompressed request body, beyond which the request is rejected with
+    /// `413 Payload Too Large`. Defaults to unlimited.
+    pub request_max_size: Option<usize>,
+    /// Request media types whose bodies are passed through still encoded. Defaults to none.
+    pub request_skip_content_types: Vec<&'static str>,
+    /// Encodings used for responses, in order of preference among equally ranked
+    /// `Accept-Encoding` entries. Defaults to gzip and deflate.
+    pub response_algorithms: Vec<CompressionAlgorithm>,
+    /// Minimum size of a response body to be compressed, bodies of unknown length being
+    /// compressed regardless. Defaults to 1024 bytes.
+    pub response_min_size: usize,
+    /// Response media type prefixes which are never compressed, such as already compressed
+    /// formats. Defaults to images, audio, video and common archives.
+    pub response_skip_content_types: Vec<&'static str>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            request_algorithms: vec![CompressionAlgorithm::Gzip, CompressionAlgorithm::Deflate],
+            request_max_size: None,
+            request_skip_content_types: Vec::new(),
+            response_algorithms: vec![CompressionAlgorithm::Gzip, CompressionAlgorithm::Deflate],
+            response_min_size: 1024,
+            response_skip_content_types: vec![
+                "image/",
+                "audio/",
+                "video/",
+                "application/gzip",
+                "application/zip",
+                "application/zstd",
+            ],
+        }
+    }
+}
+
+fn media_type(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.split(';').next())
+        .map(|media_type| media_type.trim())
+}
+
+fn is_media_type_skipped(headers: &HeaderMap, skip_content_types: &[&'static str]) -> bool {
+    match media_type(headers) {
+        Some(media_type) => skip_content_types.iter().any(|skip_content_type| {
+            media_type.len() >= skip_content_type.len()
+                && media_type[..skip_content_type.len()].eq_ignore_ascii_case(skip_content_type)
+        }),
+        None => false,
+    }
+}
+
+fn is_no_transform(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .any(|directive| directive.trim().eq_ignore_ascii_case("no-transform"))
+}
+
+fn status_response(status_code: StatusCode) -> response::Response {
+    response::Response {
+        http: hyper::Response::builder()
+            .status(status_code)
+            .body(response::ResponseBody::empty())
+            .unwrap(),
+    }
+}
+
+/// Inflates encoded request bodies and compresses response bodies according to
+/// `Accept-Encoding`. Request bodies are buffered in memory so `request_max_size` can be
+/// enforced before the handler runs, while response bodies are compressed as they are
+/// written. Responses with `Cache-Control: no-transform` and partial content responses are
+/// left as is, and strong entity tags of compressed responses are made weak.
+pub struct CompressionMiddleware {
+    config: Arc<CompressionConfig>,
+}
+
+impl CompressionMiddleware {
+    pub fn with_config(config: CompressionConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+
+    async fn decompress_request(
+        &self,
+        http_request: hyper::Request<Body>,
+    ) -> Result<hyper::Request<Body>, StatusCode> {
+        let encoding = match http_request.headers().get(header::CONTENT_ENCODING) {
+            Some(encoding) => encoding
+                .to_str()
+                .map_err(|_| StatusCode::UNSUPPORTED_MEDIA_TYPE)?
+                .trim()
+                .to_owned(),
+            None => return Ok(http_request),
+        };
+        if encoding.is_empty() || encoding.eq_ignore_ascii_case("identity") {
+            return Ok(http_request);
+        }
+        if is_media_type_skipped(
+            http_request.headers(),
+            &self.config.request_skip_content_types,
+        ) {
+            return Ok(http_request);
+        }
+        let algorithm = self
+            .config
+            .request_algorithms
+            .iter()
+            .find(|algorithm| algorithm.encoding().eq_ignore_ascii_case(&encoding))
+            .ok_or(StatusCode::UNSUPPORTED_MEDIA_TYPE)?;
+
+        let (mut parts, body) = http_request.into_parts();
+        let bytes = hyper::body::to_bytes(body)
+            .await
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+        let decompressed = algorithm
+            .decompress(&bytes, self.config.request_max_size)
+            .map_err(|_| StatusCode::BAD_REQUEST)?
+            .ok_or(StatusCode::PAYLOAD_TOO_LARGE)?;
+        parts.headers.remove(header::CONTENT_ENCODING);
+        parts.headers.insert(
+            header::CONTENT_LENGTH,
+            HeaderValue::from(decompressed.len()),
+        );
+        Ok(hyper::Request::from_parts(parts, Body::from(decompressed)))
+    }
+
+    fn negotiate(&self, accept_encoding: &str) -> Option<CompressionAlgorithm> {
+        let mut wildcard_quality = None;
+        let mut qualities = Vec::new();
+        for item in accept_encoding.split(',') {
+            let mut params = item.split(';');
+            let coding = params.next().unwrap_or("").trim();
+            let quality = params
+                .filter_map(|param| {
+                    let (key, value) = param.split_once('=')?;
+                    key.trim()
+                        .eq_ignore_ascii_case("q")
+                        .then(|| value.trim().parse::<f32>().ok())?
+                })
+                .next()
+                .unwrap_or(1.0);
+            if coding == "*" {
+                wildcard_quality = Some(quality);
+            } else if !coding.is_empty() {
+                qualities.push((coding, quality));
+            }
+        }
+
+        let mut negotiated: Option<(CompressionAlgorithm, f32)> = None;
+        for algorithm in &self.config.response_algorithms {
+            let quality = qualities
+                .iter()
+                .find(|(coding, _)| coding.eq_ignore_ascii_case(algorithm.encoding()))
+                .map(|(_, quality)| *quality)
+                .or(wildcard_quality)
+                .unwrap_or(0.0);
+            if quality > 0.0 && !negotiated.is_some_and(|(_, best)| quality <= best) {
+                negotiated = Some((*algorithm, quality));
+            }
+        }
+        negotiated.map(|(algorithm, _)| algorithm)
+    }
+
+    fn compress_response(
+        &self,
+        algorithm: CompressionAlgorithm,
+        http_response: hyper::Response<response::ResponseBody>,
+    ) -> hyper::Response<response::ResponseBody> {
+        let (mut parts, body) = http_response.into_parts();
+        parts
+            .headers
+            .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+        if body
+            .size_hint()
+            .exact()
+            .is_some_and(|len| len < self.config.response_min_size as u64)
+        {
+            return hyper::Response::from_parts(parts, body);
+        }
+        parts.headers.insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(algorithm.encoding()),
+        );
+        parts.headers.remove(header::CONTENT_LENGTH);
+        // The compressed representation is no longer byte-identical to the one a strong
+        // entity tag stands for
+        if let Some(etag) = parts.headers.get(header::ETAG) {
+            if !etag.as_bytes().starts_with(b"W/") {
+                let mut weak_etag = b"W/".to_vec();
+                weak_etag.extend_from_slice(etag.as_bytes());
+                if let Ok(weak_etag) = HeaderValue::from_bytes(&weak_etag) {
+                    parts.headers.insert(header::ETAG, weak_etag);
+                }
+            }
+        }
+        let body = response::ResponseBody::new(CompressedBody {
+            body,
+            encoder: Some(algorithm.encoder()),
+        });
+        hyper::Response::from_parts(parts, body)
+    }
+
+    fn is_compressible(&self, http_response: &hyper::Response<response::ResponseBody>) -> bool {
+        let status = http_response.status();
+        !(status.is_informational()
+            || http_response
+                .extensions()
+                .get::<response::ResponseWriteMode>()
+                == Some(&response::ResponseWriteMode::Immediate)
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+            || status == StatusCode::PARTIAL_CONTENT
+            || http_response.headers().contains_key(header::CONTENT_RANGE)
+            || http_response
+                .headers()
+                .contains_key(header::CONTENT_ENCODING)
+            || is_no_transform(http_response.headers())
+            || is_media_type_skipped(
+                http_response.headers(),
+                &self.config.response_skip_content_types,
+            ))
+    }
+}
+
+#[async_trait]
+impl<Extensions> Middleware<RoutedRequest<request::Request<Extensions>>, response::Response>
+    for CompressionMiddleware
+where
+    Extensions: Sync + Send + 'static,
+{
+    type Request = RoutedRequest<request::Request<Extensions>>;
+    type Response = response::Response;
+    async fn respond(
+        &self,
+        mut routed_request: RoutedRequest<request::Request<Extensions>>,
+        next: DFnOnce<RoutedRequest<request::Request<Extensions>>, response::Response>,
+    ) -> response::Response {
+        let algorithm = routed_request
+            .origin
+            .http
+            .headers()
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| self.negotiate(h));
+
+        let http_request = std::mem::take(&mut routed_request.origin.http);
+        routed_request.origin.http = match self.decompress_request(http_request).await {
+            Ok(http_request) => http_request,
+            Err(status_code) => return status_response(status_code),
+        };
+
+        let mut response = next(routed_request).await;
+
+        if let Some(algorithm) = algorithm {
+            if self.is_compressible(&response.http) {
+                let http_response = std::mem::take(&mut response.http);
+                response.http = self.compress_response(algorithm, http_response);
+            }
+        }
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use futures_util::stream;
+
+    fn compress(
+        http_response: hyper::Response<response::ResponseBody>,
+    ) -> hyper::Response<response::ResponseBody> {
+        let middleware = CompressionMiddleware::with_config(CompressionConfig::default());
+        if middleware.is_compressible(&http_response) {
+            middleware.compress_response(CompressionAlgorithm::Gzip, http_response)
+        } else {
+            http_response
+        }
+    }
+
+    #[tokio::test]
+    async fn compresses_streamed_bodies_chunk_by_chunk() {
+        let chunks: Vec<Result<Bytes, DError>> = (0..64)
+            .map(|index| Ok(Bytes::from(format!("chunk {index} ").repeat(512))))
+            .collect();
+        let http_response = compress(hyper::Response::new(response::ResponseBody::wrap_stream(
+            stream::iter(chunks),
+        )));
+        assert_eq!(http_response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert!(!http_response.headers().contains_key(header::CONTENT_LENGTH));
+
+        let mut body = http_response.into_body();
+        let mut compressed = Vec::new();
+        let mut compressed_chunks = 0;
+        while let Some(chunk) = body.data().await {
+            compressed.extend_from_slice(&chunk.unwrap());
+            compressed_chunks += 1;
+        }
+        assert!(compressed_chunks > 1);
+        let mut decompressed = String::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        let expected: String = (0..64)
+            .map(|index| format!("chunk {index} ").repeat(512))
+            .collect();
+        assert_eq!(decompressed, expected);
+    }
+
+    #[test]
+    fn leaves_small_bodies_uncompressed() {
+        let http_response = compress(hyper::Response::new(response::ResponseBody::from("small")));
+        assert!(!http_response
+            .headers()
+            .contains_key(header::CONTENT_ENCODING));
+        assert_eq!(http_response.headers()[header::VARY], "accept-encoding");
+    }
+
+    #[test]
+    fn respects_no_transform() {
+        let http_response = compress(
+            hyper::Response::builder()
+                .header(header::CACHE_CONTROL, "public, No-Transform")
+                .body(response::ResponseBody::from("large ".repeat(1024)))
+                .unwrap(),
+        );
+        assert!(!http_response
+            .headers()
+            .contains_key(header::CONTENT_ENCODING));
+    }
+
+    #[test]
+    fn leaves_partial_content_uncompressed() {
+        let large = "large ".repeat(1024);
+        let partial_content = compress(
+            hyper::Response::builder()
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, "bytes 0-6143/10000")
+                .body(response::ResponseBody::from(large.clone()))
+                .unwrap(),
+        );
+        assert!(!partial_content
+            .headers()
+            .contains_key(header::CONTENT_ENCODING));
+        let content_range = compress(
+            hyper::Response::builder()
+                .header(header::CONTENT_RANGE, "bytes */10000")
+                .body(response::ResponseBody::from(large))
+                .unwrap(),
+        );
+        assert!(!content_range
+            .headers()
+            .contains_key(header::CONTENT_ENCODING));
+    }
+
+    #[test]
+    fn weakens_strong_entity_tags() {
+        for (etag, expected) in [("\"abc\"", "W/\"abc\""), ("W/\"abc\"", "W/\"abc\"")] {
+            let http_response = compress(
+                hyper::Response::builder()
+                    .header(header::ETAG, etag)
+                    .body(response::ResponseBody::from("large ".repeat(1024)))
+                    .unwrap(),
+            );
+            assert_eq!(http_response.headers()[header::CONTENT_ENCODING], "gzip");
+            assert_eq!(http_response.headers()[header::ETAG], expected);
+        }
+    }
+
+    #[test]
+    fn negotiates_the_best_ranked_algorithm() {
+        let middleware = CompressionMiddleware::with_config(CompressionConfig::default());
+        assert_eq!(
+            middleware.negotiate("deflate;q=0.5, gzip;q=0.8"),
+            Some(CompressionAlgorithm::Gzip)
+        );
+        assert_eq!(
+            middleware.negotiate("gzip;q=0.5, deflate"),
+            Some(CompressionAlgorithm::Deflate)
+        );
+        assert_eq!(middleware.negotiate("*"), Some(CompressionAlgorithm::Gzip));
+        assert_eq!(middleware.negotiate("gzip;q=0, br"), None);
+    }
+}
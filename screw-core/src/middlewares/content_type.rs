@@ -0,0 +1,63 @@
+use super::super::*;
+use hyper::{header, Body, StatusCode};
+use routing::middleware::Middleware;
+use routing::router::RoutedRequest;
+use screw_components::dyn_fn::DFnOnce;
+
+pub struct ContentTypeMiddleware {
+    content_types: Vec<&'static str>,
+}
+
+impl ContentTypeMiddleware {
+    pub fn with_content_type(content_type: &'static str) -> Self {
+        Self {
+            content_types: vec![content_type],
+        }
+    }
+
+    pub fn with_content_types<C: Into<Vec<&'static str>>>(content_types: C) -> Self {
+        Self {
+            content_types: content_types.into(),
+        }
+    }
+
+    fn is_allowed(&self, http_request: &hyper::Request<Body>) -> bool {
+        http_request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.split(';').next())
+            .map(|media_type| {
+                let media_type = media_type.trim();
+                self.content_types
+                    .iter()
+                    .any(|c| c.eq_ignore_ascii_case(media_type))
+            })
+            .unwrap_or(false)
+    }
+}
+
+#[async_trait]
+impl<Extensions> Middleware<RoutedRequest<request::Request<Extensions>>, response::Response>
+    for ContentTypeMiddleware
+where
+    Extensions: Sync + Send + 'static,
+{
+    type Request = RoutedRequest<request::Request<Extensions>>;
+    type Response = response::Response;
+    async fn respond(
+        &self,
+        routed_request: RoutedRequest<request::Request<Extensions>>,
+        next: DFnOnce<RoutedRequest<request::Request<Extensions>>, response::Response>,
+    ) -> response::Response {
+        if !self.is_allowed(&routed_request.origin.http) {
+            return response::Response {
+                http: hyper::Response::builder()
+                    .status(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+                    .body(response::ResponseBody::empty())
+                    .unwrap(),
+            };
+        }
+        next(routed_request).await
+    }
+}
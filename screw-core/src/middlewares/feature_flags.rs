@@ -0,0 +1,81 @@
+use super::super::*;
+use hyper::http::Extensions;
+use hyper::Body;
+use routing::middleware::Middleware;
+use routing::router::RoutedRequest;
+use screw_components::dyn_fn::DFnOnce;
+use std::collections::HashSet;
+
+#[derive(Clone, Debug, Default)]
+pub struct FeatureFlags {
+    enabled: HashSet<&'static str>,
+}
+
+impl FeatureFlags {
+    pub fn from_extensions(extensions: &Extensions) -> Option<&Self> {
+        extensions.get::<Self>()
+    }
+
+    pub fn is_enabled(&self, flag: &str) -> bool {
+        self.enabled.contains(flag)
+    }
+}
+
+type FeatureFlagRule = Box<dyn Fn(&hyper::Request<Body>) -> bool + Send + Sync + 'static>;
+
+pub struct FeatureFlagsMiddleware {
+    rules: Vec<(&'static str, FeatureFlagRule)>,
+}
+
+impl FeatureFlagsMiddleware {
+    pub fn with_rule<R>(flag: &'static str, rule: R) -> Self
+    where
+        R: Fn(&hyper::Request<Body>) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            rules: vec![(flag, Box::new(rule))],
+        }
+    }
+
+    pub fn and_rule<R>(mut self, flag: &'static str, rule: R) -> Self
+    where
+        R: Fn(&hyper::Request<Body>) -> bool + Send + Sync + 'static,
+    {
+        self.rules.push((flag, Box::new(rule)));
+        self
+    }
+
+    fn evaluate(&self, http_request: &hyper::Request<Body>) -> FeatureFlags {
+        FeatureFlags {
+            enabled: self
+                .rules
+                .iter()
+                .filter(|(_, rule)| rule(http_request))
+                .map(|(flag, _)| *flag)
+                .collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl<Extensions> Middleware<RoutedRequest<request::Request<Extensions>>, response::Response>
+    for FeatureFlagsMiddleware
+where
+    Extensions: Sync + Send + 'static,
+{
+    type Request = RoutedRequest<request::Request<Extensions>>;
+    type Response = response::Response;
+    async fn respond(
+        &self,
+        mut routed_request: RoutedRequest<request::Request<Extensions>>,
+        next: DFnOnce<RoutedRequest<request::Request<Extensions>>, response::Response>,
+    ) -> response::Response {
+        let feature_flags = self.evaluate(&routed_request.origin.http);
+        routed_request
+            .origin
+            .http
+            .extensions_mut()
+            .insert(feature_flags);
+        next(routed_request).await
+    }
+}
@@ -0,0 +1,204 @@
+use super::super::*;
+use hyper::body::Bytes;
+use hyper::{header, StatusCode};
+use routing::middleware::Middleware;
+use routing::router::RoutedRequest;
+use screw_components::dyn_fn::DFnOnce;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Switch of `MaintenanceMiddleware`, shared by cloning, e.g. with an admin handler flipping
+/// it. Disabled by default.
+#[derive(Clone, Debug, Default)]
+pub struct MaintenanceMode(Arc<AtomicBool>);
+
+impl MaintenanceMode {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Responds `503 Service Unavailable` to every request while its `MaintenanceMode` is
+/// enabled, except to those of allowed paths such as health checks and admin endpoints.
+/// Toggling the mode takes effect for the next request, requests already being handled
+/// running to completion.
+pub struct MaintenanceMiddleware {
+    mode: MaintenanceMode,
+    allowed_paths: Vec<String>,
+    content_type: &'static str,
+    body: Bytes,
+    retry_after: Option<Duration>,
+}
+
+impl MaintenanceMiddleware {
+    pub fn with_mode(mode: MaintenanceMode) -> Self {
+        Self {
+            mode,
+            allowed_paths: Vec::new(),
+            content_type: "text/plain",
+            body: Bytes::from_static(b"service is under maintenance"),
+            retry_after: None,
+        }
+    }
+
+    /// Keeps serving requests to `path` and paths below it, e.g. `/admin` allowing
+    /// `/admin/users`, while in maintenance.
+    pub fn and_allowed_path<P: Into<String>>(mut self, path: P) -> Self {
+        self.allowed_paths
+            .push(path.into().trim_end_matches('/').to_owned());
+        self
+    }
+
+    /// Responds `body` of `content_type` while in maintenance instead of the default
+    /// `text/plain` notice.
+    pub fn and_body<B: Into<Bytes>>(mut self, content_type: &'static str, body: B) -> Self {
+        self.content_type = content_type;
+        self.body = body.into();
+        self
+    }
+
+    /// Tells clients to retry after `retry_after` with the `Retry-After` header.
+    pub fn and_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    fn is_allowed(&self, path: &str) -> bool {
+        self.allowed_paths.iter().any(|allowed_path| {
+            path.strip_prefix(allowed_path.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+
+    fn response(&self) -> response::Response {
+        let mut http_response = hyper::Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header(header::CONTENT_TYPE, self.content_type);
+        if let Some(retry_after) = self.retry_after {
+            let retry_after_secs = retry_after.as_secs() + (retry_after.subsec_nanos() > 0) as u64;
+            http_response = http_response.header(header::RETRY_AFTER, retry_after_secs);
+        }
+        response::Response {
+            http: http_response
+                .body(response::ResponseBody::from(self.body.clone()))
+                .unwrap(),
+        }
+    }
+}
+
+#[async_trait]
+impl<Extensions> Middleware<RoutedRequest<request::Request<Extensions>>, response::Response>
+    for MaintenanceMiddleware
+where
+    Extensions: Sync + Send + 'static,
+{
+    type Request = RoutedRequest<request::Request<Extensions>>;
+    type Response = response::Response;
+    async fn respond(
+        &self,
+        routed_request: RoutedRequest<request::Request<Extensions>>,
+        next: DFnOnce<RoutedRequest<request::Request<Extensions>>, response::Response>,
+    ) -> response::Response {
+        if self.mode.is_enabled() && !self.is_allowed(routed_request.origin.http.uri().path()) {
+            return self.response();
+        }
+        next(routed_request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Body;
+    use routing::router;
+
+    /// Router sending every request through `maintenance` to a handler responding `200 OK`.
+    fn router(
+        maintenance: MaintenanceMiddleware,
+    ) -> router::second::Router<request::Request<()>, response::Response> {
+        let maintenance = Arc::new(maintenance);
+        router::first::Router::with_fallback_handler(
+            move |routed_request: RoutedRequest<request::Request<()>>| {
+                let maintenance = maintenance.clone();
+                async move {
+                    maintenance
+                        .respond(
+                            routed_request,
+                            Box::new(|_| {
+                                Box::pin(async {
+                                    response::Response {
+                                        http: hyper::Response::new(response::ResponseBody::empty()),
+                                    }
+                                })
+                            }),
+                        )
+                        .await
+                }
+            },
+        )
+        .and_routes(|routes| routes)
+    }
+
+    fn request(path: &str) -> request::Request<()> {
+        request::Request::with_http(
+            hyper::Request::builder()
+                .uri(path)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn responds_unavailable_while_enabled() {
+        let mode = MaintenanceMode::default();
+        let router = router(
+            MaintenanceMiddleware::with_mode(mode.clone())
+                .and_body("application/json", r#"{"maintenance":true}"#)
+                .and_retry_after(Duration::from_millis(1500)),
+        );
+        assert_eq!(
+            router.process(request("/")).await.http.status(),
+            StatusCode::OK
+        );
+
+        mode.set_enabled(true);
+        let http_response = router.process(request("/")).await.http;
+        assert_eq!(http_response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            http_response.headers()[header::CONTENT_TYPE],
+            "application/json"
+        );
+        assert_eq!(http_response.headers()[header::RETRY_AFTER], "2");
+        let body = hyper::body::to_bytes(http_response.into_body())
+            .await
+            .unwrap();
+        assert_eq!(body, r#"{"maintenance":true}"#);
+
+        mode.set_enabled(false);
+        assert_eq!(
+            router.process(request("/")).await.http.status(),
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_allowed_paths_and_paths_below_them() {
+        let mode = MaintenanceMode::default();
+        mode.set_enabled(true);
+        let router = router(MaintenanceMiddleware::with_mode(mode).and_allowed_path("/admin/"));
+        for (path, status) in [
+            ("/admin", StatusCode::OK),
+            ("/admin/users", StatusCode::OK),
+            ("/administrator", StatusCode::SERVICE_UNAVAILABLE),
+            ("/", StatusCode::SERVICE_UNAVAILABLE),
+        ] {
+            let http_response = router.process(request(path)).await.http;
+            assert_eq!(http_response.status(), status, "{path}");
+        }
+    }
+}
@@ -0,0 +1,19 @@
+mod access_log;
+mod bearer_token;
+mod body_inspection;
+mod compression;
+mod content_type;
+mod feature_flags;
+mod maintenance;
+mod passthrough_log;
+mod rate_limit;
+
+pub use access_log::*;
+pub use bearer_token::*;
+pub use body_inspection::*;
+pub use compression::*;
+pub use content_type::*;
+pub use feature_flags::*;
+pub use maintenance::*;
+pub use passthrough_log::*;
+pub use rate_limit::*;
@@ -0,0 +1,59 @@
+use super::super::*;
+use hyper::header::{HeaderName, HeaderValue};
+use routing::middleware::Middleware;
+use routing::router::RoutedRequest;
+use screw_components::dyn_fn::DFnOnce;
+use std::time::Instant;
+
+/// Passes requests and responses through unchanged, emitting a debug event per request under
+/// the `screw::router` target with its method, path, status and duration. Meant as a
+/// lightweight diagnostic for raw routes while developing, the `()` middleware remaining the
+/// zero-overhead passthrough.
+#[derive(Default)]
+pub struct PassthroughLogMiddleware {
+    default_headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl PassthroughLogMiddleware {
+    /// Adds the `name` header to responses lacking it.
+    pub fn and_default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.default_headers.push((name, value));
+        self
+    }
+}
+
+#[async_trait]
+impl<Extensions> Middleware<RoutedRequest<request::Request<Extensions>>, response::Response>
+    for PassthroughLogMiddleware
+where
+    Extensions: Sync + Send + 'static,
+{
+    type Request = RoutedRequest<request::Request<Extensions>>;
+    type Response = response::Response;
+    async fn respond(
+        &self,
+        routed_request: RoutedRequest<request::Request<Extensions>>,
+        next: DFnOnce<RoutedRequest<request::Request<Extensions>>, response::Response>,
+    ) -> response::Response {
+        let started = Instant::now();
+        let remote_addr = routed_request.origin.remote_addr;
+        let method = routed_request.origin.http.method().clone();
+        let path = routed_request.origin.http.uri().path().to_owned();
+        let mut response = next(routed_request).await;
+        for (name, value) in &self.default_headers {
+            if !response.http.headers().contains_key(name) {
+                response.http.headers_mut().insert(name, value.clone());
+            }
+        }
+        tracing::debug!(
+            target: "screw::router",
+            %remote_addr,
+            %method,
+            path,
+            status = response.http.status().as_u16(),
+            elapsed = ?started.elapsed(),
+            "passed through"
+        );
+        response
+    }
+}
@@ -0,0 +1,227 @@
+use super::super::*;
+use client_key::ClientKey;
+use clock::{Clock, TokioClock};
+use hyper::{header, StatusCode};
+use routing::middleware::Middleware;
+use routing::router::RoutedRequest;
+use screw_components::dyn_fn::DFnOnce;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+pub struct RateLimitMiddleware {
+    interval: Duration,
+    max_queue_depth: Option<usize>,
+    client_key: Option<Box<dyn ClientKey>>,
+    next_slots: Mutex<HashMap<Option<String>, Instant>>,
+    queue_depth: AtomicUsize,
+    clock: Arc<dyn Clock>,
+}
+
+impl RateLimitMiddleware {
+    pub fn with_rate(requests: u32, period: Duration) -> Self {
+        Self {
+            interval: period / requests.max(1),
+            max_queue_depth: None,
+            client_key: None,
+            next_slots: Mutex::new(HashMap::new()),
+            queue_depth: AtomicUsize::new(0),
+            clock: Arc::new(TokioClock),
+        }
+    }
+
+    pub fn and_max_queue_depth(mut self, max_queue_depth: Option<usize>) -> Self {
+        self.max_queue_depth = max_queue_depth;
+        self
+    }
+
+    /// Limits the rate per client instead of across all requests. Requests the key yields
+    /// nothing for share a single rate, while the queue depth is always shared.
+    pub fn and_client_key<K: ClientKey>(mut self, client_key: K) -> Self {
+        self.client_key = Some(Box::new(client_key));
+        self
+    }
+
+    /// Takes the time of `clock` instead of `tokio::time`, e.g. a `TestClock` in tests.
+    pub fn and_clock<C: Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    fn acquire(&self, key: Option<String>) -> Result<Option<QueuedRequest<'_>>, Duration> {
+        let mut next_slots = self.next_slots.lock().unwrap();
+        let now = self.clock.now();
+        next_slots.retain(|_, next_slot| *next_slot > now);
+        let next_slot = next_slots.entry(key).or_insert(now);
+        let slot = (*next_slot).max(now);
+        let delay = slot - now;
+        if delay.is_zero() {
+            *next_slot = slot + self.interval;
+            return Ok(None);
+        }
+        let queue_depth = self.queue_depth.load(Ordering::SeqCst);
+        if matches!(self.max_queue_depth, Some(max_queue_depth) if queue_depth >= max_queue_depth) {
+            return Err(delay);
+        }
+        *next_slot = slot + self.interval;
+        self.queue_depth.fetch_add(1, Ordering::SeqCst);
+        Ok(Some(QueuedRequest {
+            queue_depth: &self.queue_depth,
+            slot,
+        }))
+    }
+}
+
+struct QueuedRequest<'a> {
+    queue_depth: &'a AtomicUsize,
+    slot: Instant,
+}
+
+impl Drop for QueuedRequest<'_> {
+    fn drop(&mut self) {
+        self.queue_depth.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[async_trait]
+impl<Extensions> Middleware<RoutedRequest<request::Request<Extensions>>, response::Response>
+    for RateLimitMiddleware
+where
+    Extensions: Sync + Send + 'static,
+{
+    type Request = RoutedRequest<request::Request<Extensions>>;
+    type Response = response::Response;
+    async fn respond(
+        &self,
+        routed_request: RoutedRequest<request::Request<Extensions>>,
+        next: DFnOnce<RoutedRequest<request::Request<Extensions>>, response::Response>,
+    ) -> response::Response {
+        let key = self.client_key.as_ref().and_then(|client_key| {
+            client_key.client_key(
+                routed_request.origin.remote_addr,
+                &routed_request.origin.http,
+            )
+        });
+        match self.acquire(key) {
+            Ok(Some(queued_request)) => {
+                self.clock.sleep_until(queued_request.slot).await;
+                drop(queued_request);
+            }
+            Ok(None) => {}
+            Err(retry_after) => {
+                let retry_after_secs =
+                    retry_after.as_secs() + (retry_after.subsec_nanos() > 0) as u64;
+                return response::Response {
+                    http: hyper::Response::builder()
+                        .status(StatusCode::TOO_MANY_REQUESTS)
+                        .header(header::RETRY_AFTER, retry_after_secs)
+                        .body(response::ResponseBody::empty())
+                        .unwrap(),
+                };
+            }
+        }
+        next(routed_request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Body;
+    use routing::router;
+    use test_support::TestClock;
+
+    /// Router sending every request through `rate_limit` to a handler responding `200 OK`.
+    fn router(
+        rate_limit: RateLimitMiddleware,
+    ) -> Arc<router::second::Router<request::Request<()>, response::Response>> {
+        let rate_limit = Arc::new(rate_limit);
+        Arc::new(
+            router::first::Router::with_fallback_handler(
+                move |routed_request: RoutedRequest<request::Request<()>>| {
+                    let rate_limit = rate_limit.clone();
+                    async move {
+                        rate_limit
+                            .respond(
+                                routed_request,
+                                Box::new(|_| {
+                                    Box::pin(async {
+                                        response::Response {
+                                            http: hyper::Response::new(
+                                                response::ResponseBody::empty(),
+                                            ),
+                                        }
+                                    })
+                                }),
+                            )
+                            .await
+                    }
+                },
+            )
+            .and_routes(|routes| routes),
+        )
+    }
+
+    fn request() -> request::Request<()> {
+        request::Request::with_http(hyper::Request::new(Body::empty()))
+    }
+
+    /// Lets spawned requests run as far as they can without the clock advancing.
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn queues_requests_until_their_slot() {
+        let clock = TestClock::new();
+        let router = router(
+            RateLimitMiddleware::with_rate(1, Duration::from_secs(1)).and_clock(clock.clone()),
+        );
+
+        let response = router.process(request()).await;
+        assert_eq!(response.http.status(), StatusCode::OK);
+
+        let queued_router = router.clone();
+        let queued = tokio::spawn(async move { queued_router.process(request()).await });
+        settle().await;
+        assert!(!queued.is_finished());
+
+        clock.advance(Duration::from_millis(999));
+        settle().await;
+        assert!(!queued.is_finished());
+
+        clock.advance(Duration::from_millis(1));
+        let response = queued.await.unwrap();
+        assert_eq!(response.http.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn rejects_requests_beyond_queue_depth() {
+        let clock = TestClock::new();
+        let router = router(
+            RateLimitMiddleware::with_rate(1, Duration::from_secs(1))
+                .and_max_queue_depth(Some(1))
+                .and_clock(clock.clone()),
+        );
+
+        router.process(request()).await;
+        let queued_router = router.clone();
+        let queued = tokio::spawn(async move { queued_router.process(request()).await });
+        settle().await;
+
+        let response = router.process(request()).await;
+        assert_eq!(response.http.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.http.headers()[header::RETRY_AFTER], "2");
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(queued.await.unwrap().http.status(), StatusCode::OK);
+
+        clock.advance(Duration::from_secs(1));
+        let response = router.process(request()).await;
+        assert_eq!(response.http.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,173 @@
+use super::*;
+use hyper::body::{Bytes, Sender};
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::StatusCode;
+/// Boundary of 128 random bits, so neither part bodies nor clients can predict it.
+pub(super) fn generate_boundary() -> String {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes).expect("random bytes of a multipart boundary");
+    let mut boundary = String::with_capacity(6 + bytes.len() * 2);
+    boundary.push_str("screw-");
+    for byte in bytes {
+        boundary.push_str(&format!("{:02x}", byte));
+    }
+    boundary
+}
+
+pub struct MultipartResponseBuilder {
+    subtype: &'static str,
+    boundary: String,
+    status: StatusCode,
+}
+
+impl MultipartResponseBuilder {
+    pub fn mixed() -> Self {
+        Self::with_subtype("mixed")
+    }
+
+    pub fn with_subtype(subtype: &'static str) -> Self {
+        Self {
+            subtype,
+            boundary: generate_boundary(),
+            status: StatusCode::OK,
+        }
+    }
+
+    pub fn and_boundary<B: Into<String>>(mut self, boundary: B) -> Self {
+        self.boundary = boundary.into();
+        self
+    }
+
+    pub fn and_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Returns the response streaming the parts sent through the returned sender, which should
+    /// be moved to a task writing parts while the response is being returned.
+    pub fn build(self) -> (response::Response, MultipartSender) {
+        let (sender, body) = response::ResponseBody::channel();
+        let content_type = format!("multipart/{}; boundary={}", self.subtype, self.boundary);
+        let http = hyper::Response::builder()
+            .status(self.status)
+            .header(header::CONTENT_TYPE, content_type)
+            .body(body)
+            .unwrap();
+        (
+            response::Response { http },
+            MultipartSender {
+                sender: Some(sender),
+                boundary: self.boundary,
+            },
+        )
+    }
+}
+
+/// Writes parts of a multipart response, each as a single chunk flushed once written.
+///
+/// Dropping the sender before `finish` aborts the response body, so a client never sees a
+/// truncated multipart body as a complete one.
+pub struct MultipartSender {
+    sender: Option<Sender>,
+    boundary: String,
+}
+
+impl MultipartSender {
+    pub async fn send_part<B: Into<Bytes>>(
+        &mut self,
+        headers: &HeaderMap<HeaderValue>,
+        body: B,
+    ) -> hyper::Result<()> {
+        let body = body.into();
+        let mut chunk = Vec::with_capacity(self.boundary.len() + body.len() + 64);
+        chunk.extend_from_slice(b"--");
+        chunk.extend_from_slice(self.boundary.as_bytes());
+        chunk.extend_from_slice(b"\r\n");
+        for (name, value) in headers {
+            chunk.extend_from_slice(name.as_str().as_bytes());
+            chunk.extend_from_slice(b": ");
+            chunk.extend_from_slice(value.as_bytes());
+            chunk.extend_from_slice(b"\r\n");
+        }
+        chunk.extend_from_slice(b"\r\n");
+        chunk.extend_from_slice(&body);
+        chunk.extend_from_slice(b"\r\n");
+        self.send(Bytes::from(chunk)).await
+    }
+
+    pub async fn finish(mut self) -> hyper::Result<()> {
+        let terminator = format!("--{}--\r\n", self.boundary);
+        self.send(Bytes::from(terminator)).await?;
+        self.sender.take();
+        Ok(())
+    }
+
+    async fn send(&mut self, chunk: Bytes) -> hyper::Result<()> {
+        match &mut self.sender {
+            Some(sender) => sender.send_data(chunk).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for MultipartSender {
+    fn drop(&mut self) {
+        if let Some(sender) = self.sender.take() {
+            sender.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_distinct_boundaries() {
+        let boundary = generate_boundary();
+        assert_eq!(boundary.len(), 38);
+        assert!(boundary.starts_with("screw-"));
+        assert!(boundary[6..].bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_ne!(boundary, generate_boundary());
+    }
+
+    #[tokio::test]
+    async fn streams_parts_and_the_terminator() {
+        let (response, mut sender) = MultipartResponseBuilder::mixed()
+            .and_boundary("boundary")
+            .and_status(StatusCode::PARTIAL_CONTENT)
+            .build();
+        assert_eq!(response.http.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.http.headers()[header::CONTENT_TYPE],
+            "multipart/mixed; boundary=boundary"
+        );
+        tokio::spawn(async move {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+            sender.send_part(&headers, "first").await.unwrap();
+            sender.send_part(&HeaderMap::new(), "second").await.unwrap();
+            sender.finish().await.unwrap();
+        });
+        let body = hyper::body::to_bytes(response.http.into_body())
+            .await
+            .unwrap();
+        assert_eq!(
+            body,
+            "--boundary\r\ncontent-type: text/plain\r\n\r\nfirst\r\n\
+             --boundary\r\n\r\nsecond\r\n\
+             --boundary--\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn aborts_the_body_when_dropped_before_finishing() {
+        let (response, mut sender) = MultipartResponseBuilder::mixed().build();
+        tokio::spawn(async move {
+            sender.send_part(&HeaderMap::new(), "first").await.unwrap();
+        });
+        assert!(hyper::body::to_bytes(response.http.into_body())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::panic;
+use std::sync::Once;
+
+thread_local! {
+    static LAST_BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+/// Chains a panic hook recording the backtrace of the panicking thread, so it can be
+/// attached to the panic once caught. Installed once, by the first responder factory with
+/// panic diagnostics enabled, as capturing a backtrace is costly.
+pub(super) fn install_backtrace_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |panic_info| {
+            LAST_BACKTRACE.with(|last_backtrace| {
+                *last_backtrace.borrow_mut() = Some(Backtrace::force_capture());
+            });
+            previous_hook(panic_info);
+        }));
+    });
+}
+
+/// Forgets the backtrace recorded for a panic of the current thread caught by someone else,
+/// so it is not attached to the next panic caught here.
+pub(super) fn clear_backtrace() {
+    LAST_BACKTRACE.with(|last_backtrace| last_backtrace.borrow_mut().take());
+}
+
+pub(super) struct CaughtPanic {
+    pub(super) message: String,
+    pub(super) backtrace: Option<Backtrace>,
+}
+
+impl CaughtPanic {
+    pub(super) fn from_payload(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "Box<dyn Any>".to_owned(),
+            },
+        };
+        let backtrace = LAST_BACKTRACE.with(|last_backtrace| last_backtrace.borrow_mut().take());
+        Self { message, backtrace }
+    }
+
+    pub(super) fn backtrace_string(&self) -> String {
+        match &self.backtrace {
+            Some(backtrace) => backtrace.to_string(),
+            None => "<no backtrace>".to_owned(),
+        }
+    }
+}
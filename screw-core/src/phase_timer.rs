@@ -0,0 +1,35 @@
+use std::time::{Duration, Instant};
+
+/// Times consecutive phases of handling a request, such as routing and handling in
+/// `Router::process` or conversion, handler and serialization in the API converters. A timer
+/// started disabled never reads the clock, so opting out of phase timings costs nothing.
+#[derive(Clone, Copy, Debug)]
+pub struct PhaseTimer {
+    phase_started: Option<Instant>,
+}
+
+impl PhaseTimer {
+    pub fn start(enabled: bool) -> Self {
+        Self {
+            phase_started: enabled.then(Instant::now),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.phase_started.is_some()
+    }
+
+    /// Ends the current phase and starts the next one, returning the duration of the ended
+    /// phase, or zero when the timer is disabled.
+    pub fn lap(&mut self) -> Duration {
+        match &mut self.phase_started {
+            Some(phase_started) => {
+                let now = Instant::now();
+                let elapsed = now - *phase_started;
+                *phase_started = now;
+                elapsed
+            }
+            None => Duration::ZERO,
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use super::request::Request;
+use super::response::{Response, ResponseBody};
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{self, HeaderMap, HeaderName};
+use hyper::{Body, Uri};
+use screw_components::dyn_result::DError;
+
+const HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+/// Removes the headers describing a single connection, the standard ones and those listed by
+/// `Connection`, which a proxy must not forward. `TE: trailers` is kept, even when listed by
+/// `Connection` as its senders must, since it tells the upstream the client accepts trailers,
+/// such as the status of gRPC calls.
+pub fn remove_hop_by_hop_headers(headers: &mut HeaderMap) {
+    let listed: Vec<HeaderName> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|header_value| header_value.to_str().ok())
+        .flat_map(|names| names.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect();
+    let te_trailers = match headers.get_all(header::TE).iter().collect::<Vec<_>>()[..] {
+        [te] if te
+            .to_str()
+            .is_ok_and(|te| te.trim().eq_ignore_ascii_case("trailers")) =>
+        {
+            Some(te.clone())
+        }
+        _ => None,
+    };
+    for name in listed.iter().chain(HOP_BY_HOP_HEADERS.iter()) {
+        headers.remove(name);
+    }
+    if let Some(te_trailers) = te_trailers {
+        headers.insert(header::TE, te_trailers);
+    }
+}
+
+/// Turns `request` into a request to `uri` for an upstream server, keeping the method and the
+/// end-to-end headers. The body is handed over as is, so it streams to the upstream while the
+/// client sends it.
+pub fn upstream_request<Extensions>(
+    request: Request<Extensions>,
+    uri: Uri,
+) -> hyper::Request<Body> {
+    let (mut parts, body) = request.http.into_parts();
+    remove_hop_by_hop_headers(&mut parts.headers);
+    parts.uri = uri;
+    hyper::Request::from_parts(parts, body)
+}
+
+/// Turns the response of an upstream server into a response for the client, keeping the
+/// status and the end-to-end headers. The body is passed through chunk by chunk as the
+/// upstream produces it, never buffered.
+pub fn passthrough_response<B>(upstream_response: hyper::Response<B>) -> Response
+where
+    B: HttpBody<Data = Bytes> + Send + 'static,
+    B::Error: Into<DError>,
+{
+    let (mut parts, body) = upstream_response.into_parts();
+    remove_hop_by_hop_headers(&mut parts.headers);
+    Response {
+        http: hyper::Response::from_parts(parts, ResponseBody::new(body)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers<const N: usize>(headers: [(&str, &str); N]) -> HeaderMap {
+        headers
+            .into_iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                    value.parse().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn headers_with_te<const N: usize>(values: [&'static str; N]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(header::TE, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn removes_standard_hop_by_hop_headers() {
+        let mut headers = headers([
+            ("connection", "keep-alive"),
+            ("keep-alive", "timeout=5"),
+            ("proxy-authorization", "Basic YTpi"),
+            ("te", "gzip"),
+            ("trailer", "checksum"),
+            ("transfer-encoding", "chunked"),
+            ("upgrade", "websocket"),
+            ("content-type", "text/plain"),
+        ]);
+        remove_hop_by_hop_headers(&mut headers);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[header::CONTENT_TYPE], "text/plain");
+    }
+
+    #[test]
+    fn removes_headers_listed_by_connection() {
+        let mut headers = headers([
+            ("connection", "x-session, X-Trace ,close"),
+            ("x-session", "abc"),
+            ("x-trace", "def"),
+            ("x-request-id", "ghi"),
+        ]);
+        remove_hop_by_hop_headers(&mut headers);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers["x-request-id"], "ghi");
+    }
+
+    #[test]
+    fn keeps_te_trailers() {
+        let mut headers = headers([("connection", "TE"), ("te", " Trailers ")]);
+        remove_hop_by_hop_headers(&mut headers);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[header::TE], " Trailers ");
+
+        let mut headers = headers_with_te(["trailers, gzip"]);
+        remove_hop_by_hop_headers(&mut headers);
+        assert!(headers.is_empty());
+
+        let mut headers = headers_with_te(["trailers", "gzip"]);
+        remove_hop_by_hop_headers(&mut headers);
+        assert!(headers.is_empty());
+    }
+}
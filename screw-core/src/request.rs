@@ -1,6 +1,67 @@
+use hyper::http::Extensions as HttpExtensions;
 use hyper::Body;
-use std::net::SocketAddr;
-use std::sync::Arc;
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+/// State shared by the requests of a single connection, such as memoized authentication,
+/// dropped when the connection closes. Requests served by the server find it with
+/// `Request::connection_state`.
+///
+/// Handlers see three scopes of state:
+///
+/// - `Request::extensions`, set by `ResponderFactory::and_extensions`, is shared by every
+///   request the server handles.
+/// - `ConnectionState` is shared by the requests of a keep-alive connection, HTTP/2 streams
+///   included.
+/// - `hyper::Request::extensions` of `Request::http` only lives as long as the request.
+#[derive(Clone, Default)]
+pub struct ConnectionState {
+    extensions: Arc<Mutex<HttpExtensions>>,
+}
+
+impl ConnectionState {
+    pub fn get<T: Clone + Send + Sync + 'static>(&self) -> Option<T> {
+        self.lock().get::<T>().cloned()
+    }
+
+    pub fn insert<T: Send + Sync + 'static>(&self, value: T) -> Option<T> {
+        self.lock().insert(value)
+    }
+
+    pub fn remove<T: Send + Sync + 'static>(&self) -> Option<T> {
+        self.lock().remove::<T>()
+    }
+
+    /// Returns the value of type `T`, inserting the one `f` makes if there is none yet.
+    pub fn get_or_insert_with<T, F>(&self, f: F) -> T
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> T,
+    {
+        let mut extensions = self.lock();
+        match extensions.get::<T>() {
+            Some(value) => value.clone(),
+            None => {
+                let value = f();
+                extensions.insert(value.clone());
+                value
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HttpExtensions> {
+        self.extensions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionState").finish_non_exhaustive()
+    }
+}
 
 pub struct Request<Extensions> {
     pub remote_addr: SocketAddr,
@@ -8,8 +69,56 @@ pub struct Request<Extensions> {
     pub http: hyper::Request<Body>,
 }
 
+impl Request<()> {
+    /// Builds a request outside of the server, e.g. for passing to `Router::process` in tests
+    /// or custom drivers. The remote address defaults to `127.0.0.1:0` and extensions to `()`.
+    pub fn with_http(http: hyper::Request<Body>) -> Self {
+        Self {
+            remote_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+            extensions: Arc::new(()),
+            http,
+        }
+    }
+}
+
+impl<Extensions> Request<Extensions> {
+    pub fn and_remote_addr(mut self, remote_addr: SocketAddr) -> Self {
+        self.remote_addr = remote_addr;
+        self
+    }
+
+    /// Returns the state of the connection the request came from, or `None` for requests not
+    /// received by the server, see `ConnectionState`.
+    pub fn connection_state(&self) -> Option<&ConnectionState> {
+        self.http.extensions().get::<ConnectionState>()
+    }
+
+    /// Takes the body out of the request, leaving an empty one. The body is a `Stream` of
+    /// chunks read from the connection as the client sends them.
+    pub fn take_body(&mut self) -> Body {
+        std::mem::take(self.http.body_mut())
+    }
+
+    pub fn and_extensions<NewExtensions>(
+        self,
+        extensions: NewExtensions,
+    ) -> Request<NewExtensions> {
+        Request {
+            remote_addr: self.remote_addr,
+            extensions: Arc::new(extensions),
+            http: self.http,
+        }
+    }
+}
+
 impl<Extensions> AsRef<hyper::Request<Body>> for Request<Extensions> {
     fn as_ref(&self) -> &hyper::Request<Body> {
         &self.http
     }
 }
+
+impl<Extensions> AsMut<hyper::Request<Body>> for Request<Extensions> {
+    fn as_mut(&mut self) -> &mut hyper::Request<Body> {
+        &mut self.http
+    }
+}
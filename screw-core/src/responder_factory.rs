@@ -2,9 +2,14 @@ pub type ResponderFactory<Extensions> = first::ResponderFactory<Extensions>;
 pub type FResponderFactory<Extensions> = second::ResponderFactory<Extensions>;
 
 use super::*;
-use hyper::Body;
+use futures_util::FutureExt;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Method, StatusCode, Version};
+use std::error::Error;
+use std::fmt;
 use std::future::Future;
 use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
 use std::sync::Arc;
 
@@ -16,7 +21,7 @@ pub mod first {
     where
         Extensions: Sync + Send + 'static,
     {
-        router:
+        pub(super) router:
             Arc<routing::router::second::Router<request::Request<Extensions>, response::Response>>,
     }
 
@@ -39,14 +44,98 @@ pub mod first {
             self,
             extensions: Extensions,
         ) -> second::ResponderFactory<Extensions> {
+            if cfg!(debug_assertions) {
+                panic::install_backtrace_hook();
+            }
             second::ResponderFactory {
-                router: self.router,
-                extensions: Arc::new(extensions),
+                context: Arc::new(SharedContext {
+                    router: self.router,
+                    extensions: Arc::new(extensions),
+                    disallowed_methods: vec![&Method::TRACE, &Method::CONNECT],
+                    panic_diagnostics: cfg!(debug_assertions),
+                    http_10_rejection: None,
+                    require_host: false,
+                    expect_continue_limit: None,
+                }),
             }
         }
     }
 }
 
+#[derive(Debug)]
+pub struct MissingExtensionsError {
+    pub type_names: Vec<&'static str>,
+}
+
+impl fmt::Display for MissingExtensionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "missing required extensions: {}",
+            self.type_names.join(", ")
+        )
+    }
+}
+
+impl Error for MissingExtensionsError {}
+
+impl first::ResponderFactory<hyper::http::Extensions> {
+    /// Same as `and_extensions`, failing when extensions required by the routes and their
+    /// middlewares are not provided.
+    pub fn and_checked_extensions(
+        self,
+        extensions: hyper::http::Extensions,
+    ) -> Result<second::ResponderFactory<hyper::http::Extensions>, MissingExtensionsError> {
+        let mut type_names: Vec<&'static str> = self
+            .router
+            .required_extensions()
+            .iter()
+            .filter(|required_extension| !required_extension.is_provided(&extensions))
+            .map(|required_extension| required_extension.type_name())
+            .collect();
+        type_names.sort_unstable();
+        type_names.dedup();
+        if !type_names.is_empty() {
+            return Err(MissingExtensionsError { type_names });
+        }
+        Ok(self.and_extensions(extensions))
+    }
+}
+
+/// Settings and state of a `ResponderFactory`, shared by its responders behind a single `Arc`
+/// so making a responder, once per connection, clones one pointer. Responding still clones
+/// two per request, the context and the extensions, since `Request::extensions` is an `Arc`
+/// of its own.
+struct SharedContext<Extensions>
+where
+    Extensions: Sync + Send + 'static,
+{
+    router: Arc<routing::router::second::Router<request::Request<Extensions>, response::Response>>,
+    extensions: Arc<Extensions>,
+    disallowed_methods: Vec<&'static Method>,
+    panic_diagnostics: bool,
+    http_10_rejection: Option<StatusCode>,
+    require_host: bool,
+    expect_continue_limit: Option<u64>,
+}
+
+impl<Extensions> Clone for SharedContext<Extensions>
+where
+    Extensions: Sync + Send + 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            router: self.router.clone(),
+            extensions: self.extensions.clone(),
+            disallowed_methods: self.disallowed_methods.clone(),
+            panic_diagnostics: self.panic_diagnostics,
+            http_10_rejection: self.http_10_rejection,
+            require_host: self.require_host,
+            expect_continue_limit: self.expect_continue_limit,
+        }
+    }
+}
+
 pub mod second {
     use super::*;
     use std::net::SocketAddr;
@@ -56,9 +145,59 @@ pub mod second {
     where
         Extensions: Sync + Send + 'static,
     {
-        pub(super) router:
-            Arc<routing::router::second::Router<request::Request<Extensions>, response::Response>>,
-        pub(super) extensions: Arc<Extensions>,
+        pub(super) context: Arc<SharedContext<Extensions>>,
+    }
+
+    impl<Extensions> ResponderFactory<Extensions>
+    where
+        Extensions: Sync + Send + 'static,
+    {
+        /// Methods rejected with `405 Method Not Allowed` before routing, the `Allow` header
+        /// listing the other methods of the routes matching the path. Defaults to `TRACE` and
+        /// `CONNECT`.
+        pub fn and_disallowed_methods<M: Into<Vec<&'static Method>>>(
+            mut self,
+            disallowed_methods: M,
+        ) -> Self {
+            Arc::make_mut(&mut self.context).disallowed_methods = disallowed_methods.into();
+            self
+        }
+
+        /// Whether the message and backtrace of a handler panic are written to the body of the
+        /// `500 Internal Server Error` response. Panics are logged regardless. Defaults to
+        /// `true` in debug builds and `false` in release builds.
+        pub fn and_panic_diagnostics(mut self, panic_diagnostics: bool) -> Self {
+            if panic_diagnostics {
+                panic::install_backtrace_hook();
+            }
+            Arc::make_mut(&mut self.context).panic_diagnostics = panic_diagnostics;
+            self
+        }
+
+        /// Status HTTP/1.0 and older requests are rejected with, such as
+        /// `426 Upgrade Required` or `505 HTTP Version Not Supported`. Defaults to `None`,
+        /// accepting them.
+        pub fn and_http_10_rejection(mut self, http_10_rejection: Option<StatusCode>) -> Self {
+            Arc::make_mut(&mut self.context).http_10_rejection = http_10_rejection;
+            self
+        }
+
+        /// Whether requests without a `Host` header, or an authority for HTTP/2, are rejected
+        /// with `400 Bad Request`. Defaults to `false`.
+        pub fn and_require_host(mut self, require_host: bool) -> Self {
+            Arc::make_mut(&mut self.context).require_host = require_host;
+            self
+        }
+
+        /// Largest `Content-Length` of requests sent with `Expect: 100-continue`, larger ones
+        /// being rejected with `417 Expectation Failed` before the client uploads the body.
+        /// Accepted requests get `100 Continue` from hyper once their body is first read, so a
+        /// handler responding without reading the body spares the upload as well. Defaults to
+        /// `None`, accepting any length.
+        pub fn and_expect_continue_limit(mut self, expect_continue_limit: Option<u64>) -> Self {
+            Arc::make_mut(&mut self.context).expect_continue_limit = expect_continue_limit;
+            self
+        }
     }
 
     impl<Extensions> server::ResponderFactory for ResponderFactory<Extensions>
@@ -69,41 +208,587 @@ pub mod second {
         fn make_responder(&self, remote_addr: SocketAddr) -> Self::Responder {
             Responder {
                 remote_addr,
-                router: self.router.clone(),
-                extensions: self.extensions.clone(),
+                connection_state: request::ConnectionState::default(),
+                context: self.context.clone(),
             }
         }
     }
 }
 
+impl<Extensions> routing::router::second::Router<request::Request<Extensions>, response::Response>
+where
+    Extensions: Sync + Send + 'static,
+{
+    pub fn into_make_service(
+        self,
+        extensions: Extensions,
+    ) -> server::ServerService<FResponderFactory<Extensions>, Responder<Extensions>> {
+        server::ServerService::with_responder_factory(
+            ResponderFactory::with_router(self).and_extensions(extensions),
+        )
+    }
+}
+
 pub struct Responder<Extensions>
 where
     Extensions: Sync + Send + 'static,
 {
     remote_addr: SocketAddr,
-    router: Arc<routing::router::second::Router<request::Request<Extensions>, response::Response>>,
-    extensions: Arc<Extensions>,
+    connection_state: request::ConnectionState,
+    context: Arc<SharedContext<Extensions>>,
+}
+
+impl<Extensions> Responder<Extensions>
+where
+    Extensions: Sync + Send + 'static,
+{
+    fn reject(
+        &self,
+        http_request: &hyper::Request<Body>,
+    ) -> Option<hyper::Response<response::ResponseBody>> {
+        if let Some(reason) = ambiguous_framing(http_request) {
+            tracing::warn!(
+                target: "screw::responder",
+                remote_addr = %self.remote_addr,
+                reason,
+                "rejected request with ambiguous body framing"
+            );
+            return Some(
+                hyper::Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .header(header::CONNECTION, "close")
+                    .body(response::ResponseBody::empty())
+                    .unwrap(),
+            );
+        }
+        if self
+            .context
+            .disallowed_methods
+            .contains(&http_request.method())
+        {
+            let allowed = self
+                .context
+                .router
+                .allowed_methods(http_request)
+                .into_iter()
+                .filter(|method| !self.context.disallowed_methods.contains(method))
+                .map(Method::as_str)
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Some(
+                hyper::Response::builder()
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .header(header::ALLOW, allowed)
+                    .body(response::ResponseBody::empty())
+                    .unwrap(),
+            );
+        }
+        if let Some(status_code) = self.context.http_10_rejection {
+            if http_request.version() <= Version::HTTP_10 {
+                let mut http_response = hyper::Response::builder()
+                    .status(status_code)
+                    .body(response::ResponseBody::empty())
+                    .unwrap();
+                if status_code == StatusCode::UPGRADE_REQUIRED {
+                    let headers = http_response.headers_mut();
+                    headers.insert(header::UPGRADE, HeaderValue::from_static("HTTP/1.1"));
+                    headers.insert(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+                }
+                return Some(http_response);
+            }
+        }
+        if self.context.require_host
+            && !http_request.headers().contains_key(header::HOST)
+            && http_request.uri().authority().is_none()
+        {
+            return Some(
+                hyper::Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(response::ResponseBody::empty())
+                    .unwrap(),
+            );
+        }
+        if let Some(expect_continue_limit) = self.context.expect_continue_limit {
+            if expects_continue(http_request)
+                && content_length(http_request).is_some_and(|length| length > expect_continue_limit)
+            {
+                return Some(
+                    hyper::Response::builder()
+                        .status(StatusCode::EXPECTATION_FAILED)
+                        .header(header::CONNECTION, "close")
+                        .body(response::ResponseBody::empty())
+                        .unwrap(),
+                );
+            }
+        }
+        None
+    }
+}
+
+/// Describes why the body length of `http_request` is ambiguous, which would let a request
+/// be smuggled past a proxy framing it differently, or returns `None` when it is not.
+///
+/// hyper already rejects invalid and differing `Content-Length` values, `Transfer-Encoding`
+/// not ending with `chunked` and `Transfer-Encoding` before HTTP/1.1, and ignores a
+/// `Content-Length` following `Transfer-Encoding`, framing the body as chunked. Left are
+/// repeated `Content-Length` values over HTTP/2, a `Content-Length` preceding
+/// `Transfer-Encoding` and `chunked` applied more than once.
+fn ambiguous_framing(http_request: &hyper::Request<Body>) -> Option<&'static str> {
+    let headers = http_request.headers();
+    let content_length_count = headers
+        .get_all(header::CONTENT_LENGTH)
+        .iter()
+        .flat_map(|header_value| header_value.as_bytes().split(|byte| *byte == b','))
+        .count();
+    if content_length_count > 1 {
+        return Some("duplicate content-length");
+    }
+    let transfer_encodings: Vec<_> = headers
+        .get_all(header::TRANSFER_ENCODING)
+        .iter()
+        .flat_map(|header_value| header_value.as_bytes().split(|byte| *byte == b','))
+        .map(|coding| coding.trim_ascii())
+        .collect();
+    if transfer_encodings.is_empty() {
+        return None;
+    }
+    if content_length_count > 0 {
+        return Some("both content-length and transfer-encoding");
+    }
+    let chunked_count = transfer_encodings
+        .iter()
+        .filter(|coding| coding.eq_ignore_ascii_case(b"chunked"))
+        .count();
+    if chunked_count > 1 {
+        return Some("chunked applied more than once");
+    }
+    None
+}
+
+fn expects_continue(http_request: &hyper::Request<Body>) -> bool {
+    http_request
+        .headers()
+        .get(header::EXPECT)
+        .is_some_and(|header_value| {
+            header_value
+                .as_bytes()
+                .eq_ignore_ascii_case(b"100-continue")
+        })
+}
+
+fn content_length(http_request: &hyper::Request<Body>) -> Option<u64> {
+    http_request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|header_value| header_value.to_str().ok())
+        .and_then(|content_length| content_length.parse().ok())
 }
 
 impl<Extensions> server::Responder for Responder<Extensions>
 where
     Extensions: Sync + Send + 'static,
 {
-    type ResponseFuture = Pin<Box<dyn Future<Output = hyper::Response<Body>> + Send>>;
+    type ResponseFuture =
+        Pin<Box<dyn Future<Output = hyper::Response<response::ResponseBody>> + Send>>;
+
+    fn response(&mut self, mut http_request: hyper::Request<Body>) -> Self::ResponseFuture {
+        if let Some(http_response) = self.reject(&http_request) {
+            return Box::pin(async { http_response });
+        }
 
-    fn response(&mut self, http_request: hyper::Request<Body>) -> Self::ResponseFuture {
+        http_request
+            .extensions_mut()
+            .insert(self.connection_state.clone());
         let remote_addr = self.remote_addr;
-        let router = self.router.clone();
-        let extensions = self.extensions.clone();
+        let context = self.context.clone();
         Box::pin(async move {
             let request = request::Request {
                 remote_addr,
-                extensions,
+                extensions: context.extensions.clone(),
                 http: http_request,
             };
-            let response = router.process(request).await;
-            let http_response = response.http;
-            http_response
+            panic::clear_backtrace();
+            match AssertUnwindSafe(context.router.process(request))
+                .catch_unwind()
+                .await
+            {
+                Ok(response) => response.http,
+                Err(payload) => {
+                    let caught_panic = panic::CaughtPanic::from_payload(payload);
+                    let backtrace = caught_panic.backtrace_string();
+                    tracing::error!(
+                        target: "screw::responder",
+                        %remote_addr,
+                        message = %caught_panic.message,
+                        %backtrace,
+                        "handler panicked"
+                    );
+                    let body = if context.panic_diagnostics {
+                        response::ResponseBody::from(format!(
+                            "{}\n\n{}",
+                            caught_panic.message, backtrace
+                        ))
+                    } else {
+                        response::ResponseBody::empty()
+                    };
+                    hyper::Response::builder()
+                        .status(StatusCode::INTERNAL_SERVER_ERROR)
+                        .body(body)
+                        .unwrap()
+                }
+            }
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::server::conn::Http;
+    use hyper::service::service_fn;
+    use routing::router::RoutedRequest;
+    use server::{Responder as _, ResponderFactory as _};
+    use std::convert::Infallible;
+    use std::time::Duration;
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    async fn handler(_request: RoutedRequest<request::Request<()>>) -> response::Response {
+        response::Response {
+            http: hyper::Response::new(response::ResponseBody::empty()),
+        }
+    }
+
+    /// Responder of a router handling every request with `handler`, configured by `configure`.
+    fn responder_with(
+        configure: impl FnOnce(FResponderFactory<()>) -> FResponderFactory<()>,
+    ) -> Responder<()> {
+        let router = routing::router::first::Router::with_fallback_handler(handler)
+            .and_routes(|routes| routes);
+        configure(ResponderFactory::with_router(router).and_extensions(()))
+            .make_responder(SocketAddr::from(([127, 0, 0, 1], 8080)))
+    }
+
+    fn serve_connection(http: Http) -> DuplexStream {
+        serve_connection_with(http, responder_with(|factory| factory))
+    }
+
+    fn serve_connection_with(http: Http, mut responder: Responder<()>) -> DuplexStream {
+        let (client, server) = duplex(4096);
+        tokio::spawn(http.serve_connection(
+            server,
+            service_fn(move |http_request| {
+                let response_future = responder.response(http_request);
+                async move { Ok::<_, Infallible>(response_future.await) }
+            }),
+        ));
+        client
+    }
+
+    /// Writes `raw_request` to an HTTP/1 connection and reads the response until the server
+    /// closes the connection.
+    async fn exchange(raw_request: &str) -> String {
+        let mut client = serve_connection(Http::new());
+        client.write_all(raw_request.as_bytes()).await.unwrap();
+        let mut raw_response = String::new();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            client.read_to_string(&mut raw_response),
+        )
+        .await
+        .expect("server kept the connection open")
+        .unwrap();
+        raw_response
+    }
+
+    enum Outcome {
+        /// `400 Bad Request` with `Connection: close` from the responder.
+        RejectedByResponder,
+        /// `400 Bad Request` from hyper failing to parse the request, closing the connection.
+        RejectedByHyper,
+        /// Framed unambiguously by hyper and handled, the request asking to close.
+        Accepted,
+    }
+
+    #[tokio::test]
+    async fn ambiguous_framing_over_http1() {
+        let cases = [
+            (
+                "differing content-lengths",
+                "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\nContent-Length: 5\r\n\r\nabcde",
+                Outcome::RejectedByHyper,
+            ),
+            (
+                "equal content-lengths",
+                "POST / HTTP/1.1\r\nHost: a\r\nConnection: close\r\nContent-Length: 3\r\nContent-Length: 3\r\n\r\nabc",
+                Outcome::Accepted,
+            ),
+            (
+                "content-length list",
+                "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 3, 3\r\n\r\nabc",
+                Outcome::RejectedByHyper,
+            ),
+            (
+                "invalid content-length",
+                "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: +3\r\n\r\nabc",
+                Outcome::RejectedByHyper,
+            ),
+            (
+                "content-length then transfer-encoding",
+                "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+                Outcome::RejectedByResponder,
+            ),
+            (
+                "transfer-encoding then content-length",
+                "POST / HTTP/1.1\r\nHost: a\r\nConnection: close\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+                Outcome::Accepted,
+            ),
+            (
+                "transfer-encoding without chunked",
+                "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: gzip\r\n\r\nabc",
+                Outcome::RejectedByHyper,
+            ),
+            (
+                "chunked not last",
+                "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked, gzip\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+                Outcome::RejectedByHyper,
+            ),
+            (
+                "chunked twice",
+                "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked, chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+                Outcome::RejectedByResponder,
+            ),
+            (
+                "chunked twice in separate headers",
+                "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+                Outcome::RejectedByResponder,
+            ),
+            (
+                "transfer-encoding over HTTP/1.0",
+                "POST / HTTP/1.0\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+                Outcome::RejectedByHyper,
+            ),
+        ];
+        for (name, raw_request, outcome) in cases {
+            let raw_response = exchange(raw_request).await.to_ascii_lowercase();
+            let closes = raw_response.contains("\r\nconnection: close\r\n");
+            match outcome {
+                Outcome::RejectedByResponder => assert!(
+                    raw_response.starts_with("http/1.1 400 ") && closes,
+                    "{name}: {raw_response}"
+                ),
+                Outcome::RejectedByHyper => assert!(
+                    raw_response.starts_with("http/1.1 400 ") && !closes,
+                    "{name}: {raw_response}"
+                ),
+                Outcome::Accepted => assert!(
+                    raw_response.starts_with("http/1.1 200 "),
+                    "{name}: {raw_response}"
+                ),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn disallowed_methods_list_allowed_methods() {
+        let router =
+            routing::router::first::Router::with_fallback_handler(handler).and_routes(|routes| {
+                routes.route(
+                    routing::route::first::Route::with_methods([&Method::GET, &Method::POST])
+                        .and_path("/users")
+                        .and_handler(handler),
+                )
+            });
+        let mut responder = ResponderFactory::with_router(router)
+            .and_extensions(())
+            .make_responder(SocketAddr::from(([127, 0, 0, 1], 8080)));
+        for (uri, allowed) in [("/users", "GET, POST"), ("/unknown", "")] {
+            let http_response = responder
+                .response(
+                    hyper::Request::builder()
+                        .method(Method::TRACE)
+                        .uri(uri)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await;
+            assert_eq!(http_response.status(), StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(http_response.headers()[header::ALLOW], allowed);
+        }
+    }
+
+    fn request(version: Version, host: Option<&str>) -> hyper::Request<Body> {
+        let mut http_request = hyper::Request::builder().version(version);
+        if let Some(host) = host {
+            http_request = http_request.header(header::HOST, host);
+        }
+        http_request.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn http_10_requests_are_rejected_when_configured() {
+        let mut responder = responder_with(|factory| factory);
+        let http_response = responder.response(request(Version::HTTP_10, None)).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+
+        let mut responder = responder_with(|factory| {
+            factory.and_http_10_rejection(Some(StatusCode::HTTP_VERSION_NOT_SUPPORTED))
+        });
+        let http_response = responder.response(request(Version::HTTP_10, None)).await;
+        assert_eq!(
+            http_response.status(),
+            StatusCode::HTTP_VERSION_NOT_SUPPORTED
+        );
+        assert!(!http_response.headers().contains_key(header::UPGRADE));
+
+        let mut responder = responder_with(|factory| {
+            factory.and_http_10_rejection(Some(StatusCode::UPGRADE_REQUIRED))
+        });
+        for version in [Version::HTTP_09, Version::HTTP_10] {
+            let http_response = responder.response(request(version, None)).await;
+            assert_eq!(http_response.status(), StatusCode::UPGRADE_REQUIRED);
+            assert_eq!(http_response.headers()[header::UPGRADE], "HTTP/1.1");
+            assert_eq!(http_response.headers()[header::CONNECTION], "Upgrade");
+        }
+        let http_response = responder.response(request(Version::HTTP_11, None)).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn requests_without_host_are_rejected_when_required() {
+        let mut responder = responder_with(|factory| factory);
+        let http_response = responder.response(request(Version::HTTP_11, None)).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+
+        let mut responder = responder_with(|factory| factory.and_require_host(true));
+        let http_response = responder.response(request(Version::HTTP_11, None)).await;
+        assert_eq!(http_response.status(), StatusCode::BAD_REQUEST);
+        let http_response = responder
+            .response(request(Version::HTTP_11, Some("a")))
+            .await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        let http_response = responder
+            .response(
+                hyper::Request::builder()
+                    .version(Version::HTTP_2)
+                    .uri("http://a/")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn expect_continue_limit_rejects_larger_bodies() {
+        let mut responder = responder_with(|factory| factory.and_expect_continue_limit(Some(10)));
+        for (expect, content_length, status) in [
+            (Some("100-continue"), "11", StatusCode::EXPECTATION_FAILED),
+            (Some("100-Continue"), "11", StatusCode::EXPECTATION_FAILED),
+            (Some("100-continue"), "10", StatusCode::OK),
+            (None, "11", StatusCode::OK),
+        ] {
+            let mut http_request = hyper::Request::post("/")
+                .header(header::HOST, "a")
+                .header(header::CONTENT_LENGTH, content_length);
+            if let Some(expect) = expect {
+                http_request = http_request.header(header::EXPECT, expect);
+            }
+            let http_response = responder
+                .response(http_request.body(Body::empty()).unwrap())
+                .await;
+            assert_eq!(
+                http_response.status(),
+                status,
+                "{expect:?} {content_length}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn expect_continue_limit_spares_the_upload() {
+        let mut client = serve_connection_with(
+            Http::new(),
+            responder_with(|factory| factory.and_expect_continue_limit(Some(10))),
+        );
+        client
+            .write_all(
+                b"POST / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 11\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut raw_response = String::new();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            client.read_to_string(&mut raw_response),
+        )
+        .await
+        .expect("server kept the connection open")
+        .unwrap();
+        assert!(raw_response.starts_with("HTTP/1.1 417 "), "{raw_response}");
+        assert!(!raw_response.contains("100 Continue"), "{raw_response}");
+    }
+
+    async fn panicking_handler(
+        _request: RoutedRequest<request::Request<()>>,
+    ) -> response::Response {
+        panic!("handler gave up")
+    }
+
+    async fn panic_response(panic_diagnostics: bool) -> hyper::Response<response::ResponseBody> {
+        let router = routing::router::first::Router::with_fallback_handler(panicking_handler)
+            .and_routes(|routes| routes);
+        let mut responder = ResponderFactory::with_router(router)
+            .and_extensions(())
+            .and_panic_diagnostics(panic_diagnostics)
+            .make_responder(SocketAddr::from(([127, 0, 0, 1], 8080)));
+        responder.response(hyper::Request::new(Body::empty())).await
+    }
+
+    #[tokio::test]
+    async fn panics_are_redacted_without_diagnostics() {
+        let http_response = panic_response(false).await;
+        assert_eq!(http_response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = hyper::body::to_bytes(http_response.into_body()).await;
+        assert!(body.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn panics_are_described_with_diagnostics() {
+        let http_response = panic_response(true).await;
+        assert_eq!(http_response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = hyper::body::to_bytes(http_response.into_body())
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let (message, backtrace) = body.split_once("\n\n").unwrap();
+        assert_eq!(message, "handler gave up");
+        assert_ne!(backtrace, "<no backtrace>");
+    }
+
+    #[tokio::test]
+    async fn duplicate_content_length_over_http2() {
+        let mut http = Http::new();
+        http.http2_only(true);
+        let (mut send_request, connection) = hyper::client::conn::Builder::new()
+            .http2_only(true)
+            .handshake::<_, Body>(serve_connection(http))
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+        for content_lengths in [["3", "3"], ["3", "5"]] {
+            let mut http_request = hyper::Request::post("http://a/");
+            for content_length in content_lengths {
+                http_request = http_request.header(header::CONTENT_LENGTH, content_length);
+            }
+            let http_response = send_request
+                .send_request(http_request.body(Body::from("abc")).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(
+                http_response.status(),
+                StatusCode::BAD_REQUEST,
+                "{content_lengths:?}"
+            );
+        }
+    }
+}
@@ -1,5 +1,420 @@
-use hyper::Body;
+use futures_util::Stream;
+use hyper::body::{Buf, Bytes, HttpBody, SizeHint};
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, HeaderMap};
+use screw_components::dyn_result::DError;
+use std::fmt;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Response extension asking the server to close the connection once the response is written.
+#[derive(Clone, Copy, Debug)]
+pub struct CloseConnection;
+
+/// Response extension hinting how the server writes the response, see
+/// `Response::and_write_mode`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResponseWriteMode {
+    /// The body is collected before anything is written, so the head and the body are
+    /// written together, in a single syscall where hyper's vectored writes allow. Meant for
+    /// small latency-sensitive responses, as the whole body is held in memory.
+    Buffered,
+    /// Every chunk of the body is written as soon as it is produced, middlewares holding
+    /// back data such as `CompressionMiddleware` leaving the response as is. Combine with
+    /// `Http1Config::tcp_nodelay` so small chunks are not delayed by Nagle's algorithm.
+    Immediate,
+}
+
+/// Type-erased response body, able to hold any body implementation yielding `Bytes`.
+pub struct ResponseBody {
+    inner: http_body::combinators::UnsyncBoxBody<Bytes, DError>,
+}
+
+impl ResponseBody {
+    pub fn new<B>(body: B) -> Self
+    where
+        B: HttpBody<Data = Bytes> + Send + 'static,
+        B::Error: Into<DError>,
+    {
+        Self {
+            inner: body.map_err(Into::into).boxed_unsync(),
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self::new(http_body::Empty::new())
+    }
+
+    pub fn channel() -> (hyper::body::Sender, Self) {
+        let (sender, body) = Body::channel();
+        (sender, Self::new(body))
+    }
+
+    pub fn wrap_stream<S, O, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + 'static,
+        E: Into<DError> + 'static,
+    {
+        Self::new(Body::wrap_stream(stream))
+    }
+
+    /// Sends `trailers` after the body data, replacing same-named trailers of the body itself.
+    pub fn and_trailers(self, trailers: HeaderMap) -> Self {
+        self.and_trailers_future(future::ready(Some(trailers)))
+    }
+
+    /// Sends the trailers `trailers_future` resolves to once the body data is written, so a
+    /// streamed body can report its outcome at the end. Note that hyper writes trailers on
+    /// HTTP/2 connections only, HTTP/1.1 responses end without them.
+    pub fn and_trailers_future<F>(self, trailers_future: F) -> Self
+    where
+        F: Future<Output = Option<HeaderMap>> + Send + 'static,
+    {
+        Self::new(TrailersBody {
+            body: self,
+            body_trailers: None,
+            trailers_future: Some(Box::pin(trailers_future)),
+        })
+    }
+}
+
+struct TrailersBody {
+    body: ResponseBody,
+    body_trailers: Option<Option<HeaderMap>>,
+    trailers_future: Option<Pin<Box<dyn Future<Output = Option<HeaderMap>> + Send>>>,
+}
+
+impl HttpBody for TrailersBody {
+    type Data = Bytes;
+    type Error = DError;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Pin::new(&mut self.body).poll_data(cx)
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let this = &mut *self;
+        if this.body_trailers.is_none() {
+            this.body_trailers = Some(ready!(Pin::new(&mut this.body).poll_trailers(cx))?);
+        }
+        let trailers = match this.trailers_future.as_mut() {
+            Some(trailers_future) => ready!(trailers_future.as_mut().poll(cx)),
+            None => None,
+        };
+        this.trailers_future = None;
+        let trailers = match (this.body_trailers.take().flatten(), trailers) {
+            (Some(mut body_trailers), Some(trailers)) => {
+                body_trailers.extend(trailers);
+                Some(body_trailers)
+            }
+            (body_trailers, trailers) => trailers.or(body_trailers),
+        };
+        Poll::Ready(Ok(trailers))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.trailers_future.is_none() && self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+/// Tally of a `CountingBody`, given to its callback once the body is dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BodyCount {
+    /// Bytes of data the body yielded, trailers excluded.
+    pub bytes: u64,
+    /// Whether the body reached its end, `false` when it failed or was dropped before, such
+    /// as when the client disconnected mid-body, `bytes` then being a partial count.
+    pub completed: bool,
+}
+
+/// Body passing `body` through while counting the bytes of data it yields, calling
+/// `on_finished` with the count once it is dropped, whether it was written to the end or
+/// abandoned. Wrapped around a response body, it tells how much of it reached the connection,
+/// e.g. for access logs and metrics.
+pub struct CountingBody<B: HttpBody> {
+    body: B,
+    bytes: u64,
+    ended: bool,
+    failed: bool,
+    on_finished: Option<Box<dyn FnOnce(BodyCount) + Send + 'static>>,
+}
+
+impl<B: HttpBody> CountingBody<B> {
+    pub fn new<F>(body: B, on_finished: F) -> Self
+    where
+        F: FnOnce(BodyCount) + Send + 'static,
+    {
+        Self {
+            body,
+            bytes: 0,
+            ended: false,
+            failed: false,
+            on_finished: Some(Box::new(on_finished)),
+        }
+    }
+}
+
+impl<B> HttpBody for CountingBody<B>
+where
+    B: HttpBody + Unpin,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let poll = Pin::new(&mut self.body).poll_data(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(data))) => self.bytes += data.remaining() as u64,
+            Poll::Ready(Some(Err(_))) => self.failed = true,
+            Poll::Ready(None) => self.ended = true,
+            Poll::Pending => {}
+        }
+        poll
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let poll = Pin::new(&mut self.body).poll_trailers(cx);
+        if let Poll::Ready(Err(_)) = &poll {
+            self.failed = true;
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+impl<B: HttpBody> Drop for CountingBody<B> {
+    fn drop(&mut self) {
+        if let Some(on_finished) = self.on_finished.take() {
+            on_finished(BodyCount {
+                bytes: self.bytes,
+                completed: !self.failed && (self.ended || self.body.is_end_stream()),
+            });
+        }
+    }
+}
+
+impl Default for ResponseBody {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl fmt::Debug for ResponseBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseBody").finish_non_exhaustive()
+    }
+}
+
+impl HttpBody for ResponseBody {
+    type Data = Bytes;
+    type Error = DError;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_data(cx)
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl From<Body> for ResponseBody {
+    fn from(body: Body) -> Self {
+        Self::new(body)
+    }
+}
+
+impl From<Bytes> for ResponseBody {
+    fn from(bytes: Bytes) -> Self {
+        Self::new(http_body::Full::new(bytes))
+    }
+}
+
+impl From<Vec<u8>> for ResponseBody {
+    fn from(vec: Vec<u8>) -> Self {
+        Self::from(Bytes::from(vec))
+    }
+}
+
+impl From<&'static [u8]> for ResponseBody {
+    fn from(slice: &'static [u8]) -> Self {
+        Self::from(Bytes::from_static(slice))
+    }
+}
+
+impl From<String> for ResponseBody {
+    fn from(string: String) -> Self {
+        Self::from(Bytes::from(string))
+    }
+}
+
+impl From<&'static str> for ResponseBody {
+    fn from(string: &'static str) -> Self {
+        Self::from(Bytes::from_static(string.as_bytes()))
+    }
+}
 
 pub struct Response {
-    pub http: hyper::Response<Body>,
+    pub http: hyper::Response<ResponseBody>,
+}
+
+impl Response {
+    pub fn close_connection(mut self) -> Self {
+        self.http.extensions_mut().insert(CloseConnection);
+        self
+    }
+
+    pub fn and_write_mode(mut self, write_mode: ResponseWriteMode) -> Self {
+        self.http.extensions_mut().insert(write_mode);
+        self
+    }
+
+    /// Inserts `extension` into the extensions of the HTTP response, which reach the server
+    /// untouched, to be read by response hooks, see `ServerBuilder::and_response_hook`.
+    pub fn and_extension<T: Send + Sync + 'static>(mut self, extension: T) -> Self {
+        self.http.extensions_mut().insert(extension);
+        self
+    }
+
+    /// Sends `trailers` after the body, announcing their names in the `Trailer` header.
+    pub fn and_trailers(mut self, trailers: HeaderMap) -> Self {
+        if !trailers.is_empty() {
+            let names = trailers
+                .keys()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            if let Ok(names) = HeaderValue::from_str(&names) {
+                self.http.headers_mut().insert(header::TRAILER, names);
+            }
+        }
+        self.and_trailers_future(future::ready(Some(trailers)))
+    }
+
+    /// Sends the trailers `trailers_future` resolves to once the body is written.
+    pub fn and_trailers_future<F>(mut self, trailers_future: F) -> Self
+    where
+        F: Future<Output = Option<HeaderMap>> + Send + 'static,
+    {
+        let body = std::mem::take(self.http.body_mut());
+        *self.http.body_mut() = body.and_trailers_future(trailers_future);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    fn counting_body(
+        body: ResponseBody,
+    ) -> (CountingBody<ResponseBody>, Arc<Mutex<Option<BodyCount>>>) {
+        let body_count = Arc::new(Mutex::new(None));
+        let on_finished_body_count = body_count.clone();
+        let body = CountingBody::new(body, move |count| {
+            *on_finished_body_count.lock().unwrap() = Some(count);
+        });
+        (body, body_count)
+    }
+
+    fn chunks(chunks: Vec<io::Result<&'static str>>) -> ResponseBody {
+        ResponseBody::wrap_stream(stream::iter(chunks))
+    }
+
+    #[tokio::test]
+    async fn counts_bodies_written_to_the_end() {
+        let (mut body, body_count) = counting_body(chunks(vec![Ok("abc"), Ok("de")]));
+        while let Some(chunk) = body.data().await {
+            chunk.unwrap();
+        }
+        drop(body);
+        let expected = BodyCount {
+            bytes: 5,
+            completed: true,
+        };
+        assert_eq!(*body_count.lock().unwrap(), Some(expected));
+    }
+
+    #[tokio::test]
+    async fn counts_bodies_dropped_mid_stream() {
+        let (mut body, body_count) = counting_body(chunks(vec![Ok("abc"), Ok("de")]));
+        body.data().await.unwrap().unwrap();
+        assert_eq!(*body_count.lock().unwrap(), None);
+        drop(body);
+        let expected = BodyCount {
+            bytes: 3,
+            completed: false,
+        };
+        assert_eq!(*body_count.lock().unwrap(), Some(expected));
+    }
+
+    #[tokio::test]
+    async fn counts_bodies_failing_mid_stream() {
+        let (mut body, body_count) = counting_body(chunks(vec![
+            Ok("abc"),
+            Err(io::ErrorKind::ConnectionReset.into()),
+            Ok("de"),
+        ]));
+        body.data().await.unwrap().unwrap();
+        assert!(body.data().await.unwrap().is_err());
+        drop(body);
+        let expected = BodyCount {
+            bytes: 3,
+            completed: false,
+        };
+        assert_eq!(*body_count.lock().unwrap(), Some(expected));
+    }
+
+    #[test]
+    fn counts_empty_bodies_as_completed() {
+        let (body, body_count) = counting_body(ResponseBody::empty());
+        drop(body);
+        let expected = BodyCount {
+            bytes: 0,
+            completed: true,
+        };
+        assert_eq!(*body_count.lock().unwrap(), Some(expected));
+    }
 }
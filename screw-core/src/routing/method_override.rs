@@ -0,0 +1,346 @@
+use futures_util::stream::{self, StreamExt};
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{self, HeaderName};
+use hyper::{Body, Method, Request, Version};
+
+const DEFAULT_FORM_BODY_LIMIT: usize = 64 * 1024;
+
+/// Lets `POST` requests ask to be routed as another method, for clients unable to send it.
+/// The method is taken from the `X-HTTP-Method-Override` header, then from a `_method` query
+/// field, then from a `_method` field of an `application/x-www-form-urlencoded` body when
+/// enabled. Only methods in the allowed list, by default `PUT`, `PATCH` and `DELETE`, are
+/// honored. See `Router::and_method_override`.
+#[derive(Clone, Debug)]
+pub struct MethodOverride {
+    methods: Vec<&'static Method>,
+    header_name: HeaderName,
+    field_name: String,
+    query_field: bool,
+    form_field: bool,
+    form_body_limit: usize,
+}
+
+impl Default for MethodOverride {
+    fn default() -> Self {
+        Self::with_methods([&Method::PUT, &Method::PATCH, &Method::DELETE])
+    }
+}
+
+impl MethodOverride {
+    pub fn with_methods<M: Into<Vec<&'static Method>>>(methods: M) -> Self {
+        Self {
+            methods: methods.into(),
+            header_name: HeaderName::from_static("x-http-method-override"),
+            field_name: "_method".to_owned(),
+            query_field: true,
+            form_field: false,
+            form_body_limit: DEFAULT_FORM_BODY_LIMIT,
+        }
+    }
+
+    pub fn and_header_name(mut self, header_name: HeaderName) -> Self {
+        self.header_name = header_name;
+        self
+    }
+
+    pub fn and_field_name<N: Into<String>>(mut self, field_name: N) -> Self {
+        self.field_name = field_name.into();
+        self
+    }
+
+    pub fn and_query_field(mut self, query_field: bool) -> Self {
+        self.query_field = query_field;
+        self
+    }
+
+    /// Reads the field from form bodies, buffering at most `form_body_limit` bytes of the
+    /// body before routing, and none of a body whose length is known to exceed it. The body
+    /// is handed to the handler unchanged either way.
+    pub fn and_form_field(mut self, form_field: bool, form_body_limit: usize) -> Self {
+        self.form_field = form_field;
+        self.form_body_limit = form_body_limit;
+        self
+    }
+
+    pub(super) async fn apply(&self, http_request: &mut Request<Body>) {
+        if http_request.method() != Method::POST {
+            return;
+        }
+        let mut method = http_request
+            .headers()
+            .get(&self.header_name)
+            .and_then(|header_value| header_value.to_str().ok())
+            .map(str::to_owned);
+        if method.is_none() && self.query_field {
+            method = http_request
+                .uri()
+                .query()
+                .and_then(|query| self.find_field(query.as_bytes()));
+        }
+        if method.is_none() && self.form_field && is_form(http_request) {
+            method = self.read_form_field(http_request).await;
+        }
+        let method = match method
+            .and_then(|m| Method::from_bytes(m.trim().to_ascii_uppercase().as_bytes()).ok())
+        {
+            Some(method) => method,
+            None => return,
+        };
+        if self.methods.iter().any(|allowed| **allowed == method) {
+            *http_request.method_mut() = method;
+        }
+    }
+
+    fn find_field(&self, form: &[u8]) -> Option<String> {
+        url::form_urlencoded::parse(form)
+            .find(|(name, _)| *name == self.field_name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    async fn read_form_field(&self, http_request: &mut Request<Body>) -> Option<String> {
+        let is_over_limit = http_request
+            .body()
+            .size_hint()
+            .exact()
+            .is_some_and(|length| length > self.form_body_limit as u64);
+        if is_over_limit {
+            return None;
+        }
+        let version = http_request.version();
+        let body = http_request.body_mut();
+        let mut buffer = Vec::new();
+        let mut error = None;
+        let mut complete = false;
+        while buffer.len() <= self.form_body_limit {
+            match body.data().await {
+                Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                Some(Err(data_error)) => {
+                    error = Some(data_error);
+                    break;
+                }
+                None => {
+                    complete = true;
+                    break;
+                }
+            }
+        }
+        let method = complete.then(|| self.find_field(&buffer)).flatten();
+        let rest = std::mem::take(body);
+        *body = replayed_body(Bytes::from(buffer), error, rest, complete, version).await;
+        method
+    }
+}
+
+/// Body handing out `read`, then the `error` reading stopped at or the `rest` of the body,
+/// along with its trailers. A completely read body without trailers keeps its exact size.
+async fn replayed_body(
+    read: Bytes,
+    error: Option<hyper::Error>,
+    mut rest: Body,
+    complete: bool,
+    version: Version,
+) -> Body {
+    if let Some(error) = error {
+        return Body::wrap_stream(stream::iter([Ok(read), Err(error)]));
+    }
+    if complete {
+        return match rest.trailers().await {
+            Ok(None) => Body::from(read),
+            Ok(Some(trailers)) => {
+                let (mut sender, body) = Body::channel();
+                let _ = sender.try_send_data(read);
+                let _ = sender.send_trailers(trailers).await;
+                body
+            }
+            Err(error) => Body::wrap_stream(stream::iter([Ok(read), Err(error)])),
+        };
+    }
+    // hyper only surfaces trailers of HTTP/2 bodies, which need forwarding to keep them
+    if version != Version::HTTP_2 {
+        return Body::wrap_stream(stream::iter([Ok(read)]).chain(rest));
+    }
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        if sender.send_data(read).await.is_err() {
+            return;
+        }
+        while let Some(chunk) = rest.data().await {
+            match chunk {
+                Ok(chunk) => {
+                    if sender.send_data(chunk).await.is_err() {
+                        return;
+                    }
+                }
+                Err(_) => return sender.abort(),
+            }
+        }
+        match rest.trailers().await {
+            Ok(Some(trailers)) => {
+                let _ = sender.send_trailers(trailers).await;
+            }
+            Ok(None) => {}
+            Err(_) => sender.abort(),
+        }
+    });
+    body
+}
+
+fn is_form(http_request: &Request<Body>) -> bool {
+    http_request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|header_value| header_value.to_str().ok())
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|media_type| {
+            media_type
+                .trim()
+                .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: Method, uri: &str, body: Body) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(body)
+            .unwrap()
+    }
+
+    fn form_request(body: Body) -> Request<Body> {
+        let mut http_request = request(Method::POST, "/", body);
+        http_request.headers_mut().insert(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded".parse().unwrap(),
+        );
+        http_request
+    }
+
+    #[tokio::test]
+    async fn overrides_with_the_header() {
+        let mut http_request = request(Method::POST, "/", Body::empty());
+        http_request
+            .headers_mut()
+            .insert("x-http-method-override", "delete".parse().unwrap());
+        MethodOverride::default().apply(&mut http_request).await;
+        assert_eq!(http_request.method(), Method::DELETE);
+    }
+
+    #[tokio::test]
+    async fn overrides_with_the_query_field() {
+        let mut http_request = request(Method::POST, "/?_method=PATCH", Body::empty());
+        MethodOverride::default().apply(&mut http_request).await;
+        assert_eq!(http_request.method(), Method::PATCH);
+    }
+
+    #[tokio::test]
+    async fn ignores_methods_not_allowed() {
+        let mut http_request = request(Method::POST, "/?_method=CONNECT", Body::empty());
+        MethodOverride::default().apply(&mut http_request).await;
+        assert_eq!(http_request.method(), Method::POST);
+    }
+
+    #[tokio::test]
+    async fn ignores_requests_other_than_post() {
+        let mut http_request = request(Method::GET, "/?_method=DELETE", Body::empty());
+        http_request
+            .headers_mut()
+            .insert("x-http-method-override", "DELETE".parse().unwrap());
+        MethodOverride::default().apply(&mut http_request).await;
+        assert_eq!(http_request.method(), Method::GET);
+    }
+
+    #[tokio::test]
+    async fn overrides_with_the_form_field_keeping_the_body() {
+        let mut http_request = form_request(Body::from("name=screw&_method=PUT"));
+        MethodOverride::default()
+            .and_form_field(true, 1024)
+            .apply(&mut http_request)
+            .await;
+        assert_eq!(http_request.method(), Method::PUT);
+        assert_eq!(http_request.body().size_hint().exact(), Some(22));
+        let body = hyper::body::to_bytes(http_request.into_body()).await;
+        assert_eq!(body.unwrap(), "name=screw&_method=PUT");
+    }
+
+    #[tokio::test]
+    async fn skips_form_bodies_known_to_exceed_the_limit() {
+        let mut http_request = form_request(Body::from("_method=PUT"));
+        MethodOverride::default()
+            .and_form_field(true, 4)
+            .apply(&mut http_request)
+            .await;
+        assert_eq!(http_request.method(), Method::POST);
+        assert_eq!(http_request.body().size_hint().exact(), Some(11));
+    }
+
+    #[tokio::test]
+    async fn replays_form_bodies_with_their_trailers() {
+        let (mut sender, body) = Body::channel();
+        let mut http_request = form_request(body);
+        *http_request.version_mut() = Version::HTTP_2;
+        tokio::spawn(async move {
+            sender
+                .send_data(Bytes::from_static(b"_method="))
+                .await
+                .unwrap();
+            sender
+                .send_data(Bytes::from_static(b"DELETE"))
+                .await
+                .unwrap();
+            let mut trailers = hyper::HeaderMap::new();
+            trailers.insert("checksum", "d41d8cd9".parse().unwrap());
+            sender.send_trailers(trailers).await.unwrap();
+        });
+        MethodOverride::default()
+            .and_form_field(true, 1024)
+            .apply(&mut http_request)
+            .await;
+        assert_eq!(http_request.method(), Method::DELETE);
+        let mut body = http_request.into_body();
+        assert_eq!(body.data().await.unwrap().unwrap(), "_method=DELETE");
+        assert!(body.data().await.is_none());
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["checksum"], "d41d8cd9");
+    }
+
+    #[tokio::test]
+    async fn forwards_the_rest_of_form_bodies_over_the_limit() {
+        let (mut sender, body) = Body::channel();
+        let mut http_request = form_request(body);
+        *http_request.version_mut() = Version::HTTP_2;
+        tokio::spawn(async move {
+            sender
+                .send_data(Bytes::from_static(b"name="))
+                .await
+                .unwrap();
+            sender
+                .send_data(Bytes::from_static(b"screw"))
+                .await
+                .unwrap();
+            sender
+                .send_data(Bytes::from_static(b"&_method=PUT"))
+                .await
+                .unwrap();
+            let mut trailers = hyper::HeaderMap::new();
+            trailers.insert("checksum", "d41d8cd9".parse().unwrap());
+            sender.send_trailers(trailers).await.unwrap();
+        });
+        MethodOverride::default()
+            .and_form_field(true, 4)
+            .apply(&mut http_request)
+            .await;
+        assert_eq!(http_request.method(), Method::POST);
+        let mut body = http_request.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(bytes, b"name=screw&_method=PUT");
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["checksum"], "d41d8cd9");
+    }
+}
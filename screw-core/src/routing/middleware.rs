@@ -1,11 +1,78 @@
+use hyper::http::Extensions;
 use screw_components::dyn_fn::DFnOnce;
+use std::any::{type_name, TypeId};
 use std::future::Future;
+use std::marker::PhantomData;
+
+/// Extension type which has to be present in `hyper::http::Extensions` used as the request
+/// extensions, checked when the responder factory is built.
+#[derive(Clone, Copy, Debug)]
+pub struct RequiredExtension {
+    type_id: TypeId,
+    type_name: &'static str,
+    is_provided: fn(&Extensions) -> bool,
+}
+
+impl RequiredExtension {
+    pub fn of<T: Send + Sync + 'static>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+            is_provided: |extensions| extensions.get::<T>().is_some(),
+        }
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn is_provided(&self, extensions: &Extensions) -> bool {
+        (self.is_provided)(extensions)
+    }
+}
+
+/// What happens when a middleware returns without calling `next`, see
+/// `Router::and_unused_next`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnusedNext {
+    /// Nothing, as middlewares short-circuit on purpose, e.g. rejecting a request.
+    #[default]
+    Ignore,
+    /// A warning is emitted under the `screw::router` target, naming the middleware.
+    Warn,
+    /// The handling panics, caught by the responder like any handler panic.
+    Panic,
+}
+
+impl UnusedNext {
+    pub(super) fn report(&self, middleware: &str, path: &str) {
+        match self {
+            UnusedNext::Ignore => {}
+            UnusedNext::Warn => tracing::warn!(
+                target: "screw::router",
+                middleware,
+                path,
+                "middleware returned without calling next"
+            ),
+            UnusedNext::Panic => {
+                panic!("middleware {middleware} returned without calling next for {path}")
+            }
+        }
+    }
+}
 
 #[async_trait]
 pub trait Middleware<Rq, Rs> {
     type Request;
     type Response;
     async fn respond(&self, request: Self::Request, next: DFnOnce<Rq, Rs>) -> Self::Response;
+    fn required_extensions(&self) -> Vec<RequiredExtension> {
+        Vec::new()
+    }
 }
 
 #[async_trait]
@@ -35,3 +102,44 @@ where
         self(request, next).await
     }
 }
+
+/// Middleware built from a closure by `fn_middleware`.
+pub struct FnMiddleware<ORq, ORs, Rq, Rs, HFn> {
+    handler: HFn,
+    _p_o: PhantomData<fn(ORq) -> ORs>,
+    _p_next: PhantomData<fn(Rq) -> Rs>,
+}
+
+/// Builds a middleware from an async closure taking the request and the next handler, which,
+/// unlike a closure used as a middleware directly, may convert between request and response
+/// types, e.g. `fn_middleware(|request: Request, next: DFnOnce<ApiRequest, ApiResponse>| ...)`.
+pub fn fn_middleware<ORq, ORs, Rq, Rs, HFn, HFut>(
+    handler: HFn,
+) -> FnMiddleware<ORq, ORs, Rq, Rs, HFn>
+where
+    HFn: Fn(ORq, DFnOnce<Rq, Rs>) -> HFut + Send + Sync + 'static,
+    HFut: Future<Output = ORs> + Send + 'static,
+{
+    FnMiddleware {
+        handler,
+        _p_o: PhantomData,
+        _p_next: PhantomData,
+    }
+}
+
+#[async_trait]
+impl<ORq, ORs, Rq, Rs, HFn, HFut> Middleware<Rq, Rs> for FnMiddleware<ORq, ORs, Rq, Rs, HFn>
+where
+    ORq: Send + 'static,
+    ORs: Send + 'static,
+    Rq: Send + 'static,
+    Rs: Send + 'static,
+    HFn: Fn(ORq, DFnOnce<Rq, Rs>) -> HFut + Send + Sync + 'static,
+    HFut: Future<Output = ORs> + Send + 'static,
+{
+    type Request = ORq;
+    type Response = ORs;
+    async fn respond(&self, request: Self::Request, next: DFnOnce<Rq, Rs>) -> Self::Response {
+        (self.handler)(request, next).await
+    }
+}
@@ -0,0 +1,173 @@
+use super::middleware::{Middleware, RequiredExtension};
+use screw_components::dyn_fn::DFnOnce;
+use std::sync::Arc;
+
+/// Middlewares composed into a single one, the first pushed being the outermost.
+///
+/// A middleware returning without calling `next` short-circuits the stack: the middlewares
+/// pushed after it and the handler are not called, while the ones pushed before it receive
+/// its response as the response of their `next`.
+pub struct MiddlewareStack<Outer, Inner> {
+    outer: Arc<Outer>,
+    inner: Arc<Inner>,
+}
+
+impl MiddlewareStack<(), ()> {
+    pub fn new() -> Self {
+        Self {
+            outer: Arc::new(()),
+            inner: Arc::new(()),
+        }
+    }
+}
+
+impl Default for MiddlewareStack<(), ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Outer, Inner> MiddlewareStack<Outer, Inner> {
+    pub fn push<M>(self, middleware: M) -> MiddlewareStack<Self, M> {
+        MiddlewareStack {
+            outer: Arc::new(self),
+            inner: Arc::new(middleware),
+        }
+    }
+}
+
+#[async_trait]
+impl<Rq, Rs, Outer, Inner> Middleware<Rq, Rs> for MiddlewareStack<Outer, Inner>
+where
+    Rq: Send + 'static,
+    Rs: Send + 'static,
+    Inner: Middleware<Rq, Rs> + Send + Sync + 'static,
+    Inner::Request: Send + 'static,
+    Inner::Response: Send + 'static,
+    Outer: Middleware<Inner::Request, Inner::Response> + Send + Sync + 'static,
+    Outer::Request: Send + 'static,
+{
+    type Request = Outer::Request;
+    type Response = Outer::Response;
+    async fn respond(&self, request: Self::Request, next: DFnOnce<Rq, Rs>) -> Self::Response {
+        let inner = self.inner.clone();
+        self.outer
+            .respond(
+                request,
+                Box::new(move |request| {
+                    Box::pin(async move { inner.respond(request, next).await })
+                }),
+            )
+            .await
+    }
+    fn required_extensions(&self) -> Vec<RequiredExtension> {
+        let mut required_extensions = self.outer.required_extensions();
+        required_extensions.extend(self.inner.required_extensions());
+        required_extensions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Trail = Vec<String>;
+
+    struct Recording {
+        name: &'static str,
+        calls_next: bool,
+    }
+
+    impl Recording {
+        fn passing(name: &'static str) -> Self {
+            Self {
+                name,
+                calls_next: true,
+            }
+        }
+
+        fn skipping(name: &'static str) -> Self {
+            Self {
+                name,
+                calls_next: false,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Middleware<Trail, Trail> for Recording {
+        type Request = Trail;
+        type Response = Trail;
+        async fn respond(&self, mut trail: Trail, next: DFnOnce<Trail, Trail>) -> Trail {
+            trail.push(format!("{} request", self.name));
+            let mut trail = if self.calls_next {
+                next(trail).await
+            } else {
+                trail
+            };
+            trail.push(format!("{} response", self.name));
+            trail
+        }
+    }
+
+    async fn respond<M>(middleware: M) -> Trail
+    where
+        M: Middleware<Trail, Trail, Request = Trail, Response = Trail>,
+    {
+        middleware
+            .respond(
+                Vec::new(),
+                Box::new(|mut trail: Trail| {
+                    Box::pin(async move {
+                        trail.push("handler".to_owned());
+                        trail
+                    })
+                }),
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn first_pushed_is_outermost() {
+        let stack = MiddlewareStack::new()
+            .push(Recording::passing("first"))
+            .push(Recording::passing("second"))
+            .push(Recording::passing("third"));
+
+        assert_eq!(
+            respond(stack).await,
+            [
+                "first request",
+                "second request",
+                "third request",
+                "handler",
+                "third response",
+                "second response",
+                "first response",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn skipping_next_short_circuits_the_rest() {
+        let stack = MiddlewareStack::new()
+            .push(Recording::passing("first"))
+            .push(Recording::skipping("second"))
+            .push(Recording::passing("third"));
+
+        assert_eq!(
+            respond(stack).await,
+            [
+                "first request",
+                "second request",
+                "second response",
+                "first response",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_stack_calls_the_handler() {
+        assert_eq!(respond(MiddlewareStack::new()).await, ["handler"]);
+    }
+}
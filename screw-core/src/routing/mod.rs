@@ -1,4 +1,6 @@
+pub mod method_override;
 pub mod middleware;
+pub mod middleware_stack;
 pub mod route;
 pub mod router;
 pub mod routes;
@@ -1,3 +1,15 @@
+use hyper::{Body, Request};
+use std::sync::Arc;
+
+pub(super) type Guard = Arc<dyn Fn(&Request<Body>) -> bool + Send + Sync + 'static>;
+
+/// What a request has to satisfy besides the method and the path to match a route.
+#[derive(Clone, Default)]
+pub(super) struct Conditions {
+    pub(super) content_types: Vec<&'static str>,
+    pub(super) guards: Vec<Guard>,
+}
+
 pub mod first {
     use super::*;
     use hyper::Method;
@@ -23,10 +35,22 @@ pub mod first {
             Self { methods: vec![] }
         }
 
+        /// Adds `method` to the methods the route matches, such as `HEAD` to a route of
+        /// `with_method(&Method::GET)`. A route matching any method, see `with_any_method`,
+        /// keeps doing so instead of being narrowed to `method`.
+        pub fn and_method<M: Into<&'static Method>>(mut self, method: M) -> Self {
+            let method = method.into();
+            if !self.methods.is_empty() && !self.methods.contains(&method) {
+                self.methods.push(method);
+            }
+            self
+        }
+
         pub fn and_path<P: Into<String>>(self, path: P) -> second::Route {
             second::Route {
                 methods: self.methods,
                 path: path.into(),
+                conditions: Conditions::default(),
             }
         }
     }
@@ -40,9 +64,49 @@ pub mod second {
     pub struct Route {
         pub(super) methods: Vec<&'static Method>,
         pub(super) path: String,
+        pub(super) conditions: Conditions,
     }
 
     impl Route {
+        /// Matches the route only for requests of the `content_type` media type, so the same
+        /// method and path can be routed to several handlers by `Content-Type`. A request no
+        /// route accepts the media type of reaches the fallback handler with
+        /// `FallbackReason::UnsupportedMediaType`. Routes match any media type by default.
+        pub fn and_content_type(mut self, content_type: &'static str) -> Self {
+            if !self.conditions.content_types.contains(&content_type) {
+                self.conditions.content_types.push(content_type);
+            }
+            self
+        }
+
+        /// Same as `and_content_type` for several media types.
+        pub fn and_content_types<C: Into<Vec<&'static str>>>(mut self, content_types: C) -> Self {
+            for content_type in content_types.into() {
+                self = self.and_content_type(content_type);
+            }
+            self
+        }
+
+        /// Matches the route only for requests `guard` returns `true` for, evaluated on every
+        /// request before middlewares run, so an endpoint can be dark-launched behind a flag
+        /// that changes at runtime. A request whose path only matches routes with failing
+        /// guards reaches the fallback handler with `FallbackReason::NotFound`.
+        pub fn and_guard<G>(mut self, guard: G) -> Self
+        where
+            G: Fn(&Request<Body>) -> bool + Send + Sync + 'static,
+        {
+            self.conditions.guards.push(Arc::new(guard));
+            self
+        }
+
+        pub(in super::super) fn and_conditions(mut self, conditions: Conditions) -> Self {
+            self.conditions
+                .content_types
+                .extend(conditions.content_types);
+            self.conditions.guards.extend(conditions.guards);
+            self
+        }
+
         pub fn and_handler<Rq, Rs, HFn, HFut>(self, handler: HFn) -> third::Route<Rq, Rs, HFn, HFut>
         where
             Rq: Send + 'static,
@@ -53,11 +117,55 @@ pub mod second {
             third::Route {
                 methods: self.methods,
                 path: self.path,
+                conditions: self.conditions,
                 handler,
                 _p_rq: Default::default(),
                 _p_h_fut: Default::default(),
             }
         }
+
+        /// Same as `and_handler`, passing a clone of `state` to every call. A handler written
+        /// as `move |request| async move { state.get(request) }` fails to compile, because
+        /// the `async move` block moves `state` out of a closure called more than once:
+        ///
+        /// ```compile_fail,E0507
+        /// # use hyper::Method;
+        /// # use screw_core::routing::route::first::Route;
+        /// # use std::sync::Arc;
+        /// let greeting = Arc::new(String::from("hello"));
+        /// Route::with_method(&Method::GET)
+        ///     .and_path("/greet")
+        ///     .and_handler(move |name: String| async move { format!("{greeting} {name}") });
+        /// ```
+        ///
+        /// With `|state, request| async move { state.get(request) }` each future owns its
+        /// clone:
+        ///
+        /// ```
+        /// # use hyper::Method;
+        /// # use screw_core::routing::route::first::Route;
+        /// # use std::sync::Arc;
+        /// let greeting = Arc::new(String::from("hello"));
+        /// Route::with_method(&Method::GET)
+        ///     .and_path("/greet")
+        ///     .and_handler_with_state(greeting, |greeting: Arc<String>, name: String| async move {
+        ///         format!("{greeting} {name}")
+        ///     });
+        /// ```
+        pub fn and_handler_with_state<S, Rq, Rs, HFn, HFut>(
+            self,
+            state: S,
+            handler: HFn,
+        ) -> third::Route<Rq, Rs, impl Fn(Rq) -> HFut + Send + Sync + 'static, HFut>
+        where
+            S: Clone + Send + Sync + 'static,
+            Rq: Send + 'static,
+            Rs: Send + 'static,
+            HFn: Fn(S, Rq) -> HFut + Send + Sync + 'static,
+            HFut: Future<Output = Rs> + Send + 'static,
+        {
+            self.and_handler(move |request| handler(state.clone(), request))
+        }
     }
 }
 
@@ -75,6 +183,7 @@ pub mod third {
     {
         pub(in super::super) methods: Vec<&'static Method>,
         pub(in super::super) path: String,
+        pub(in super::super) conditions: super::Conditions,
         pub(in super::super) handler: HFn,
         pub(super) _p_rq: PhantomData<Rq>,
         pub(super) _p_h_fut: PhantomData<HFut>,
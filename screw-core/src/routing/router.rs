@@ -1,16 +1,153 @@
+use super::super::phase_timer::PhaseTimer;
 use super::*;
 use actix::{Path, ResourceDef, Router as InnerRouter};
+use hyper::{Method, StatusCode};
 use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// Default of `Router::and_max_path_segments`.
+pub const DEFAULT_MAX_PATH_SEGMENTS: usize = 256;
+
+/// Path template of the route a request matched, such as `/users/{id}`. Besides
+/// `RoutedRequest::matched_path`, it is inserted into the extensions of the HTTP request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MatchedPath(Arc<str>);
+
+impl MatchedPath {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// How `Router::process` treats paths with empty, `.` or `..` segments, such as `/api//users`
+/// or `/api/./users`, see `Router::and_path_normalization`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathNormalization {
+    /// Such paths are matched as they are.
+    #[default]
+    Leave,
+    /// Empty and `.` segments are removed and `..` segments remove the previous one, never
+    /// going above the root, before matching. The HTTP request keeps its original path.
+    Normalize,
+    /// Such paths skip matching and reach the fallback handler with
+    /// `FallbackReason::NonCanonicalPath`.
+    Reject,
+}
+
+impl PathNormalization {
+    /// Returns `path` with its empty, `.` and `..` segments resolved, keeping a trailing
+    /// slash, or `None` when it has none of them.
+    fn normalize(path: &str) -> Option<String> {
+        let mut segments = Vec::new();
+        let mut canonical = true;
+        let raw_segments: Vec<&str> = path.strip_prefix('/').unwrap_or(path).split('/').collect();
+        let last_index = raw_segments.len() - 1;
+        for (index, segment) in raw_segments.iter().enumerate() {
+            match *segment {
+                "" if index == last_index => {}
+                "" | "." => canonical = false,
+                ".." => {
+                    canonical = false;
+                    segments.pop();
+                }
+                segment => segments.push(segment),
+            }
+        }
+        if canonical {
+            return None;
+        }
+        let trailing_slash = path.ends_with('/') || path.ends_with("/.") || path.ends_with("/..");
+        let mut normalized = format!("/{}", segments.join("/"));
+        if trailing_slash && !segments.is_empty() {
+            normalized.push('/');
+        }
+        Some(normalized)
+    }
+}
+
+/// Why a request reached the fallback handler, see `RoutedRequest::fallback_reason`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FallbackReason {
+    /// No route matches the path.
+    NotFound,
+    /// Routes match the path, but none of them accepts the method of the request.
+    MethodNotAllowed { allowed: Vec<&'static Method> },
+    /// Routes match the path and the method, but none of them accepts the media type of the
+    /// request, see `Route::and_content_type`.
+    UnsupportedMediaType { accepted: Vec<&'static str> },
+    /// The path is not valid percent-encoded UTF-8.
+    InvalidPath,
+    /// The path has empty, `.` or `..` segments, rejected by `PathNormalization::Reject`.
+    NonCanonicalPath,
+    /// The path has more segments than `Router::and_max_path_segments` allows.
+    TooManyPathSegments(usize),
+    /// The request is `OPTIONS *`, asking for the methods of the whole server, see
+    /// `Router::and_asterisk_options`. Usually responded with an `Allow` header listing
+    /// `allowed`.
+    AsteriskOptions { allowed: Vec<&'static Method> },
+}
+
+impl FallbackReason {
+    /// Returns the status code usually responded for this reason.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::AsteriskOptions { .. } => StatusCode::OK,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::MethodNotAllowed { .. } => StatusCode::METHOD_NOT_ALLOWED,
+            Self::UnsupportedMediaType { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::InvalidPath | Self::NonCanonicalPath | Self::TooManyPathSegments(_) => {
+                StatusCode::BAD_REQUEST
+            }
+        }
+    }
+}
 
 pub struct RoutedRequest<ORq> {
     pub path: Path<String>,
+    /// Decoded query pairs, keeping the last value of a repeated key, see `query_multimap`.
     pub query: HashMap<String, String>,
     pub origin: ORq,
+    matched_path: Option<MatchedPath>,
+    fallback_reason: Option<FallbackReason>,
+    raw_query: Option<String>,
+    query_multimap: OnceLock<HashMap<String, Vec<String>>>,
+}
+
+impl<ORq> RoutedRequest<ORq> {
+    /// Returns the path template of the matched route, or `None` for the fallback handler.
+    pub fn matched_path(&self) -> Option<&MatchedPath> {
+        self.matched_path.as_ref()
+    }
+
+    /// Returns why no route handles the request, or `None` outside the fallback handler.
+    pub fn fallback_reason(&self) -> Option<&FallbackReason> {
+        self.fallback_reason.as_ref()
+    }
+
+    /// Returns the query string as received, still percent-encoded, or `None` when the URI
+    /// has none.
+    pub fn raw_query(&self) -> Option<&str> {
+        self.raw_query.as_deref()
+    }
+
+    /// Returns the decoded values of every query key in the order they appear, so that
+    /// `?tag=a&tag=b` yields both tags. Parsed on first use.
+    pub fn query_multimap(&self) -> &HashMap<String, Vec<String>> {
+        self.query_multimap.get_or_init(|| {
+            let mut query_multimap: HashMap<String, Vec<String>> = HashMap::new();
+            if let Some(raw_query) = &self.raw_query {
+                for (key, value) in url::form_urlencoded::parse(raw_query.as_bytes()).into_owned() {
+                    query_multimap.entry(key).or_default().push(value);
+                }
+            }
+            query_multimap
+        })
+    }
 }
 
 pub mod first {
     use super::*;
-    use screw_components::dyn_fn::{AsDynFn, DFn};
     use std::future::Future;
 
     pub struct Router<ORq, ORs>
@@ -18,7 +155,18 @@ pub mod first {
         ORq: Send + 'static,
         ORs: Send + 'static,
     {
-        fallback_handler: DFn<RoutedRequest<ORq>, ORs>,
+        fallback_handler: routes::Handler<RoutedRequest<ORq>, ORs>,
+        aliases: Vec<(String, String)>,
+        rewrites: HashMap<String, String>,
+        default_timeout: Option<Duration>,
+        timeout_handler: Option<second::TimeoutHandler<ORs>>,
+        max_path_segments: usize,
+        path_segments_handler: Option<second::PathSegmentsHandler<ORs>>,
+        method_override: Option<method_override::MethodOverride>,
+        unused_next: middleware::UnusedNext,
+        path_normalization: PathNormalization,
+        asterisk_options: Option<Vec<&'static Method>>,
+        phase_timings: bool,
     }
 
     impl<ORq, ORs> Router<ORq, ORs>
@@ -26,32 +174,187 @@ pub mod first {
         ORq: Send + 'static,
         ORs: Send + 'static,
     {
+        /// The fallback handler responds to requests no route handles, telling why with
+        /// `RoutedRequest::fallback_reason`.
         pub fn with_fallback_handler<HFn, HFut>(fallback_handler: HFn) -> Self
         where
             HFn: Fn(RoutedRequest<ORq>) -> HFut + Send + Sync + 'static,
             HFut: Future<Output = ORs> + Send + 'static,
         {
             Router {
-                fallback_handler: fallback_handler.to_dyn_fn(),
+                fallback_handler: Arc::new(move |request| Box::pin(fallback_handler(request))),
+                aliases: Vec::new(),
+                rewrites: HashMap::new(),
+                default_timeout: None,
+                timeout_handler: None,
+                max_path_segments: DEFAULT_MAX_PATH_SEGMENTS,
+                path_segments_handler: None,
+                method_override: None,
+                unused_next: middleware::UnusedNext::default(),
+                path_normalization: PathNormalization::default(),
+                asterisk_options: None,
+                phase_timings: false,
             }
         }
 
+        /// Responds with `timeout_handler` when handling a request, middlewares included,
+        /// takes longer than the timeout of its route or `default_timeout`. Routes given a
+        /// timeout with `Routes::route_with_timeout` require a timeout handler, `and_routes`
+        /// panicking otherwise.
+        pub fn and_timeout_handler<HFn>(
+            mut self,
+            default_timeout: Option<Duration>,
+            timeout_handler: HFn,
+        ) -> Self
+        where
+            HFn: Fn(Duration) -> ORs + Send + Sync + 'static,
+        {
+            self.default_timeout = default_timeout;
+            self.timeout_handler = Some(Arc::new(timeout_handler));
+            self
+        }
+
+        /// Responds with `path_segments_handler`, given the number of segments, to requests whose
+        /// path has more than `max_path_segments` segments, before matching them against routes.
+        /// The handler would typically respond `400 Bad Request`. Without a handler, such paths
+        /// skip matching and reach the fallback handler, the limit being
+        /// `DEFAULT_MAX_PATH_SEGMENTS` by default.
+        pub fn and_max_path_segments<HFn>(
+            mut self,
+            max_path_segments: usize,
+            path_segments_handler: HFn,
+        ) -> Self
+        where
+            HFn: Fn(usize) -> ORs + Send + Sync + 'static,
+        {
+            self.max_path_segments = max_path_segments;
+            self.path_segments_handler = Some(Arc::new(path_segments_handler));
+            self
+        }
+
+        /// Rewrites the method of `POST` requests asking for another one before matching, see
+        /// `MethodOverride`. Disabled by default.
+        pub fn and_method_override(
+            mut self,
+            method_override: method_override::MethodOverride,
+        ) -> Self {
+            self.method_override = Some(method_override);
+            self
+        }
+
+        /// Reports middlewares of the routes returning without calling `next`, to catch
+        /// accidental short-circuits while developing. Deliberate ones, such as rejections, are
+        /// reported as well. Defaults to `UnusedNext::Ignore`.
+        pub fn and_unused_next(mut self, unused_next: middleware::UnusedNext) -> Self {
+            self.unused_next = unused_next;
+            self
+        }
+
+        /// Normalizes or rejects paths with empty, `.` or `..` segments before rewrites and
+        /// matching, as they are percent-decoded, so `%2e%2e` counts as `..`. Defaults to
+        /// `PathNormalization::Leave`.
+        pub fn and_path_normalization(mut self, path_normalization: PathNormalization) -> Self {
+            self.path_normalization = path_normalization;
+            self
+        }
+
+        /// Sends `OPTIONS *` requests, which ask for the methods of the whole server rather than
+        /// of a resource, to the fallback handler with `FallbackReason::AsteriskOptions`
+        /// carrying `allowed`, skipping matching. Without it, they reach the fallback handler
+        /// as `FallbackReason::NotFound` like any path no route matches.
+        pub fn and_asterisk_options<M: Into<Vec<&'static Method>>>(mut self, allowed: M) -> Self {
+            self.asterisk_options = Some(allowed.into());
+            self
+        }
+
+        /// Emits the durations of routing and handling every request as a `router phases`
+        /// event. Disabled by default, sparing the clock reads.
+        pub fn and_phase_timings(mut self, phase_timings: bool) -> Self {
+            self.phase_timings = phase_timings;
+            self
+        }
+
+        /// Registers every route whose path is exactly `to` under `from` as well.
+        /// Aliases are registered after all routes, so a real route matching `from` wins.
+        pub fn and_alias<F: Into<String>, T: Into<String>>(mut self, from: F, to: T) -> Self {
+            self.aliases.push((from.into(), to.into()));
+            self
+        }
+
+        /// Replaces a request path equal to `from` with `to` before matching.
+        /// Rewrites are applied first, so they take precedence over routes and aliases.
+        pub fn and_rewrite<F: Into<String>, T: Into<String>>(mut self, from: F, to: T) -> Self {
+            self.rewrites.insert(from.into(), to.into());
+            self
+        }
+
+        /// Builds the router from the routes added by `handler`.
+        ///
+        /// Panics when a route is added with `Routes::route_with_timeout` while no timeout
+        /// handler is set, see `and_timeout_handler`, as its timeout would never apply.
         pub fn and_routes<F>(self, handler: F) -> router::second::Router<ORq, ORs>
         where
             F: FnOnce(
                 routes::Routes<RoutedRequest<ORq>, ORs, ()>,
             ) -> routes::Routes<RoutedRequest<ORq>, ORs, ()>,
         {
-            let routes = handler(routes::Routes::new());
+            let routes = handler(routes::Routes::with_unused_next(self.unused_next));
+            let (mut handlers, required_extensions) = routes.finish();
+            if self.timeout_handler.is_none() {
+                if let Some((_, _, path, _, _)) = handlers
+                    .iter()
+                    .find(|(_, _, _, _, timeout)| timeout.is_some())
+                {
+                    panic!("route {path} has a timeout but the router has no timeout handler")
+                }
+            }
+            let alias_handlers: Vec<_> = self
+                .aliases
+                .iter()
+                .flat_map(|(from, to)| {
+                    handlers
+                        .iter()
+                        .filter(move |(_, _, path, _, _)| path == to)
+                        .map(move |(methods, conditions, _, handler, timeout)| {
+                            (
+                                methods.clone(),
+                                conditions.clone(),
+                                from.clone(),
+                                handler.clone(),
+                                *timeout,
+                            )
+                        })
+                })
+                .collect();
+            handlers.extend(alias_handlers);
             router::second::Router {
                 inner: {
                     let mut inner_router = InnerRouter::build();
-                    for (methods, path, handler) in routes.handlers() {
-                        inner_router.push(ResourceDef::new(path), handler, methods);
+                    for (methods, conditions, path, handler, timeout) in handlers {
+                        let route_entry = second::RouteEntry {
+                            handler,
+                            matched_path: MatchedPath(Arc::from(path.as_str())),
+                            timeout,
+                        };
+                        let route_guard = second::RouteGuard {
+                            methods,
+                            conditions,
+                        };
+                        inner_router.push(ResourceDef::new(path), route_entry, route_guard);
                     }
                     inner_router.finish()
                 },
                 fallback_handler: self.fallback_handler,
+                rewrites: self.rewrites,
+                default_timeout: self.default_timeout,
+                timeout_handler: self.timeout_handler,
+                max_path_segments: self.max_path_segments,
+                path_segments_handler: self.path_segments_handler,
+                method_override: self.method_override,
+                path_normalization: self.path_normalization,
+                asterisk_options: self.asterisk_options,
+                phase_timings: self.phase_timings,
+                required_extensions,
             }
         }
     }
@@ -59,35 +362,130 @@ pub mod first {
 
 pub mod second {
     use super::*;
-    use hyper::{Body, Method, Request};
-    use screw_components::dyn_fn::DFn;
+    use hyper::{header, Body, Request};
+
+    pub(super) type TimeoutHandler<ORs> = Arc<dyn Fn(Duration) -> ORs + Send + Sync + 'static>;
+    pub(super) type PathSegmentsHandler<ORs> = Arc<dyn Fn(usize) -> ORs + Send + Sync + 'static>;
+
+    pub(super) struct RouteGuard {
+        pub(super) methods: Vec<&'static Method>,
+        pub(super) conditions: route::Conditions,
+    }
+
+    impl RouteGuard {
+        fn accepts_request(&self, http_request: &Request<Body>) -> bool {
+            self.conditions
+                .guards
+                .iter()
+                .all(|guard| guard(http_request))
+        }
+
+        fn accepts_method(&self, method: &Method) -> bool {
+            self.methods.is_empty() || self.methods.contains(&method)
+        }
+
+        fn accepts_media_type(&self, media_type: Option<&str>) -> bool {
+            self.conditions.content_types.is_empty()
+                || media_type.is_some_and(|media_type| {
+                    self.conditions
+                        .content_types
+                        .iter()
+                        .any(|content_type| content_type.eq_ignore_ascii_case(media_type))
+                })
+        }
+    }
+
+    pub(super) struct RouteEntry<ORq, ORs> {
+        pub(super) handler: routes::Handler<RoutedRequest<ORq>, ORs>,
+        pub(super) matched_path: MatchedPath,
+        pub(super) timeout: Option<Duration>,
+    }
 
     pub struct Router<ORq, ORs>
     where
         ORq: Send + 'static,
         ORs: Send + 'static,
     {
-        pub(super) inner: InnerRouter<DFn<RoutedRequest<ORq>, ORs>, Vec<&'static Method>>,
-        pub(super) fallback_handler: DFn<RoutedRequest<ORq>, ORs>,
+        pub(super) inner: InnerRouter<RouteEntry<ORq, ORs>, RouteGuard>,
+        pub(super) fallback_handler: routes::Handler<RoutedRequest<ORq>, ORs>,
+        pub(super) rewrites: HashMap<String, String>,
+        pub(super) default_timeout: Option<Duration>,
+        pub(super) timeout_handler: Option<TimeoutHandler<ORs>>,
+        pub(super) max_path_segments: usize,
+        pub(super) path_segments_handler: Option<PathSegmentsHandler<ORs>>,
+        pub(super) method_override: Option<method_override::MethodOverride>,
+        pub(super) path_normalization: PathNormalization,
+        pub(super) asterisk_options: Option<Vec<&'static Method>>,
+        pub(super) phase_timings: bool,
+        pub(super) required_extensions: Vec<middleware::RequiredExtension>,
+    }
+
+    impl<ORq, ORs> Router<ORq, ORs>
+    where
+        ORq: Send + 'static,
+        ORs: Send + 'static,
+    {
+        pub fn required_extensions(&self) -> &[middleware::RequiredExtension] {
+            &self.required_extensions
+        }
     }
 
     impl<ORq, ORs> Router<ORq, ORs>
     where
-        ORq: AsRef<Request<Body>> + Send + 'static,
+        ORq: AsRef<Request<Body>> + AsMut<Request<Body>> + Send + 'static,
         ORs: Send + 'static,
     {
-        pub async fn process(&self, request: ORq) -> ORs {
+        pub async fn process(&self, mut request: ORq) -> ORs {
+            let mut phase_timer = PhaseTimer::start(self.phase_timings);
+            if let Some(method_override) = &self.method_override {
+                method_override.apply(request.as_mut()).await;
+            }
             let http_request_ref = request.as_ref();
 
             let method = http_request_ref.method();
-            let mut path = Path::new(
-                urlencoding::decode(http_request_ref.uri().path())
-                    .unwrap_or_default()
-                    .into_owned(),
-            );
-            let query = http_request_ref
+            let media_type = http_request_ref
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|header_value| header_value.to_str().ok())
+                .and_then(|content_type| content_type.split(';').next())
+                .map(str::trim);
+            let path_segments = http_request_ref
                 .uri()
-                .query()
+                .path()
+                .bytes()
+                .filter(|byte| *byte == b'/')
+                .count();
+            let too_many_path_segments = path_segments > self.max_path_segments;
+            if too_many_path_segments {
+                tracing::debug!(
+                    target: "screw::router",
+                    path_segments,
+                    max_path_segments = self.max_path_segments,
+                    "too many path segments"
+                );
+                if let Some(path_segments_handler) = &self.path_segments_handler {
+                    return path_segments_handler(path_segments);
+                }
+            }
+            let decoded_path = urlencoding::decode(http_request_ref.uri().path());
+            let invalid_path = decoded_path.is_err();
+            let mut decoded_path = decoded_path.unwrap_or_default().into_owned();
+            let mut non_canonical_path = false;
+            if self.path_normalization != PathNormalization::Leave {
+                if let Some(normalized_path) = PathNormalization::normalize(&decoded_path) {
+                    match self.path_normalization {
+                        PathNormalization::Normalize => decoded_path = normalized_path,
+                        _ => non_canonical_path = true,
+                    }
+                }
+            }
+            let mut path = Path::new(match self.rewrites.get(&decoded_path) {
+                Some(rewritten_path) => rewritten_path.clone(),
+                None => decoded_path,
+            });
+            let raw_query = http_request_ref.uri().query().map(str::to_owned);
+            let query = raw_query
+                .as_ref()
                 .map(|v| {
                     url::form_urlencoded::parse(v.as_bytes())
                         .into_owned()
@@ -95,25 +493,467 @@ pub mod second {
                 })
                 .unwrap_or_default();
 
-            let handler = self
-                .inner
-                .recognize_fn(&mut path, |_, m| {
-                    if !m.is_empty() {
-                        m.contains(&method)
-                    } else {
-                        true
-                    }
+            let asterisk_options = self
+                .asterisk_options
+                .as_ref()
+                .filter(|_| method == Method::OPTIONS && http_request_ref.uri().path() == "*");
+
+            let recognized = if too_many_path_segments
+                || invalid_path
+                || non_canonical_path
+                || asterisk_options.is_some()
+            {
+                None
+            } else {
+                self.inner.recognize_fn(&mut path, |_, route_guard| {
+                    route_guard.accepts_method(method)
+                        && route_guard.accepts_media_type(media_type)
+                        && route_guard.accepts_request(http_request_ref)
                 })
-                .map(|r| r.0)
-                .unwrap_or(&self.fallback_handler);
+            };
+            let (handler, matched_path, timeout, fallback_reason) = match recognized {
+                Some((route_entry, _)) => (
+                    &route_entry.handler,
+                    Some(route_entry.matched_path.clone()),
+                    route_entry.timeout,
+                    None,
+                ),
+                None => {
+                    let fallback_reason = if let Some(allowed) = asterisk_options {
+                        FallbackReason::AsteriskOptions {
+                            allowed: allowed.clone(),
+                        }
+                    } else if too_many_path_segments {
+                        FallbackReason::TooManyPathSegments(path_segments)
+                    } else if invalid_path {
+                        FallbackReason::InvalidPath
+                    } else if non_canonical_path {
+                        FallbackReason::NonCanonicalPath
+                    } else {
+                        self.classify_miss(&path, http_request_ref)
+                    };
+                    (&self.fallback_handler, None, None, Some(fallback_reason))
+                }
+            };
+            let timeout = timeout.or(self.default_timeout);
 
+            if let Some(matched_path) = &matched_path {
+                request
+                    .as_mut()
+                    .extensions_mut()
+                    .insert(matched_path.clone());
+            }
             let request = RoutedRequest {
                 path,
                 query,
                 origin: request,
+                matched_path,
+                fallback_reason,
+                raw_query,
+                query_multimap: OnceLock::new(),
             };
-            let response = handler(request).await;
+            let routing_elapsed = phase_timer.lap();
+
+            let response = match (timeout, &self.timeout_handler) {
+                (Some(timeout), Some(timeout_handler)) => {
+                    match tokio::time::timeout(timeout, handler(request)).await {
+                        Ok(response) => response,
+                        Err(_) => {
+                            tracing::warn!(target: "screw::router", ?timeout, "handler timed out");
+                            timeout_handler(timeout)
+                        }
+                    }
+                }
+                _ => handler(request).await,
+            };
+            if phase_timer.is_enabled() {
+                tracing::debug!(
+                    target: "screw::router",
+                    routing = ?routing_elapsed,
+                    handling = ?phase_timer.lap(),
+                    "router phases"
+                );
+            }
             response
         }
+
+        /// Methods of the routes matching the path of `http_request`, whatever its method,
+        /// such as the `Allow` header of a `405 Method Not Allowed` response lists. Paths
+        /// `process` skips matching for, such as those rejected by `PathNormalization::Reject`,
+        /// have none.
+        pub fn allowed_methods(&self, http_request: &Request<Body>) -> Vec<&'static Method> {
+            let path_segments = http_request
+                .uri()
+                .path()
+                .bytes()
+                .filter(|byte| *byte == b'/')
+                .count();
+            if path_segments > self.max_path_segments {
+                return Vec::new();
+            }
+            let mut decoded_path = match urlencoding::decode(http_request.uri().path()) {
+                Ok(decoded_path) => decoded_path.into_owned(),
+                Err(_) => return Vec::new(),
+            };
+            if self.path_normalization != PathNormalization::Leave {
+                if let Some(normalized_path) = PathNormalization::normalize(&decoded_path) {
+                    match self.path_normalization {
+                        PathNormalization::Normalize => decoded_path = normalized_path,
+                        _ => return Vec::new(),
+                    }
+                }
+            }
+            let mut path = Path::new(match self.rewrites.get(&decoded_path) {
+                Some(rewritten_path) => rewritten_path.clone(),
+                None => decoded_path,
+            });
+            let mut allowed = Vec::new();
+            self.inner.recognize_fn(&mut path, |_, route_guard| {
+                if route_guard.accepts_request(http_request) {
+                    for allowed_method in &route_guard.methods {
+                        if !allowed.contains(allowed_method) {
+                            allowed.push(*allowed_method);
+                        }
+                    }
+                }
+                false
+            });
+            allowed
+        }
+
+        fn classify_miss(
+            &self,
+            path: &Path<String>,
+            http_request: &Request<Body>,
+        ) -> FallbackReason {
+            let method = http_request.method();
+            let mut allowed = Vec::new();
+            let mut accepted = Vec::new();
+            let mut method_matched = false;
+            self.inner
+                .recognize_fn(&mut path.clone(), |_, route_guard| {
+                    if !route_guard.accepts_request(http_request) {
+                        return false;
+                    }
+                    for allowed_method in &route_guard.methods {
+                        if !allowed.contains(allowed_method) {
+                            allowed.push(*allowed_method);
+                        }
+                    }
+                    if route_guard.accepts_method(method) {
+                        method_matched = true;
+                        for content_type in &route_guard.conditions.content_types {
+                            if !accepted.contains(content_type) {
+                                accepted.push(*content_type);
+                            }
+                        }
+                    }
+                    false
+                });
+            if method_matched {
+                FallbackReason::UnsupportedMediaType { accepted }
+            } else if allowed.is_empty() {
+                FallbackReason::NotFound
+            } else {
+                FallbackReason::MethodNotAllowed { allowed }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::request::Request;
+    use super::*;
+    use hyper::Body;
+    use route::first::Route;
+
+    fn router(
+        configure: impl FnOnce(
+            first::Router<Request<()>, Option<FallbackReason>>,
+        ) -> first::Router<Request<()>, Option<FallbackReason>>,
+    ) -> second::Router<Request<()>, Option<FallbackReason>> {
+        let router = first::Router::with_fallback_handler(
+            |request: RoutedRequest<Request<()>>| async move { request.fallback_reason().cloned() },
+        );
+        configure(router).and_routes(|routes| {
+            routes
+                .route(
+                    Route::with_methods([&Method::GET, &Method::OPTIONS])
+                        .and_path("/users")
+                        .and_handler(|_: RoutedRequest<Request<()>>| async { None }),
+                )
+                .route(
+                    Route::with_methods([&Method::POST])
+                        .and_path("/users")
+                        .and_content_types(["application/json", "application/xml"])
+                        .and_handler(|_: RoutedRequest<Request<()>>| async { None }),
+                )
+        })
+    }
+
+    fn request(method: Method, uri: &str) -> Request<()> {
+        Request::with_http(
+            hyper::Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    }
+
+    fn post_request(content_type: Option<&str>) -> Request<()> {
+        let mut request = request(Method::POST, "/users");
+        if let Some(content_type) = content_type {
+            request
+                .http
+                .headers_mut()
+                .insert(hyper::header::CONTENT_TYPE, content_type.parse().unwrap());
+        }
+        request
+    }
+
+    #[tokio::test]
+    async fn leaves_non_canonical_paths_by_default() {
+        let router = router(|router| router);
+
+        assert_eq!(router.process(request(Method::GET, "/users")).await, None);
+        assert_eq!(
+            router.process(request(Method::GET, "/./users")).await,
+            Some(FallbackReason::NotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn normalizes_non_canonical_paths() {
+        let router = router(|router| router.and_path_normalization(PathNormalization::Normalize));
+
+        for uri in [
+            "/users",
+            "//users",
+            "/./users",
+            "/api/../users",
+            "/%2e%2e/users",
+        ] {
+            assert_eq!(
+                router.process(request(Method::GET, uri)).await,
+                None,
+                "{uri}"
+            );
+        }
+        assert_eq!(
+            router.allowed_methods(&request(Method::DELETE, "/./users").http),
+            [&Method::GET, &Method::OPTIONS, &Method::POST]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_non_canonical_paths() {
+        let router = router(|router| router.and_path_normalization(PathNormalization::Reject));
+
+        assert_eq!(router.process(request(Method::GET, "/users")).await, None);
+        for uri in ["//users", "/./users", "/api/../users", "/%2e%2e/users"] {
+            assert_eq!(
+                router.process(request(Method::GET, uri)).await,
+                Some(FallbackReason::NonCanonicalPath),
+                "{uri}"
+            );
+            assert!(router
+                .allowed_methods(&request(Method::DELETE, uri).http)
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn normalizes_paths_keeping_trailing_slashes() {
+        assert_eq!(PathNormalization::normalize("/users/7"), None);
+        assert_eq!(PathNormalization::normalize("/users/7/"), None);
+        assert_eq!(
+            PathNormalization::normalize("/users//7/").as_deref(),
+            Some("/users/7/")
+        );
+        assert_eq!(
+            PathNormalization::normalize("/users/7/..").as_deref(),
+            Some("/users/")
+        );
+        assert_eq!(PathNormalization::normalize("/../..").as_deref(), Some("/"));
+    }
+
+    #[tokio::test]
+    async fn paths_with_too_many_segments_reach_the_fallback_handler() {
+        let router = router(|router| router);
+        let uri = "/users".repeat(DEFAULT_MAX_PATH_SEGMENTS + 1);
+
+        assert_eq!(
+            router.process(request(Method::GET, &uri)).await,
+            Some(FallbackReason::TooManyPathSegments(
+                DEFAULT_MAX_PATH_SEGMENTS + 1
+            ))
+        );
+        assert!(router
+            .allowed_methods(&request(Method::GET, &uri).http)
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn paths_with_too_many_segments_respond_with_the_segments_handler() {
+        let router = router(|router| {
+            router.and_max_path_segments(2, |path_segments| {
+                Some(FallbackReason::TooManyPathSegments(path_segments * 10))
+            })
+        });
+
+        assert_eq!(
+            router.process(request(Method::GET, "/a/b/c")).await,
+            Some(FallbackReason::TooManyPathSegments(30))
+        );
+        assert_eq!(
+            router.process(request(Method::GET, "/a/b")).await,
+            Some(FallbackReason::NotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn routes_by_content_type() {
+        let router = router(|router| router);
+
+        for content_type in ["application/json", "Application/XML; charset=utf-8"] {
+            assert_eq!(
+                router.process(post_request(Some(content_type))).await,
+                None,
+                "{content_type}"
+            );
+        }
+        for content_type in [Some("text/plain"), None] {
+            assert_eq!(
+                router.process(post_request(content_type)).await,
+                Some(FallbackReason::UnsupportedMediaType {
+                    accepted: vec!["application/json", "application/xml"],
+                }),
+                "{content_type:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn misses_tell_why_they_reach_the_fallback_handler() {
+        let router = router(|router| router);
+
+        assert_eq!(
+            router.process(request(Method::DELETE, "/users")).await,
+            Some(FallbackReason::MethodNotAllowed {
+                allowed: vec![&Method::GET, &Method::OPTIONS, &Method::POST],
+            })
+        );
+        assert_eq!(
+            router.process(request(Method::GET, "/unknown")).await,
+            Some(FallbackReason::NotFound)
+        );
+        assert_eq!(
+            router.process(request(Method::GET, "/users%FF")).await,
+            Some(FallbackReason::InvalidPath)
+        );
+    }
+
+    #[tokio::test]
+    async fn added_methods_extend_the_methods_of_a_route() {
+        let router = first::Router::with_fallback_handler(
+            |request: RoutedRequest<Request<()>>| async move { request.fallback_reason().cloned() },
+        )
+        .and_routes(|routes| {
+            routes
+                .route(
+                    Route::with_method(&Method::GET)
+                        .and_method(&Method::HEAD)
+                        .and_method(&Method::GET)
+                        .and_path("/items")
+                        .and_handler(|_: RoutedRequest<Request<()>>| async { None }),
+                )
+                .route(
+                    Route::with_any_method()
+                        .and_method(&Method::POST)
+                        .and_path("/any")
+                        .and_handler(|_: RoutedRequest<Request<()>>| async { None }),
+                )
+        });
+
+        for method in [Method::GET, Method::HEAD] {
+            assert_eq!(router.process(request(method, "/items")).await, None);
+        }
+        assert_eq!(
+            router.process(request(Method::POST, "/items")).await,
+            Some(FallbackReason::MethodNotAllowed {
+                allowed: vec![&Method::GET, &Method::HEAD],
+            })
+        );
+        for method in [Method::GET, Method::POST, Method::DELETE] {
+            assert_eq!(router.process(request(method, "/any")).await, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn asterisk_options_reach_the_fallback_handler_with_allowed_methods() {
+        let router = router(|router| router.and_asterisk_options([&Method::GET, &Method::OPTIONS]));
+
+        assert_eq!(
+            router.process(request(Method::OPTIONS, "*")).await,
+            Some(FallbackReason::AsteriskOptions {
+                allowed: vec![&Method::GET, &Method::OPTIONS],
+            })
+        );
+        assert_eq!(
+            router.process(request(Method::OPTIONS, "/users")).await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn asterisk_options_are_not_found_by_default() {
+        let router = router(|router| router);
+
+        assert_eq!(
+            router.process(request(Method::OPTIONS, "*")).await,
+            Some(FallbackReason::NotFound)
+        );
+        assert_eq!(
+            router.process(request(Method::OPTIONS, "/users")).await,
+            None
+        );
+    }
+
+    fn timed_out_route(
+        router: first::Router<Request<()>, Option<FallbackReason>>,
+    ) -> second::Router<Request<()>, Option<FallbackReason>> {
+        router.and_routes(|routes| {
+            routes.route_with_timeout(
+                Route::with_methods([&Method::GET])
+                    .and_path("/slow")
+                    .and_handler(|_: RoutedRequest<Request<()>>| {
+                        std::future::pending::<Option<FallbackReason>>()
+                    }),
+                Duration::from_millis(10),
+            )
+        })
+    }
+
+    #[tokio::test]
+    async fn route_timeout_responds_with_the_timeout_handler() {
+        let router = timed_out_route(
+            first::Router::with_fallback_handler(|_: RoutedRequest<Request<()>>| async { None })
+                .and_timeout_handler(None, |_| Some(FallbackReason::NotFound)),
+        );
+
+        assert_eq!(
+            router.process(request(Method::GET, "/slow")).await,
+            Some(FallbackReason::NotFound)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "route /slow has a timeout but the router has no timeout handler")]
+    fn route_timeout_requires_a_timeout_handler() {
+        timed_out_route(first::Router::with_fallback_handler(
+            |_: RoutedRequest<Request<()>>| async { None },
+        ));
     }
 }
@@ -1,8 +1,21 @@
 use super::*;
 use hyper::Method;
-use screw_components::dyn_fn::{DFn, DFnOnce};
+use middleware::{RequiredExtension, UnusedNext};
+use screw_components::dyn_fn::{DFnOnce, DFuture};
+use std::any::type_name;
 use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+
+pub type Handler<Rq, Rs> = Arc<dyn Fn(Rq) -> DFuture<Rs> + Send + Sync + 'static>;
+type Handlers<Rq, Rs> = Vec<(
+    Vec<&'static Method>,
+    route::Conditions,
+    String,
+    Handler<Rq, Rs>,
+    Option<Duration>,
+)>;
 
 pub struct Routes<ORq, ORs, M>
 where
@@ -12,7 +25,9 @@ where
 {
     scope_path: String,
     middleware: Arc<M>,
-    handlers: Vec<(Vec<&'static Method>, String, DFn<ORq, ORs>)>,
+    handlers: Handlers<ORq, ORs>,
+    required_extensions: Vec<RequiredExtension>,
+    unused_next: UnusedNext,
 }
 
 impl<ORq, ORs> Routes<ORq, ORs, ()>
@@ -21,14 +36,19 @@ where
     ORs: Send + 'static,
 {
     pub(super) fn new() -> Self {
+        Self::with_unused_next(UnusedNext::default())
+    }
+    pub(super) fn with_unused_next(unused_next: UnusedNext) -> Self {
         Self {
             scope_path: "".to_owned(),
             middleware: Arc::new(()),
             handlers: Vec::new(),
+            required_extensions: Vec::new(),
+            unused_next,
         }
     }
-    pub(super) fn handlers(self) -> Vec<(Vec<&'static Method>, String, DFn<ORq, ORs>)> {
-        self.handlers
+    pub(super) fn finish(self) -> (Handlers<ORq, ORs>, Vec<RequiredExtension>) {
+        (self.handlers, self.required_extensions)
     }
 }
 
@@ -42,15 +62,23 @@ where
     where
         F: FnOnce(Routes<ORq, ORs, M>) -> Routes<ORq, ORs, M>,
     {
-        let Self { handlers, .. } = handler(Self {
+        let Self {
+            handlers,
+            required_extensions,
+            ..
+        } = handler(Self {
             scope_path: self.scope_path.clone() + scope_path,
             middleware: self.middleware.clone(),
             handlers: self.handlers,
+            required_extensions: self.required_extensions,
+            unused_next: self.unused_next,
         });
         Self {
             scope_path: self.scope_path,
             middleware: self.middleware,
             handlers,
+            required_extensions,
+            unused_next: self.unused_next,
         }
     }
 
@@ -69,29 +97,37 @@ where
     {
         let Routes {
             handlers: middleware_handlers,
+            required_extensions: middleware_required_extensions,
             ..
         } = handler(Routes {
             scope_path: self.scope_path.clone() + scope_path,
             middleware: Arc::new(middleware),
             handlers: Vec::new(),
+            required_extensions: Vec::new(),
+            unused_next: self.unused_next,
         });
-        let handlers = {
-            let mut handlers = self.handlers;
-            for (methods, path, middleware_handler) in middleware_handlers {
-                Self::add_route_to_handlers(
-                    route::first::Route::with_methods(methods)
-                        .and_path(path)
-                        .and_handler(middleware_handler),
-                    &mut handlers,
-                    self.middleware.clone(),
-                )
-            }
-            handlers
-        };
+        let mut handlers = self.handlers;
+        let mut required_extensions = self.required_extensions;
+        required_extensions.extend(middleware_required_extensions);
+        for (methods, conditions, path, middleware_handler, timeout) in middleware_handlers {
+            Self::add_route_to_handlers(
+                route::first::Route::with_methods(methods)
+                    .and_path(path)
+                    .and_conditions(conditions)
+                    .and_handler(move |request| middleware_handler(request)),
+                timeout,
+                &mut handlers,
+                &mut required_extensions,
+                self.middleware.clone(),
+                self.unused_next,
+            )
+        }
         Self {
             scope_path: self.scope_path,
             middleware: self.middleware,
             handlers,
+            required_extensions,
+            unused_next: self.unused_next,
         }
     }
 
@@ -110,6 +146,83 @@ where
         self,
         route: route::third::Route<FRq, IRs, HFn, HFut>,
     ) -> Self
+    where
+        M: middleware::Middleware<Rq, Rs, Request = ORq, Response = ORs>,
+        FRq: From<Rq> + Send + 'static,
+        Rq: Send + 'static,
+        IRs: Into<Rs> + Send + 'static,
+        Rs: Send + 'static,
+        HFn: Fn(FRq) -> HFut + Send + Sync + 'static,
+        HFut: Future<Output = IRs> + Send + 'static,
+    {
+        self.add_route(route, None)
+    }
+
+    /// Same as `route` when `condition` holds, leaving the route out otherwise, e.g. for
+    /// endpoints enabled by configuration read once at startup. Routes toggled at runtime are
+    /// guarded by `Route::and_guard` instead.
+    pub fn route_if<FRq, Rq, IRs, Rs, HFn, HFut>(
+        self,
+        condition: bool,
+        route: route::third::Route<FRq, IRs, HFn, HFut>,
+    ) -> Self
+    where
+        M: middleware::Middleware<Rq, Rs, Request = ORq, Response = ORs>,
+        FRq: From<Rq> + Send + 'static,
+        Rq: Send + 'static,
+        IRs: Into<Rs> + Send + 'static,
+        Rs: Send + 'static,
+        HFn: Fn(FRq) -> HFut + Send + Sync + 'static,
+        HFut: Future<Output = IRs> + Send + 'static,
+    {
+        if condition {
+            self.add_route(route, None)
+        } else {
+            self
+        }
+    }
+
+    /// Same as `route` for every route of `routes`, for route tables built at runtime.
+    pub fn routes<FRq, Rq, IRs, Rs, HFn, HFut, I>(self, routes: I) -> Self
+    where
+        M: middleware::Middleware<Rq, Rs, Request = ORq, Response = ORs>,
+        FRq: From<Rq> + Send + 'static,
+        Rq: Send + 'static,
+        IRs: Into<Rs> + Send + 'static,
+        Rs: Send + 'static,
+        HFn: Fn(FRq) -> HFut + Send + Sync + 'static,
+        HFut: Future<Output = IRs> + Send + 'static,
+        I: IntoIterator<Item = route::third::Route<FRq, IRs, HFn, HFut>>,
+    {
+        routes
+            .into_iter()
+            .fold(self, |routes, route| routes.add_route(route, None))
+    }
+
+    /// Same as `route`, overriding the default timeout of the router for this route,
+    /// see `Router::and_timeout_handler`, which the router must have.
+    pub fn route_with_timeout<FRq, Rq, IRs, Rs, HFn, HFut>(
+        self,
+        route: route::third::Route<FRq, IRs, HFn, HFut>,
+        timeout: Duration,
+    ) -> Self
+    where
+        M: middleware::Middleware<Rq, Rs, Request = ORq, Response = ORs>,
+        FRq: From<Rq> + Send + 'static,
+        Rq: Send + 'static,
+        IRs: Into<Rs> + Send + 'static,
+        Rs: Send + 'static,
+        HFn: Fn(FRq) -> HFut + Send + Sync + 'static,
+        HFut: Future<Output = IRs> + Send + 'static,
+    {
+        self.add_route(route, Some(timeout))
+    }
+
+    fn add_route<FRq, Rq, IRs, Rs, HFn, HFut>(
+        self,
+        route: route::third::Route<FRq, IRs, HFn, HFut>,
+        timeout: Option<Duration>,
+    ) -> Self
     where
         M: middleware::Middleware<Rq, Rs, Request = ORq, Response = ORs>,
         FRq: From<Rq> + Send + 'static,
@@ -123,27 +236,44 @@ where
             scope_path,
             middleware,
             mut handlers,
+            mut required_extensions,
+            unused_next,
         } = self;
         {
             Self::add_route_to_handlers(
                 route::first::Route::with_methods(route.methods)
                     .and_path(scope_path.clone() + route.path.as_str())
+                    .and_conditions(route.conditions)
                     .and_handler(route.handler),
+                timeout,
                 &mut handlers,
+                &mut required_extensions,
                 middleware.clone(),
+                unused_next,
             )
         }
         Self {
             scope_path,
             middleware,
             handlers,
+            required_extensions,
+            unused_next,
         }
     }
 
+    /// Declares an extension the handlers of these routes rely on, see `RequiredExtension`.
+    pub fn requiring_extension<T: Send + Sync + 'static>(mut self) -> Self {
+        self.required_extensions.push(RequiredExtension::of::<T>());
+        self
+    }
+
     fn add_route_to_handlers<FRq, Rq, IRs, Rs, HFn, HFut>(
         route: route::third::Route<FRq, IRs, HFn, HFut>,
-        handlers: &mut Vec<(Vec<&'static Method>, String, DFn<ORq, ORs>)>,
+        timeout: Option<Duration>,
+        handlers: &mut Handlers<ORq, ORs>,
+        required_extensions: &mut Vec<RequiredExtension>,
         middleware: Arc<M>,
+        unused_next: UnusedNext,
     ) where
         M: middleware::Middleware<Rq, Rs, Request = ORq, Response = ORs>,
         FRq: From<Rq> + Send + 'static,
@@ -153,24 +283,80 @@ where
         HFn: Fn(FRq) -> HFut + Send + Sync + 'static,
         HFut: Future<Output = IRs> + Send + 'static,
     {
+        for required_extension in middleware.required_extensions() {
+            if !required_extensions
+                .iter()
+                .any(|r| r.type_id() == required_extension.type_id())
+            {
+                required_extensions.push(required_extension);
+            }
+        }
         let handler = Arc::new(route.handler);
         let middleware = middleware.clone();
+        let path = route.path.clone();
         handlers.push((
             route.methods,
+            route.conditions,
             route.path,
-            Box::new(move |request| {
+            Arc::new(move |request| {
                 let handler = handler.clone();
                 let middleware = middleware.clone();
+                let path = path.clone();
                 Box::pin(async move {
+                    let next_called = Arc::new(AtomicBool::new(false));
+                    let next_called_by_next = next_called.clone();
                     let next: DFnOnce<Rq, Rs> = Box::new(move |rq| {
+                        next_called_by_next.store(true, Ordering::Relaxed);
                         Box::pin(async move {
                             let rs = handler(From::from(rq)).await.into();
                             rs
                         })
                     });
-                    middleware.respond(request, next).await
+                    let response = middleware.respond(request, next).await;
+                    if !next_called.load(Ordering::Relaxed) {
+                        unused_next.report(type_name::<M>(), &path);
+                    }
+                    response
                 })
             }),
+            timeout,
         ));
     }
 }
+
+impl<ORq, ORs, FRq, IRs, HFn, HFut> Extend<route::third::Route<FRq, IRs, HFn, HFut>>
+    for Routes<ORq, ORs, ()>
+where
+    ORq: Send + 'static,
+    ORs: Send + 'static,
+    FRq: From<ORq> + Send + 'static,
+    IRs: Into<ORs> + Send + 'static,
+    HFn: Fn(FRq) -> HFut + Send + Sync + 'static,
+    HFut: Future<Output = IRs> + Send + 'static,
+{
+    fn extend<I: IntoIterator<Item = route::third::Route<FRq, IRs, HFn, HFut>>>(
+        &mut self,
+        routes: I,
+    ) {
+        let this = std::mem::replace(self, Self::new());
+        *self = this.routes(routes);
+    }
+}
+
+/// Collects routes without a middleware, e.g. `router.and_routes(|_| table.into_iter().collect())`.
+impl<ORq, ORs, FRq, IRs, HFn, HFut> FromIterator<route::third::Route<FRq, IRs, HFn, HFut>>
+    for Routes<ORq, ORs, ()>
+where
+    ORq: Send + 'static,
+    ORs: Send + 'static,
+    FRq: From<ORq> + Send + 'static,
+    IRs: Into<ORs> + Send + 'static,
+    HFn: Fn(FRq) -> HFut + Send + Sync + 'static,
+    HFut: Future<Output = IRs> + Send + 'static,
+{
+    fn from_iter<I: IntoIterator<Item = route::third::Route<FRq, IRs, HFn, HFut>>>(
+        routes: I,
+    ) -> Self {
+        Self::new().routes(routes)
+    }
+}
@@ -0,0 +1,251 @@
+use futures_util::{Stream, StreamExt};
+use hyper::body::{Bytes, HttpBody};
+use hyper::header;
+use hyper::{Body, Request, StatusCode, Version};
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Bytes of request bodies in flight across the server, see
+/// `ServerBuilder::and_body_budget`.
+pub(super) struct BodyBudget {
+    max_bytes: u64,
+    used_bytes: AtomicU64,
+}
+
+impl BodyBudget {
+    pub(super) fn with_max_bytes(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            used_bytes: AtomicU64::new(0),
+        }
+    }
+
+    fn try_reserve(&self, bytes: u64) -> bool {
+        self.used_bytes
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used_bytes| {
+                used_bytes
+                    .checked_add(bytes)
+                    .filter(|used_bytes| *used_bytes <= self.max_bytes)
+            })
+            .is_ok()
+    }
+}
+
+/// Error an HTTP/1 body of unknown length fails to be read with once a chunk does not fit in
+/// the `ServerBuilder::and_body_budget` budget, found in the source chain of the
+/// `hyper::Error`. HTTP/2 bodies are aborted instead.
+#[derive(Clone, Copy, Debug)]
+pub struct BodyBudgetExhausted;
+
+impl fmt::Display for BodyBudgetExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request body budget exhausted")
+    }
+}
+
+impl Error for BodyBudgetExhausted {}
+
+impl BodyBudgetExhausted {
+    /// Whether `error`, or any of its sources, is a `BodyBudgetExhausted`.
+    pub fn is_source_of(error: &(dyn Error + 'static)) -> bool {
+        let mut source = Some(error);
+        while let Some(error) = source {
+            if error.is::<BodyBudgetExhausted>() {
+                return true;
+            }
+            source = error.source();
+        }
+        false
+    }
+}
+
+/// Bytes reserved for one request, given back to the budget once dropped, after both the
+/// response and the request body are done with.
+pub(super) struct BodyBudgetReservation {
+    budget: Arc<BodyBudget>,
+    bytes: AtomicU64,
+}
+
+impl BodyBudgetReservation {
+    fn try_reserve(&self, bytes: u64) -> bool {
+        let reserved = self.budget.try_reserve(bytes);
+        if reserved {
+            self.bytes.fetch_add(bytes, Ordering::AcqRel);
+        }
+        reserved
+    }
+}
+
+impl Drop for BodyBudgetReservation {
+    fn drop(&mut self) {
+        self.budget
+            .used_bytes
+            .fetch_sub(*self.bytes.get_mut(), Ordering::AcqRel);
+    }
+}
+
+/// Reserves the `Content-Length` of `request` upfront, failing with `413 Payload Too Large`
+/// when it exceeds the whole budget or `503 Service Unavailable` when it does not fit in what
+/// is left, or, for a body of unknown length, wraps it so every chunk is reserved as it is
+/// read, the body failing with `BodyBudgetExhausted` once the budget is exhausted. HTTP/2
+/// bodies, the only ones hyper surfaces trailers of, are forwarded through a channel to keep
+/// their trailers, and are aborted instead once the budget is exhausted.
+pub(super) fn reserve(
+    budget: &Arc<BodyBudget>,
+    request: &mut Request<Body>,
+) -> Result<Arc<BodyBudgetReservation>, StatusCode> {
+    let reservation = Arc::new(BodyBudgetReservation {
+        budget: budget.clone(),
+        bytes: AtomicU64::new(0),
+    });
+    let content_length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|content_length| content_length.to_str().ok())
+        .and_then(|content_length| content_length.parse::<u64>().ok());
+    match content_length {
+        Some(content_length) if content_length > budget.max_bytes => {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE)
+        }
+        Some(content_length) if !reservation.try_reserve(content_length) => {
+            return Err(StatusCode::SERVICE_UNAVAILABLE)
+        }
+        Some(_) => {}
+        None if !request.body().is_end_stream() => {
+            let body = std::mem::take(request.body_mut());
+            *request.body_mut() = if request.version() == Version::HTTP_2 {
+                budgeted_channel_body(body, reservation.clone())
+            } else {
+                Body::wrap_stream(budgeted_body(body, reservation.clone()))
+            };
+        }
+        None => {}
+    }
+    Ok(reservation)
+}
+
+fn budgeted_body(
+    body: Body,
+    reservation: Arc<BodyBudgetReservation>,
+) -> impl Stream<Item = Result<Bytes, Box<dyn Error + Send + Sync>>> + Send + 'static {
+    body.scan(false, move |exhausted, chunk| {
+        if *exhausted {
+            return futures_util::future::ready(None);
+        }
+        let item = match chunk {
+            Ok(chunk) if reservation.try_reserve(chunk.len() as u64) => Ok(chunk),
+            Ok(_) => {
+                *exhausted = true;
+                Err(BodyBudgetExhausted.into())
+            }
+            Err(error) => Err(error.into()),
+        };
+        futures_util::future::ready(Some(item))
+    })
+}
+
+fn budgeted_channel_body(mut body: Body, reservation: Arc<BodyBudgetReservation>) -> Body {
+    let (mut sender, channel_body) = Body::channel();
+    tokio::spawn(async move {
+        while let Some(chunk) = body.data().await {
+            match chunk {
+                Ok(chunk) if reservation.try_reserve(chunk.len() as u64) => {
+                    if sender.send_data(chunk).await.is_err() {
+                        return;
+                    }
+                }
+                _ => return sender.abort(),
+            }
+        }
+        match body.trailers().await {
+            Ok(Some(trailers)) => {
+                let _ = sender.send_trailers(trailers).await;
+            }
+            Ok(None) => {}
+            Err(_) => sender.abort(),
+        }
+    });
+    channel_body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(content_length: Option<u64>, body: Body) -> Request<Body> {
+        let mut request = Request::new(body);
+        if let Some(content_length) = content_length {
+            request
+                .headers_mut()
+                .insert(header::CONTENT_LENGTH, content_length.into());
+        }
+        request
+    }
+
+    #[test]
+    fn content_length_over_the_whole_budget_is_too_large() {
+        let budget = Arc::new(BodyBudget::with_max_bytes(10));
+        let result = reserve(&budget, &mut request(Some(11), Body::empty()));
+        assert_eq!(result.err(), Some(StatusCode::PAYLOAD_TOO_LARGE));
+    }
+
+    #[test]
+    fn content_length_over_what_is_left_is_unavailable() {
+        let budget = Arc::new(BodyBudget::with_max_bytes(10));
+        let _reservation = reserve(&budget, &mut request(Some(6), Body::empty())).unwrap();
+        let result = reserve(&budget, &mut request(Some(6), Body::empty()));
+        assert_eq!(result.err(), Some(StatusCode::SERVICE_UNAVAILABLE));
+    }
+
+    #[tokio::test]
+    async fn unknown_length_fails_with_budget_exhausted() {
+        let budget = Arc::new(BodyBudget::with_max_bytes(4));
+        let (mut sender, body) = Body::channel();
+        let mut request = request(None, body);
+        let _reservation = reserve(&budget, &mut request).unwrap();
+        tokio::spawn(async move {
+            sender.send_data(Bytes::from_static(b"abc")).await.unwrap();
+            sender.send_data(Bytes::from_static(b"def")).await.unwrap();
+        });
+        let error = hyper::body::to_bytes(request.into_body())
+            .await
+            .unwrap_err();
+        assert!(BodyBudgetExhausted::is_source_of(&error));
+    }
+
+    #[tokio::test]
+    async fn http_2_bodies_keep_their_trailers() {
+        let budget = Arc::new(BodyBudget::with_max_bytes(4));
+        let (mut sender, body) = Body::channel();
+        let mut request = request(None, body);
+        *request.version_mut() = Version::HTTP_2;
+        let _reservation = reserve(&budget, &mut request).unwrap();
+        tokio::spawn(async move {
+            sender.send_data(Bytes::from_static(b"abc")).await.unwrap();
+            let mut trailers = hyper::HeaderMap::new();
+            trailers.insert("checksum", "900150983cd24fb0".parse().unwrap());
+            sender.send_trailers(trailers).await.unwrap();
+        });
+        let mut body = request.into_body();
+        assert_eq!(body.data().await.unwrap().unwrap(), "abc");
+        assert!(body.data().await.is_none());
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["checksum"], "900150983cd24fb0");
+    }
+
+    #[tokio::test]
+    async fn http_2_bodies_are_aborted_once_the_budget_is_exhausted() {
+        let budget = Arc::new(BodyBudget::with_max_bytes(4));
+        let (mut sender, body) = Body::channel();
+        let mut request = request(None, body);
+        *request.version_mut() = Version::HTTP_2;
+        let _reservation = reserve(&budget, &mut request).unwrap();
+        tokio::spawn(async move {
+            sender.send_data(Bytes::from_static(b"abc")).await.unwrap();
+            sender.send_data(Bytes::from_static(b"def")).await.unwrap();
+        });
+        assert!(hyper::body::to_bytes(request.into_body()).await.is_err());
+    }
+}
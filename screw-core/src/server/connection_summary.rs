@@ -0,0 +1,18 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub(super) type ConnectionAcceptedHandler = Arc<dyn Fn(SocketAddr) + Send + Sync + 'static>;
+pub(super) type ConnectionClosedHandler = Arc<dyn Fn(ConnectionSummary) + Send + Sync + 'static>;
+
+/// Lifetime of a connection, given to the handler of
+/// `ServerBuilder::and_connection_closed_handler` once the connection closes.
+#[derive(Clone, Debug)]
+pub struct ConnectionSummary {
+    pub remote_addr: SocketAddr,
+    /// Time from accepting the connection to closing it, or to handing it over to an
+    /// upgrade such as a WebSocket.
+    pub elapsed: Duration,
+    /// Requests received on the connection, more than one when it was kept alive.
+    pub requests: u64,
+}
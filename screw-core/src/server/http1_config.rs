@@ -0,0 +1,36 @@
+use hyper::server::conn::Http;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Http1Config {
+    /// Whether connections are kept alive between requests. Defaults to `true`.
+    pub keep_alive: bool,
+    /// Whether flushes of pipelined responses are aggregated. Defaults to `false`.
+    pub pipeline_flush: bool,
+    /// Maximum size of the connection read buffer, which bounds request head size
+    /// and buffered pipelined requests. Defaults to hyper's limit (~400 KB) when `None`.
+    pub max_buf_size: Option<usize>,
+    /// Whether `TCP_NODELAY` is set on accepted connections, disabling Nagle's algorithm so
+    /// small writes are sent right away. Defaults to `false`.
+    pub tcp_nodelay: bool,
+}
+
+impl Default for Http1Config {
+    fn default() -> Self {
+        Self {
+            keep_alive: true,
+            pipeline_flush: false,
+            max_buf_size: None,
+            tcp_nodelay: false,
+        }
+    }
+}
+
+impl Http1Config {
+    pub(super) fn apply(&self, http: &mut Http) {
+        http.http1_keep_alive(self.keep_alive)
+            .pipeline_flush(self.pipeline_flush);
+        if let Some(max_buf_size) = self.max_buf_size {
+            http.max_buf_size(max_buf_size);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use hyper::server::conn::Http;
+
+/// Settings of HTTP/2 connections, which clients open with the HTTP/2 connection preface.
+/// Every stream of a connection is a request handled concurrently with the others, so limits
+/// counted per connection, such as the WebSocket connection limits, do not bound the requests
+/// in flight, `max_concurrent_streams` times the number of connections does.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Http2Config {
+    /// Maximum number of concurrent streams a client may open on a connection. Defaults to
+    /// no limit when `None`.
+    pub max_concurrent_streams: Option<u32>,
+    /// Initial flow control window of every stream, in bytes. Defaults to hyper's 1 MB when
+    /// `None`.
+    pub initial_stream_window_size: Option<u32>,
+    /// Initial flow control window of the whole connection, in bytes. Defaults to hyper's
+    /// 1 MB when `None`.
+    pub initial_connection_window_size: Option<u32>,
+    /// Whether the window sizes adapt to the measured bandwidth-delay product, overriding
+    /// both initial window sizes. Defaults to `false`.
+    pub adaptive_window: bool,
+    /// Largest frame payload accepted, in bytes. Defaults to hyper's 16 KB when `None`.
+    pub max_frame_size: Option<u32>,
+}
+
+impl Http2Config {
+    pub(super) fn apply(&self, http: &mut Http) {
+        http.http2_max_concurrent_streams(self.max_concurrent_streams)
+            .http2_initial_stream_window_size(self.initial_stream_window_size)
+            .http2_initial_connection_window_size(self.initial_connection_window_size)
+            .http2_adaptive_window(self.adaptive_window)
+            .http2_max_frame_size(self.max_frame_size);
+    }
+}
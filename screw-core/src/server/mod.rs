@@ -1,9 +1,21 @@
+mod body_budget;
+mod connection_summary;
+mod http1_config;
+mod http2_config;
 mod responder;
 mod responder_factory;
+mod response_summary;
+mod server_builder;
 mod server_service;
 mod session_service;
 
+pub use body_budget::BodyBudgetExhausted;
+pub use connection_summary::ConnectionSummary;
+pub use http1_config::*;
+pub use http2_config::*;
 pub use responder::*;
 pub use responder_factory::*;
+pub use response_summary::ResponseSummary;
+pub use server_builder::*;
 pub use server_service::*;
 pub use session_service::*;
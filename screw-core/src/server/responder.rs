@@ -1,8 +1,9 @@
+use super::super::response::ResponseBody;
 use hyper::{Body, Request, Response};
 use std::future::Future;
 
 pub trait Responder {
-    type ResponseFuture: Future<Output = Response<Body>>;
+    type ResponseFuture: Future<Output = Response<ResponseBody>>;
     fn response(&mut self, request: Request<Body>) -> Self::ResponseFuture;
 }
 
@@ -0,0 +1,42 @@
+use super::super::response::{CountingBody, ResponseBody};
+use hyper::{Method, StatusCode, Uri};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub(super) type ResponseFinishedHandler = Arc<dyn Fn(ResponseSummary) + Send + Sync + 'static>;
+
+/// Outcome of a response, given to the handler of `ServerBuilder::and_response_finished_handler`
+/// once its body is written or abandoned.
+#[derive(Clone, Debug)]
+pub struct ResponseSummary {
+    pub remote_addr: SocketAddr,
+    pub method: Method,
+    pub uri: Uri,
+    pub status: StatusCode,
+    /// Bytes of the body handed to the connection, headers and transfer framing excluded.
+    pub body_bytes: u64,
+    /// Time from receiving the request head to finishing the response.
+    pub elapsed: Duration,
+    /// Whether the whole body was written, `false` when the body failed or the connection
+    /// closed before its end.
+    pub completed: bool,
+}
+
+/// Wraps `body` so `response_finished_handler` gets `summary` completed once it is dropped.
+pub(super) fn summary_body(
+    body: ResponseBody,
+    mut summary: ResponseSummary,
+    started: Instant,
+    response_finished_handler: ResponseFinishedHandler,
+) -> ResponseBody {
+    ResponseBody::new(CountingBody::new(body, move |count| {
+        summary.body_bytes = count.bytes;
+        summary.elapsed = started.elapsed();
+        summary.completed = count.completed
+            || summary.method == Method::HEAD
+            || summary.status == StatusCode::NO_CONTENT
+            || summary.status == StatusCode::NOT_MODIFIED;
+        response_finished_handler(summary);
+    }))
+}
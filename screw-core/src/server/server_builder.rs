@@ -0,0 +1,631 @@
+use super::super::response::ResponseBody;
+use super::body_budget::BodyBudget;
+use super::connection_summary::{ConnectionAcceptedHandler, ConnectionClosedHandler};
+use super::response_summary::ResponseFinishedHandler;
+use super::session_service::ResponseHook;
+use super::*;
+use futures_util::future;
+use hyper::server::conn::Http;
+use screw_components::dyn_fn::DFnOnce;
+use screw_components::dyn_result::DResult;
+use std::error::Error;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::sync::{mpsc, watch};
+use tokio::time::sleep;
+use tracing::Level;
+
+pub struct ServerBuilder<F, R>
+where
+    F: ResponderFactory<Responder = R>,
+    R: Responder + Send + 'static,
+    R::ResponseFuture: Send + 'static,
+{
+    responder_factory: F,
+    http1_config: Http1Config,
+    http2_config: Http2Config,
+    disconnect_log_level: Level,
+    response_finished_handler: Option<ResponseFinishedHandler>,
+    body_budget: Option<Arc<BodyBudget>>,
+    connection_accepted_handler: Option<ConnectionAcceptedHandler>,
+    connection_closed_handler: Option<ConnectionClosedHandler>,
+    response_hooks: Vec<ResponseHook>,
+    start_hooks: Vec<DFnOnce<(), DResult<()>>>,
+}
+
+impl<F, R> ServerBuilder<F, R>
+where
+    F: ResponderFactory<Responder = R>,
+    R: Responder + Send + 'static,
+    R::ResponseFuture: Send + 'static,
+{
+    pub fn with_responder_factory(responder_factory: F) -> Self {
+        Self {
+            responder_factory,
+            http1_config: Http1Config::default(),
+            http2_config: Http2Config::default(),
+            disconnect_log_level: Level::WARN,
+            response_finished_handler: None,
+            response_hooks: Vec::new(),
+            start_hooks: Vec::new(),
+            body_budget: None,
+            connection_accepted_handler: None,
+            connection_closed_handler: None,
+        }
+    }
+
+    pub fn and_http1_config(mut self, http1_config: Http1Config) -> Self {
+        self.http1_config = http1_config;
+        self
+    }
+
+    pub fn and_http2_config(mut self, http2_config: Http2Config) -> Self {
+        self.http2_config = http2_config;
+        self
+    }
+
+    pub fn and_disconnect_log_level(mut self, disconnect_log_level: Level) -> Self {
+        self.disconnect_log_level = disconnect_log_level;
+        self
+    }
+
+    /// Calls `response_finished_handler` with the status, body size and duration of every
+    /// response once its body is written, streamed bodies included, or abandoned because the
+    /// connection closed. Unlike a middleware, it observes the body as the connection writes
+    /// it, so it suits metrics and billing.
+    pub fn and_response_finished_handler<HFn>(mut self, response_finished_handler: HFn) -> Self
+    where
+        HFn: Fn(ResponseSummary) + Send + Sync + 'static,
+    {
+        self.response_finished_handler = Some(Arc::new(response_finished_handler));
+        self
+    }
+
+    /// Calls `connection_accepted_handler` with the peer of every accepted connection, before
+    /// any request is read from it.
+    pub fn and_connection_accepted_handler<HFn>(mut self, connection_accepted_handler: HFn) -> Self
+    where
+        HFn: Fn(SocketAddr) + Send + Sync + 'static,
+    {
+        self.connection_accepted_handler = Some(Arc::new(connection_accepted_handler));
+        self
+    }
+
+    /// Calls `connection_closed_handler` with the duration and the number of requests of every
+    /// connection once it closes, telling how well connections are kept alive. A connection
+    /// upgraded, e.g. to a WebSocket, counts as closed when the upgrade takes it over.
+    pub fn and_connection_closed_handler<HFn>(mut self, connection_closed_handler: HFn) -> Self
+    where
+        HFn: Fn(ConnectionSummary) + Send + Sync + 'static,
+    {
+        self.connection_closed_handler = Some(Arc::new(connection_closed_handler));
+        self
+    }
+
+    /// Caps the bytes of request bodies in flight across all connections at `max_bytes`,
+    /// counting a body from the moment it starts being read until its response is ready and
+    /// the body is dropped. A request whose `Content-Length` does not fit in what is left is
+    /// responded to with `503 Service Unavailable`, or `413 Payload Too Large` when it exceeds
+    /// `max_bytes` altogether, and `Connection: close` before reaching the responder, while a
+    /// body of unknown length fails to be read with `BodyBudgetExhausted` once a chunk does not
+    /// fit, which the API converters respond to with `503 Service Unavailable` as well, or,
+    /// over HTTP/2, is aborted. Unlimited by default.
+    pub fn and_body_budget(mut self, max_bytes: u64) -> Self {
+        self.body_budget = Some(Arc::new(BodyBudget::with_max_bytes(max_bytes)));
+        self
+    }
+
+    /// Calls `response_hook` with every response before the server writes it, after the
+    /// responder, so it can act on response extensions set by handlers and middlewares, see
+    /// `Response::and_extension`. Hooks run in the order they are added, before the server
+    /// handles its own extensions such as `CloseConnection`, which hooks may therefore insert.
+    pub fn and_response_hook<HFn>(mut self, response_hook: HFn) -> Self
+    where
+        HFn: Fn(&mut hyper::Response<ResponseBody>) + Send + Sync + 'static,
+    {
+        self.response_hooks.push(Arc::new(response_hook));
+        self
+    }
+
+    /// Runs `start_hook` to completion when serving starts, before binding and accepting
+    /// connections, so caches can be primed and pools connected ahead of the first request.
+    /// Hooks run one after another in the order they are added, and the first failing one
+    /// aborts serving with its error.
+    pub fn and_start_hook<HFn, HFut>(mut self, start_hook: HFn) -> Self
+    where
+        HFn: FnOnce() -> HFut + Send + Sync + 'static,
+        HFut: Future<Output = DResult<()>> + Send + 'static,
+    {
+        self.start_hooks
+            .push(Box::new(move |()| Box::pin(start_hook())));
+        self
+    }
+
+    pub async fn serve(self, addr: &SocketAddr) -> io::Result<()> {
+        self.serve_bindings([Binding::Tcp(*addr)]).await
+    }
+
+    /// Listens on every binding at once, all connections sharing the responder factory and
+    /// the settings of the builder. Fails when a start hook fails or any binding cannot be
+    /// bound, before accepting connections. Serves until the task is dropped, see
+    /// `serve_bindings_with_shutdown` to stop gracefully.
+    pub async fn serve_bindings<I: IntoIterator<Item = Binding>>(
+        self,
+        bindings: I,
+    ) -> io::Result<()> {
+        self.serve_bindings_with_shutdown(bindings, future::pending())
+            .await
+    }
+
+    /// Same as `serve_bindings`, until `signal` completes. Every binding then stops accepting
+    /// connections, open connections finish the requests they are handling and close, and it
+    /// returns once all of them are closed. Upgraded connections, such as WebSockets, are
+    /// taken over by their handlers and not waited for.
+    pub async fn serve_bindings_with_shutdown<I, S>(
+        mut self,
+        bindings: I,
+        signal: S,
+    ) -> io::Result<()>
+    where
+        I: IntoIterator<Item = Binding>,
+        S: Future<Output = ()>,
+    {
+        for start_hook in self.start_hooks.drain(..) {
+            start_hook(()).await.map_err(io::Error::other)?;
+        }
+        let mut listeners = Vec::new();
+        for binding in bindings {
+            listeners.push(match binding {
+                Binding::Tcp(addr) => Listener::Tcp(TcpListener::bind(addr).await?),
+                #[cfg(unix)]
+                Binding::Unix(path) => Listener::Unix(UnixListener::bind(path)?),
+            });
+        }
+        let mut http = Http::new();
+        self.http1_config.apply(&mut http);
+        self.http2_config.apply(&mut http);
+        let response_hooks: Arc<[ResponseHook]> = self.response_hooks.clone().into();
+        let (shutdown_sender, shutdown_receiver) = watch::channel(());
+        let (open_sender, mut open_receiver) = mpsc::channel::<()>(1);
+        let shutdown = ConnectionShutdown {
+            receiver: shutdown_receiver,
+            _open: open_sender,
+        };
+
+        let accept_loops = listeners
+            .into_iter()
+            .map(|listener| self.accept_loop(listener, &http, &response_hooks, &shutdown));
+        tokio::select! {
+            _ = future::join_all(accept_loops) => {}
+            _ = signal => {}
+        }
+        drop(shutdown);
+        let _ = shutdown_sender.send(());
+        // Every open connection holds a sender, so this only returns once all are closed
+        let _ = open_receiver.recv().await;
+        Ok(())
+    }
+
+    async fn accept_loop(
+        &self,
+        listener: Listener,
+        http: &Http,
+        response_hooks: &Arc<[ResponseHook]>,
+        shutdown: &ConnectionShutdown,
+    ) {
+        loop {
+            let accepted = match &listener {
+                Listener::Tcp(listener) => listener.accept().await.map(|(stream, remote_addr)| {
+                    if self.http1_config.tcp_nodelay {
+                        if let Err(error) = stream.set_nodelay(true) {
+                            tracing::debug!(target: "screw::server", %remote_addr, %error, "failed to set TCP_NODELAY");
+                        }
+                    }
+                    self.serve_connection(stream, remote_addr, http, response_hooks, shutdown.clone())
+                }),
+                #[cfg(unix)]
+                Listener::Unix(listener) => listener.accept().await.map(|(stream, _)| {
+                    let remote_addr = SocketAddr::from(([0, 0, 0, 0], 0));
+                    self.serve_connection(stream, remote_addr, http, response_hooks, shutdown.clone())
+                }),
+            };
+            if let Err(error) = accepted {
+                tracing::error!(target: "screw::server", %error, "failed to accept connection");
+                sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+
+    fn serve_connection<S>(
+        &self,
+        stream: S,
+        remote_addr: SocketAddr,
+        http: &Http,
+        response_hooks: &Arc<[ResponseHook]>,
+        mut shutdown: ConnectionShutdown,
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        if let Some(connection_accepted_handler) = &self.connection_accepted_handler {
+            connection_accepted_handler(remote_addr);
+        }
+        let responder = self.responder_factory.make_responder(remote_addr);
+        let connection = http
+            .serve_connection(
+                stream,
+                SessionService {
+                    responder,
+                    remote_addr,
+                    response_finished_handler: self.response_finished_handler.clone(),
+                    response_hooks: response_hooks.clone(),
+                    body_budget: self.body_budget.clone(),
+                    connection_closed_handler: self.connection_closed_handler.clone(),
+                    accepted: Instant::now(),
+                    requests: 0,
+                },
+            )
+            .with_upgrades();
+        let disconnect_log_level = self.disconnect_log_level;
+
+        tokio::spawn(async move {
+            tokio::pin!(connection);
+            let result = tokio::select! {
+                result = connection.as_mut() => result,
+                Ok(()) = shutdown.receiver.changed() => {
+                    connection.as_mut().graceful_shutdown();
+                    connection.await
+                }
+            };
+            drop(shutdown);
+            if let Err(error) = result {
+                if is_client_disconnect(&error) {
+                    log_client_disconnect(disconnect_log_level, remote_addr, &error);
+                } else {
+                    tracing::error!(target: "screw::server", %remote_addr, %error, "connection error");
+                }
+            }
+        });
+    }
+}
+
+/// Lets connections know serving stops, while telling `serve_bindings_with_shutdown` they
+/// are still open by holding `_open`.
+#[derive(Clone)]
+struct ConnectionShutdown {
+    receiver: watch::Receiver<()>,
+    _open: mpsc::Sender<()>,
+}
+
+/// Endpoint `ServerBuilder::serve_bindings` listens on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Binding {
+    Tcp(SocketAddr),
+    /// Unix domain socket at the path, which must not exist yet. Having no peer address, its
+    /// connections carry the unspecified `0.0.0.0:0` as their remote address, so limits per
+    /// remote address, such as `RateLimitMiddleware` with `RemoteAddrClientKey` or
+    /// `max_connections_per_ip` of WebSockets, count all of its clients as a single one.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl From<SocketAddr> for Binding {
+    fn from(addr: SocketAddr) -> Self {
+        Binding::Tcp(addr)
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+fn is_client_disconnect(error: &hyper::Error) -> bool {
+    if error.is_incomplete_message() {
+        return true;
+    }
+    let mut source = error.source();
+    while let Some(error) = source {
+        if let Some(io_error) = error.downcast_ref::<io::Error>() {
+            return matches!(
+                io_error.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+            );
+        }
+        source = error.source();
+    }
+    false
+}
+
+fn log_client_disconnect(level: Level, remote_addr: SocketAddr, error: &hyper::Error) {
+    match level {
+        Level::ERROR => {
+            tracing::error!(target: "screw::server", %remote_addr, %error, "client disconnected")
+        }
+        Level::WARN => {
+            tracing::warn!(target: "screw::server", %remote_addr, %error, "client disconnected")
+        }
+        Level::INFO => {
+            tracing::info!(target: "screw::server", %remote_addr, %error, "client disconnected")
+        }
+        Level::DEBUG => {
+            tracing::debug!(target: "screw::server", %remote_addr, %error, "client disconnected")
+        }
+        Level::TRACE => {
+            tracing::trace!(target: "screw::server", %remote_addr, %error, "client disconnected")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::request::Request;
+    use super::super::super::responder_factory::{first, second};
+    use super::super::super::response::{CloseConnection, Response};
+    use super::super::super::routing::router::{self, RoutedRequest};
+    use super::*;
+    use hyper::header::{self, HeaderValue};
+    use hyper::{Body, StatusCode};
+
+    #[derive(Clone, Copy)]
+    struct CacheTier(&'static str);
+
+    fn server_builder(
+        response: fn() -> Response,
+    ) -> ServerBuilder<
+        second::ResponderFactory<()>,
+        <second::ResponderFactory<()> as ResponderFactory>::Responder,
+    > {
+        let router = router::first::Router::with_fallback_handler(
+            move |_: RoutedRequest<Request<()>>| async move { response() },
+        )
+        .and_routes(|routes| routes);
+        ServerBuilder::with_responder_factory(
+            first::ResponderFactory::with_router(router).and_extensions(()),
+        )
+    }
+
+    /// Sends a request over a connection served by `server_builder` and returns its response.
+    async fn respond<F, R>(server_builder: ServerBuilder<F, R>) -> hyper::Response<Body>
+    where
+        F: ResponderFactory<Responder = R>,
+        R: Responder + Send + 'static,
+        R::ResponseFuture: Send + 'static,
+    {
+        connect(server_builder)
+            .await
+            .send_request(hyper::Request::new(Body::empty()))
+            .await
+            .unwrap()
+    }
+
+    /// Opens a connection served by `server_builder`, which closes once the returned sender
+    /// is dropped.
+    async fn connect<F, R>(
+        server_builder: ServerBuilder<F, R>,
+    ) -> hyper::client::conn::SendRequest<Body>
+    where
+        F: ResponderFactory<Responder = R>,
+        R: Responder + Send + 'static,
+        R::ResponseFuture: Send + 'static,
+    {
+        let response_hooks: Arc<[ResponseHook]> = server_builder.response_hooks.clone().into();
+        let (client, server) = tokio::io::duplex(4096);
+        let (_shutdown_sender, receiver) = watch::channel(());
+        let (open_sender, _) = mpsc::channel(1);
+        server_builder.serve_connection(
+            server,
+            SocketAddr::from(([127, 0, 0, 1], 8080)),
+            &Http::new(),
+            &response_hooks,
+            ConnectionShutdown {
+                receiver,
+                _open: open_sender,
+            },
+        );
+        let (send_request, connection) = hyper::client::conn::handshake(client).await.unwrap();
+        tokio::spawn(connection);
+        send_request
+    }
+
+    #[tokio::test]
+    async fn response_hook_reads_handler_extensions() {
+        let server_builder = server_builder(|| {
+            Response {
+                http: hyper::Response::new(ResponseBody::empty()),
+            }
+            .and_extension(CacheTier("edge"))
+        })
+        .and_response_hook(|response| {
+            if let Some(CacheTier(tier)) = response.extensions().get::<CacheTier>().copied() {
+                response
+                    .headers_mut()
+                    .insert("x-cache-tier", HeaderValue::from_static(tier));
+            }
+        });
+
+        let response = respond(server_builder).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-cache-tier"], "edge");
+    }
+
+    #[tokio::test]
+    async fn response_hook_closes_connection() {
+        let server_builder = server_builder(|| Response {
+            http: hyper::Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .body(ResponseBody::empty())
+                .unwrap(),
+        })
+        .and_response_hook(|response| {
+            if response.status() == StatusCode::SERVICE_UNAVAILABLE {
+                response.extensions_mut().insert(CloseConnection);
+            }
+        });
+
+        let response = respond(server_builder).await;
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::CONNECTION], "close");
+    }
+
+    #[tokio::test]
+    async fn connection_handlers_see_accepted_and_closed_connections() {
+        let (accepted_sender, mut accepted_receiver) = mpsc::unbounded_channel();
+        let (closed_sender, mut closed_receiver) = mpsc::unbounded_channel();
+        let server_builder = server_builder(|| Response {
+            http: hyper::Response::new(ResponseBody::empty()),
+        })
+        .and_connection_accepted_handler(move |remote_addr| {
+            accepted_sender.send(remote_addr).unwrap();
+        })
+        .and_connection_closed_handler(move |connection_summary| {
+            closed_sender.send(connection_summary).unwrap();
+        });
+
+        let mut send_request = connect(server_builder).await;
+        assert_eq!(
+            accepted_receiver.try_recv(),
+            Ok(SocketAddr::from(([127, 0, 0, 1], 8080)))
+        );
+        for _ in 0..2 {
+            let response = send_request
+                .send_request(hyper::Request::new(Body::empty()))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        assert!(closed_receiver.try_recv().is_err());
+
+        drop(send_request);
+        let connection_summary =
+            tokio::time::timeout(Duration::from_secs(5), closed_receiver.recv())
+                .await
+                .expect("connection closed handler not called")
+                .unwrap();
+        assert_eq!(
+            connection_summary.remote_addr,
+            SocketAddr::from(([127, 0, 0, 1], 8080))
+        );
+        assert_eq!(connection_summary.requests, 2);
+        assert!(accepted_receiver.try_recv().is_err());
+    }
+
+    /// Server builder whose start hooks push their number to `started`, the hook numbered
+    /// `failing` failing.
+    fn server_builder_with_start_hooks(
+        started: &Arc<std::sync::Mutex<Vec<u32>>>,
+        failing: Option<u32>,
+    ) -> ServerBuilder<
+        second::ResponderFactory<()>,
+        <second::ResponderFactory<()> as ResponderFactory>::Responder,
+    > {
+        let mut server_builder = server_builder(|| Response {
+            http: hyper::Response::new(ResponseBody::empty()),
+        });
+        for number in 1..=3 {
+            let started = started.clone();
+            server_builder = server_builder.and_start_hook(move || async move {
+                tokio::task::yield_now().await;
+                started.lock().unwrap().push(number);
+                match failing {
+                    Some(failing) if failing == number => {
+                        Err(format!("hook {number} failed").into())
+                    }
+                    _ => Ok(()),
+                }
+            });
+        }
+        server_builder
+    }
+
+    #[tokio::test]
+    async fn start_hooks_run_in_order_before_serving() {
+        let started = Arc::new(std::sync::Mutex::new(Vec::new()));
+        server_builder_with_start_hooks(&started, None)
+            .serve_bindings_with_shutdown([], future::ready(()))
+            .await
+            .unwrap();
+        assert_eq!(*started.lock().unwrap(), [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn failing_start_hook_aborts_before_binding() {
+        // Bound already, so binding it would fail with another error
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let started = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let error = server_builder_with_start_hooks(&started, Some(2))
+            .serve(&listener.local_addr().unwrap())
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "hook 2 failed");
+        assert_eq!(*started.lock().unwrap(), [1, 2]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn shutdown_waits_for_in_flight_requests() {
+        let path = std::env::temp_dir().join(format!("screw-shutdown-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let release = Arc::new(tokio::sync::Notify::new());
+        let handler_release = release.clone();
+        let router =
+            router::first::Router::with_fallback_handler(move |_: RoutedRequest<Request<()>>| {
+                let handler_release = handler_release.clone();
+                async move {
+                    handler_release.notified().await;
+                    Response {
+                        http: hyper::Response::new(ResponseBody::empty()),
+                    }
+                }
+            })
+            .and_routes(|routes| routes);
+        let server_builder = ServerBuilder::with_responder_factory(
+            first::ResponderFactory::with_router(router).and_extensions(()),
+        );
+        let (signal_sender, signal_receiver) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(server_builder.serve_bindings_with_shutdown(
+            [Binding::Unix(path.clone())],
+            async move {
+                let _ = signal_receiver.await;
+            },
+        ));
+        let stream = loop {
+            match tokio::net::UnixStream::connect(&path).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::task::yield_now().await,
+            }
+        };
+        let (mut send_request, connection) = hyper::client::conn::handshake(stream).await.unwrap();
+        tokio::spawn(connection);
+        let response = tokio::spawn(send_request.send_request(hyper::Request::new(Body::empty())));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        signal_sender.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!server.is_finished());
+        assert!(tokio::net::UnixStream::connect(&path).await.is_err());
+
+        release.notify_one();
+        let response = response.await.unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server kept waiting after the last connection closed")
+            .unwrap()
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+}
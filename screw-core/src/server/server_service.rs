@@ -1,9 +1,17 @@
+use super::super::response::ResponseBody;
+use super::body_budget::BodyBudget;
+use super::connection_summary::{ConnectionAcceptedHandler, ConnectionClosedHandler};
+use super::response_summary::ResponseFinishedHandler;
+use super::session_service::ResponseHook;
 use super::*;
 use hyper::server::conn::AddrStream;
 use hyper::service::Service;
 use std::convert::Infallible;
 use std::future::{ready, Ready};
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Instant;
 
 pub struct ServerService<F, R>
 where
@@ -12,6 +20,11 @@ where
     R::ResponseFuture: Send + 'static,
 {
     responder_factory: F,
+    response_finished_handler: Option<ResponseFinishedHandler>,
+    body_budget: Option<Arc<BodyBudget>>,
+    connection_accepted_handler: Option<ConnectionAcceptedHandler>,
+    connection_closed_handler: Option<ConnectionClosedHandler>,
+    response_hooks: Arc<[ResponseHook]>,
 }
 
 impl<F, R> ServerService<F, R>
@@ -21,7 +34,63 @@ where
     R::ResponseFuture: Send + 'static,
 {
     pub fn with_responder_factory(responder_factory: F) -> Self {
-        Self { responder_factory }
+        Self {
+            responder_factory,
+            response_finished_handler: None,
+            response_hooks: Arc::new([]),
+            body_budget: None,
+            connection_accepted_handler: None,
+            connection_closed_handler: None,
+        }
+    }
+
+    /// Calls `response_finished_handler` once the body of every response is written or
+    /// abandoned, see `ServerBuilder::and_response_finished_handler`.
+    pub fn and_response_finished_handler<HFn>(mut self, response_finished_handler: HFn) -> Self
+    where
+        HFn: Fn(ResponseSummary) + Send + Sync + 'static,
+    {
+        self.response_finished_handler = Some(Arc::new(response_finished_handler));
+        self
+    }
+
+    /// Calls `connection_accepted_handler` with the peer of every accepted connection, see
+    /// `ServerBuilder::and_connection_accepted_handler`.
+    pub fn and_connection_accepted_handler<HFn>(mut self, connection_accepted_handler: HFn) -> Self
+    where
+        HFn: Fn(SocketAddr) + Send + Sync + 'static,
+    {
+        self.connection_accepted_handler = Some(Arc::new(connection_accepted_handler));
+        self
+    }
+
+    /// Calls `connection_closed_handler` once every connection closes, see
+    /// `ServerBuilder::and_connection_closed_handler`.
+    pub fn and_connection_closed_handler<HFn>(mut self, connection_closed_handler: HFn) -> Self
+    where
+        HFn: Fn(ConnectionSummary) + Send + Sync + 'static,
+    {
+        self.connection_closed_handler = Some(Arc::new(connection_closed_handler));
+        self
+    }
+
+    /// Caps the bytes of request bodies in flight across all connections, see
+    /// `ServerBuilder::and_body_budget`.
+    pub fn and_body_budget(mut self, max_bytes: u64) -> Self {
+        self.body_budget = Some(Arc::new(BodyBudget::with_max_bytes(max_bytes)));
+        self
+    }
+
+    /// Calls `response_hook` with every response before it is written, see
+    /// `ServerBuilder::and_response_hook`.
+    pub fn and_response_hook<HFn>(mut self, response_hook: HFn) -> Self
+    where
+        HFn: Fn(&mut hyper::Response<ResponseBody>) + Send + Sync + 'static,
+    {
+        let mut response_hooks = self.response_hooks.to_vec();
+        response_hooks.push(Arc::new(response_hook));
+        self.response_hooks = response_hooks.into();
+        self
     }
 }
 
@@ -41,8 +110,20 @@ where
 
     fn call(&mut self, addr_stream: &AddrStream) -> Self::Future {
         let remote_addr = addr_stream.remote_addr();
+        if let Some(connection_accepted_handler) = &self.connection_accepted_handler {
+            connection_accepted_handler(remote_addr);
+        }
         let responder = self.responder_factory.make_responder(remote_addr);
-        let session_service = SessionService { responder };
+        let session_service = SessionService {
+            responder,
+            remote_addr,
+            response_finished_handler: self.response_finished_handler.clone(),
+            response_hooks: self.response_hooks.clone(),
+            body_budget: self.body_budget.clone(),
+            connection_closed_handler: self.connection_closed_handler.clone(),
+            accepted: Instant::now(),
+            requests: 0,
+        };
         ready(Ok(session_service))
     }
 }
@@ -1,10 +1,22 @@
+use super::super::response::{CloseConnection, ResponseBody, ResponseWriteMode};
+use super::body_budget::{self, BodyBudget};
+use super::connection_summary::ConnectionClosedHandler;
+use super::response_summary::{self, ResponseFinishedHandler};
 use super::*;
+use futures_util::future::{self, Either};
+use hyper::body::HttpBody;
+use hyper::header::{self, HeaderValue};
 use hyper::service::Service;
-use hyper::{Body, Request, Response};
+use hyper::{Body, Request, Response, StatusCode};
 use std::convert::Infallible;
 use std::future::Future;
+use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Instant;
+
+pub(super) type ResponseHook = Arc<dyn Fn(&mut Response<ResponseBody>) + Send + Sync + 'static>;
 
 pub struct SessionService<R>
 where
@@ -12,6 +24,29 @@ where
     R::ResponseFuture: Send + 'static,
 {
     pub(super) responder: R,
+    pub(super) remote_addr: SocketAddr,
+    pub(super) response_finished_handler: Option<ResponseFinishedHandler>,
+    pub(super) response_hooks: Arc<[ResponseHook]>,
+    pub(super) body_budget: Option<Arc<BodyBudget>>,
+    pub(super) connection_closed_handler: Option<ConnectionClosedHandler>,
+    pub(super) accepted: Instant,
+    pub(super) requests: u64,
+}
+
+impl<R> Drop for SessionService<R>
+where
+    R: Responder,
+    R::ResponseFuture: Send + 'static,
+{
+    fn drop(&mut self) {
+        if let Some(connection_closed_handler) = &self.connection_closed_handler {
+            connection_closed_handler(ConnectionSummary {
+                remote_addr: self.remote_addr,
+                elapsed: self.accepted.elapsed(),
+                requests: self.requests,
+            });
+        }
+    }
 }
 
 impl<R> Service<Request<Body>> for SessionService<R>
@@ -19,19 +54,112 @@ where
     R: Responder,
     R::ResponseFuture: Send + 'static,
 {
-    type Response = Response<Body>;
+    type Response = Response<ResponseBody>;
     type Error = Infallible;
-    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, Infallible>> + Send>>;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<ResponseBody>, Infallible>> + Send>>;
 
     fn poll_ready(&mut self, _: &mut Context) -> Poll<Result<(), Self::Error>> {
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, request: Request<Body>) -> Self::Future {
-        let response_future = self.responder.response(request);
+    fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+        let started = Instant::now();
+        self.requests += 1;
+        let summary = self.response_finished_handler.as_ref().map(|handler| {
+            let summary = ResponseSummary {
+                remote_addr: self.remote_addr,
+                method: request.method().clone(),
+                uri: request.uri().clone(),
+                status: StatusCode::OK,
+                body_bytes: 0,
+                elapsed: Default::default(),
+                completed: false,
+            };
+            (summary, handler.clone())
+        });
+        let response_hooks = self.response_hooks.clone();
+        let mut body_budget_reservation = None;
+        let response_future = match &self.body_budget {
+            Some(body_budget) => match body_budget::reserve(body_budget, &mut request) {
+                Ok(reservation) => {
+                    body_budget_reservation = Some(reservation);
+                    Either::Left(self.responder.response(request))
+                }
+                Err(status) => {
+                    tracing::warn!(
+                        target: "screw::server",
+                        remote_addr = %self.remote_addr,
+                        %status,
+                        "request body budget exhausted"
+                    );
+                    Either::Right(future::ready(body_budget_exhausted_response(status)))
+                }
+            },
+            None => Either::Left(self.responder.response(request)),
+        };
         Box::pin(async move {
-            let response = response_future.await;
+            let mut response = response_future.await;
+            drop(body_budget_reservation);
+            for response_hook in response_hooks.iter() {
+                response_hook(&mut response);
+            }
+            if response
+                .extensions_mut()
+                .remove::<CloseConnection>()
+                .is_some()
+            {
+                response
+                    .headers_mut()
+                    .insert(header::CONNECTION, HeaderValue::from_static("close"));
+            }
+            if response.extensions().get::<ResponseWriteMode>()
+                == Some(&ResponseWriteMode::Buffered)
+            {
+                let body = std::mem::take(response.body_mut());
+                *response.body_mut() = buffer_body(body).await;
+            }
+            if let Some((mut summary, response_finished_handler)) = summary {
+                summary.status = response.status();
+                let body = std::mem::take(response.body_mut());
+                *response.body_mut() = response_summary::summary_body(
+                    body,
+                    summary,
+                    started,
+                    response_finished_handler,
+                );
+            }
             Ok(response)
         })
     }
 }
+
+fn body_budget_exhausted_response(status: StatusCode) -> Response<ResponseBody> {
+    Response::builder()
+        .status(status)
+        .header(header::CONNECTION, HeaderValue::from_static("close"))
+        .body(ResponseBody::empty())
+        .unwrap()
+}
+
+/// Collects `body` into a single chunk, keeping its trailers and its error if any.
+async fn buffer_body(mut body: ResponseBody) -> ResponseBody {
+    let mut buffer = Vec::new();
+    while let Some(chunk) = body.data().await {
+        match chunk {
+            Ok(chunk) => buffer.extend_from_slice(&chunk),
+            Err(error) => {
+                return ResponseBody::wrap_stream(futures_util::stream::iter([
+                    Ok(buffer),
+                    Err(error),
+                ]))
+            }
+        }
+    }
+    match body.trailers().await {
+        Ok(Some(trailers)) => ResponseBody::from(buffer).and_trailers(trailers),
+        Ok(None) => ResponseBody::from(buffer),
+        Err(error) => {
+            ResponseBody::wrap_stream(futures_util::stream::iter([Ok(buffer), Err(error)]))
+        }
+    }
+}
@@ -0,0 +1,692 @@
+use super::*;
+use futures_util::stream;
+use hyper::body::Bytes;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Body, Method, StatusCode};
+use std::collections::VecDeque;
+use std::io::{self, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+const CHUNK_SIZE: u64 = 64 * 1024;
+const MAX_RANGES: usize = 64;
+
+/// Serves files below a root directory. Responses carry an `ETag` and a `Last-Modified`
+/// header derived from the file size and modification time, answering `If-None-Match` and
+/// `If-Modified-Since` with `304 Not Modified`.
+///
+/// `GET` requests with a `Range` header respond `206 Partial Content`, as a
+/// `multipart/byteranges` body when several ranges are requested, or
+/// `416 Range Not Satisfiable` when none of the ranges overlaps the file.
+pub struct StaticFiles {
+    root: PathBuf,
+}
+
+impl StaticFiles {
+    pub fn with_root<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Responds the file at `relative_path` below the root, typically taken from a tail
+    /// segment of the route such as `/static/{tail}*`. Paths leading out of the root, as well
+    /// as directories, respond `404 Not Found`.
+    pub async fn serve(
+        &self,
+        http_request: &hyper::Request<Body>,
+        relative_path: &str,
+    ) -> response::Response {
+        let http = match self.serve_http(http_request, relative_path).await {
+            Ok(http) => http,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                status_response(StatusCode::NOT_FOUND)
+            }
+            Err(_) => status_response(StatusCode::INTERNAL_SERVER_ERROR),
+        };
+        response::Response { http }
+    }
+
+    /// Same as `serve`, responding the file at `index_path` below the root with `200 OK`
+    /// instead of `404 Not Found` to `GET` and `HEAD` requests of route-looking paths, whose
+    /// last segment has no file extension, accepting HTML. This serves a single-page app
+    /// whose client routes are unknown to the server, typically from the fallback handler
+    /// for `FallbackReason::NotFound`, while missing assets still respond `404 Not Found`.
+    /// Paths of other routers, such as an API prefix, have to be excluded by the caller.
+    /// The index is sent with `Cache-Control: no-cache`, so clients pick up new builds.
+    pub async fn serve_spa(
+        &self,
+        http_request: &hyper::Request<Body>,
+        relative_path: &str,
+        index_path: &str,
+    ) -> response::Response {
+        let response = self.serve(http_request, relative_path).await;
+        if response.http.status() != StatusCode::NOT_FOUND
+            || !is_spa_route(http_request, relative_path)
+        {
+            return response;
+        }
+        let mut response = self.serve(http_request, index_path).await;
+        response
+            .http
+            .headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        response
+    }
+
+    async fn serve_http(
+        &self,
+        http_request: &hyper::Request<Body>,
+        relative_path: &str,
+    ) -> io::Result<hyper::Response<response::ResponseBody>> {
+        let method = http_request.method();
+        if method != Method::GET && method != Method::HEAD {
+            let mut http_response = status_response(StatusCode::METHOD_NOT_ALLOWED);
+            http_response
+                .headers_mut()
+                .insert(header::ALLOW, HeaderValue::from_static("GET, HEAD"));
+            return Ok(http_response);
+        }
+
+        let path = self
+            .resolve(relative_path)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+        let file = File::open(&path).await?;
+        let metadata = file.metadata().await?;
+        if !metadata.is_file() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        let len = metadata.len();
+        let modified = metadata.modified().ok().map(truncate_to_secs);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, etag(len, modified));
+        if let Some(modified) = modified {
+            if let Ok(last_modified) = HeaderValue::from_str(&httpdate::fmt_http_date(modified)) {
+                headers.insert(header::LAST_MODIFIED, last_modified);
+            }
+        }
+
+        if is_not_modified(http_request.headers(), &headers, modified) {
+            let mut http_response = status_response(StatusCode::NOT_MODIFIED);
+            http_response.headers_mut().extend(headers);
+            return Ok(http_response);
+        }
+
+        headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        let content_type = content_type(&path);
+        let ranges = match http_request.headers().get(header::RANGE) {
+            Some(range)
+                if method == Method::GET && is_range_fresh(http_request.headers(), &headers) =>
+            {
+                range
+                    .to_str()
+                    .ok()
+                    .and_then(|range| parse_ranges(range, len))
+            }
+            _ => None,
+        };
+
+        let (status_code, content_type, segments) = match ranges.as_deref() {
+            None => (
+                StatusCode::OK,
+                content_type,
+                vec![Segment::File { start: 0, len }],
+            ),
+            Some([]) => {
+                let mut http_response = status_response(StatusCode::RANGE_NOT_SATISFIABLE);
+                headers.insert(
+                    header::CONTENT_RANGE,
+                    HeaderValue::from_str(&format!("bytes */{}", len)).unwrap(),
+                );
+                http_response.headers_mut().extend(headers);
+                return Ok(http_response);
+            }
+            Some(&[(first, last)]) => {
+                headers.insert(
+                    header::CONTENT_RANGE,
+                    HeaderValue::from_str(&format!("bytes {}-{}/{}", first, last, len)).unwrap(),
+                );
+                let segment = Segment::File {
+                    start: first,
+                    len: last - first + 1,
+                };
+                (StatusCode::PARTIAL_CONTENT, content_type, vec![segment])
+            }
+            Some(ranges) => {
+                let boundary = multipart::generate_boundary();
+                let mut segments = Vec::with_capacity(ranges.len() * 3 + 1);
+                for &(first, last) in ranges {
+                    let part_head = format!(
+                        "--{}\r\n{}: {}\r\n{}: bytes {}-{}/{}\r\n\r\n",
+                        boundary,
+                        header::CONTENT_TYPE,
+                        content_type.to_str().unwrap(),
+                        header::CONTENT_RANGE,
+                        first,
+                        last,
+                        len
+                    );
+                    segments.push(Segment::Bytes(Bytes::from(part_head)));
+                    segments.push(Segment::File {
+                        start: first,
+                        len: last - first + 1,
+                    });
+                    segments.push(Segment::Bytes(Bytes::from_static(b"\r\n")));
+                }
+                segments.push(Segment::Bytes(Bytes::from(format!("--{}--\r\n", boundary))));
+                let content_type =
+                    HeaderValue::from_str(&format!("multipart/byteranges; boundary={}", boundary))
+                        .unwrap();
+                (StatusCode::PARTIAL_CONTENT, content_type, segments)
+            }
+        };
+
+        let body_len: u64 = segments.iter().map(Segment::len).sum();
+        headers.insert(header::CONTENT_TYPE, content_type);
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body_len));
+        let body = if method == Method::HEAD {
+            response::ResponseBody::empty()
+        } else {
+            segments_body(file, segments.into())
+        };
+        let mut http_response = status_response(status_code);
+        *http_response.body_mut() = body;
+        http_response.headers_mut().extend(headers);
+        Ok(http_response)
+    }
+
+    fn resolve(&self, relative_path: &str) -> Option<PathBuf> {
+        let mut path = self.root.clone();
+        for component in Path::new(relative_path.trim_start_matches('/')).components() {
+            match component {
+                Component::Normal(component) => path.push(component),
+                Component::CurDir => {}
+                _ => return None,
+            }
+        }
+        Some(path)
+    }
+}
+
+/// Whether `relative_path` looks like a client route of a single-page app rather than an asset,
+/// requested by a `GET` or `HEAD` request accepting HTML.
+fn is_spa_route(http_request: &hyper::Request<Body>, relative_path: &str) -> bool {
+    let method = http_request.method();
+    let accepts_html = match http_request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+    {
+        Some(accept) => accept.split(',').any(|media_range| {
+            let media_range = media_range.split(';').next().unwrap_or("").trim();
+            media_range == "*/*"
+                || media_range.eq_ignore_ascii_case("text/*")
+                || media_range.eq_ignore_ascii_case("text/html")
+        }),
+        None => true,
+    };
+    let has_extension = relative_path
+        .rsplit('/')
+        .next()
+        .is_some_and(|segment| segment.contains('.'));
+    (method == Method::GET || method == Method::HEAD) && accepts_html && !has_extension
+}
+
+fn status_response(status_code: StatusCode) -> hyper::Response<response::ResponseBody> {
+    hyper::Response::builder()
+        .status(status_code)
+        .body(response::ResponseBody::empty())
+        .unwrap()
+}
+
+fn truncate_to_secs(time: SystemTime) -> SystemTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn etag(len: u64, modified: Option<SystemTime>) -> HeaderValue {
+    let modified_secs = modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs());
+    HeaderValue::from_str(&format!("W/\"{:x}-{:x}\"", len, modified_secs)).unwrap()
+}
+
+/// Evaluates `If-None-Match` and, only when it is absent, `If-Modified-Since` as RFC 7232
+/// orders them, comparing entity tags weakly and modification times in whole seconds. Dates in
+/// the future are ignored, as the client cannot have seen the file modified then.
+fn is_not_modified(
+    request_headers: &HeaderMap,
+    response_headers: &HeaderMap,
+    modified: Option<SystemTime>,
+) -> bool {
+    if let Some(if_none_match) = request_headers.get(header::IF_NONE_MATCH) {
+        let etag = match response_headers
+            .get(header::ETAG)
+            .and_then(|h| h.to_str().ok())
+        {
+            Some(etag) => etag,
+            None => return false,
+        };
+        return if_none_match.to_str().is_ok_and(|if_none_match| {
+            if_none_match.split(',').map(str::trim).any(|tag| {
+                tag == "*" || tag.trim_start_matches("W/") == etag.trim_start_matches("W/")
+            })
+        });
+    }
+    let since = request_headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| httpdate::parse_http_date(h).ok());
+    match (since, modified) {
+        (Some(since), Some(modified)) => since <= SystemTime::now() && modified <= since,
+        _ => false,
+    }
+}
+
+fn content_type(path: &Path) -> HeaderValue {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    HeaderValue::from_static(match extension.as_deref() {
+        Some("html") | Some("htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") | Some("mjs") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("xml") => "application/xml",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("wasm") => "application/wasm",
+        Some("pdf") => "application/pdf",
+        Some("woff2") => "font/woff2",
+        Some("mp3") => "audio/mpeg",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        _ => "application/octet-stream",
+    })
+}
+
+/// Evaluates `If-Range`, which only lets a `Range` header apply while the representation is
+/// unchanged. Entity tags are compared strongly, so weak ones never match.
+fn is_range_fresh(request_headers: &HeaderMap, response_headers: &HeaderMap) -> bool {
+    let if_range = match request_headers.get(header::IF_RANGE) {
+        Some(if_range) => match if_range.to_str() {
+            Ok(if_range) => if_range.trim(),
+            Err(_) => return false,
+        },
+        None => return true,
+    };
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        return !if_range.starts_with("W/")
+            && response_headers
+                .get(header::ETAG)
+                .is_some_and(|etag| etag.as_bytes() == if_range.as_bytes());
+    }
+    let last_modified = response_headers
+        .get(header::LAST_MODIFIED)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| httpdate::parse_http_date(h).ok());
+    match (httpdate::parse_http_date(if_range).ok(), last_modified) {
+        (Some(if_range), Some(last_modified)) => if_range == last_modified,
+        _ => false,
+    }
+}
+
+/// Parses a `bytes` range set into inclusive, sorted and coalesced ranges clamped to `len`.
+/// Returns `None` when the header must be ignored, being malformed, of another unit or listing
+/// too many ranges, and an empty list when no range is satisfiable.
+fn parse_ranges(range: &str, len: u64) -> Option<Vec<(u64, u64)>> {
+    fn parse_digits(digits: &str) -> Option<u64> {
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        // Positions past u64::MAX are still well-formed and only clamped to the file
+        Some(digits.parse().unwrap_or(u64::MAX))
+    }
+
+    let (unit, range_set) = range.split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+    let mut ranges = Vec::new();
+    for (index, range_spec) in range_set.split(',').map(str::trim).enumerate() {
+        if index >= MAX_RANGES {
+            return None;
+        }
+        if range_spec.is_empty() {
+            continue;
+        }
+        let (first, last) = range_spec.split_once('-')?;
+        let (first, last) = (first.trim(), last.trim());
+        if first.is_empty() {
+            let suffix_len = parse_digits(last)?;
+            if suffix_len > 0 && len > 0 {
+                ranges.push((len.saturating_sub(suffix_len), len - 1));
+            }
+            continue;
+        }
+        let first = parse_digits(first)?;
+        let last = match last {
+            "" => u64::MAX,
+            last => parse_digits(last)?,
+        };
+        if last < first {
+            return None;
+        }
+        if first < len {
+            ranges.push((first, last.min(len - 1)));
+        }
+    }
+    ranges.sort_unstable();
+    let mut coalesced: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (first, last) in ranges {
+        match coalesced.last_mut() {
+            Some(previous) if first <= previous.1.saturating_add(1) => {
+                previous.1 = previous.1.max(last)
+            }
+            _ => coalesced.push((first, last)),
+        }
+    }
+    Some(coalesced)
+}
+
+enum Segment {
+    Bytes(Bytes),
+    File { start: u64, len: u64 },
+}
+
+impl Segment {
+    fn len(&self) -> u64 {
+        match self {
+            Segment::Bytes(bytes) => bytes.len() as u64,
+            Segment::File { len, .. } => *len,
+        }
+    }
+}
+
+fn segments_body(file: File, segments: VecDeque<Segment>) -> response::ResponseBody {
+    response::ResponseBody::wrap_stream(stream::unfold(
+        (file, segments),
+        |(mut file, mut segments)| async move {
+            let (start, len) = match segments.pop_front()? {
+                Segment::Bytes(bytes) => return Some((Ok(bytes), (file, segments))),
+                Segment::File { len: 0, .. } => return Some((Ok(Bytes::new()), (file, segments))),
+                Segment::File { start, len } => (start, len),
+            };
+            let mut chunk = vec![0; len.min(CHUNK_SIZE) as usize];
+            let read = match file.seek(SeekFrom::Start(start)).await {
+                Ok(_) => file.read(&mut chunk).await,
+                Err(error) => Err(error),
+            };
+            match read {
+                Ok(0) => Some((
+                    Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+                    (file, VecDeque::new()),
+                )),
+                Ok(read) => {
+                    chunk.truncate(read);
+                    if (read as u64) < len {
+                        segments.push_front(Segment::File {
+                            start: start + read as u64,
+                            len: len - read as u64,
+                        });
+                    }
+                    Some((Ok(Bytes::from(chunk)), (file, segments)))
+                }
+                Err(error) => Some((Err(error), (file, VecDeque::new()))),
+            }
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "0123456789abcdefghij";
+
+    /// Static files rooted at a fresh directory holding `file.txt` with `CONTENT`.
+    fn static_files(name: &str) -> StaticFiles {
+        let root = std::env::temp_dir().join(format!(
+            "screw-static-files-{}-{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("file.txt"), CONTENT).unwrap();
+        StaticFiles::with_root(root)
+    }
+
+    async fn serve_range(name: &str, range: &str) -> hyper::Response<response::ResponseBody> {
+        let http_request = hyper::Request::builder()
+            .header(header::RANGE, range)
+            .body(Body::empty())
+            .unwrap();
+        static_files(name)
+            .serve(&http_request, "file.txt")
+            .await
+            .http
+    }
+
+    fn headers<const N: usize>(headers: [(header::HeaderName, &str); N]) -> HeaderMap {
+        headers
+            .into_iter()
+            .map(|(name, value)| (name, HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_suffix_and_open_ended_ranges() {
+        assert_eq!(parse_ranges("bytes=-5", 20), Some(vec![(15, 19)]));
+        assert_eq!(parse_ranges("bytes=-50", 20), Some(vec![(0, 19)]));
+        assert_eq!(parse_ranges("bytes=15-", 20), Some(vec![(15, 19)]));
+        assert_eq!(parse_ranges("bytes=5-100", 20), Some(vec![(5, 19)]));
+        assert_eq!(
+            parse_ranges("bytes=0-99999999999999999999999", 20),
+            Some(vec![(0, 19)])
+        );
+    }
+
+    #[test]
+    fn coalesces_overlapping_and_adjacent_ranges() {
+        assert_eq!(
+            parse_ranges("bytes=10-14, 0-4, 3-8, 15-16", 20),
+            Some(vec![(0, 8), (10, 16)])
+        );
+        assert_eq!(
+            parse_ranges("bytes=0-4,-3", 20),
+            Some(vec![(0, 4), (17, 19)])
+        );
+    }
+
+    #[test]
+    fn ignores_malformed_ranges() {
+        assert_eq!(parse_ranges("items=0-4", 20), None);
+        assert_eq!(parse_ranges("bytes=5-2", 20), None);
+        assert_eq!(parse_ranges("bytes=a-4", 20), None);
+        assert_eq!(parse_ranges("bytes=4", 20), None);
+        assert_eq!(parse_ranges("bytes=-", 20), None);
+    }
+
+    #[test]
+    fn ignores_more_than_max_ranges() {
+        let ranges = |count: usize| {
+            let range_set = vec!["0-0"; count].join(",");
+            parse_ranges(&format!("bytes={}", range_set), 20)
+        };
+        assert_eq!(ranges(MAX_RANGES), Some(vec![(0, 0)]));
+        assert_eq!(ranges(MAX_RANGES + 1), None);
+    }
+
+    #[test]
+    fn finds_no_satisfiable_range_past_the_end() {
+        assert_eq!(parse_ranges("bytes=20-30", 20), Some(vec![]));
+        assert_eq!(parse_ranges("bytes=-0", 20), Some(vec![]));
+        assert_eq!(parse_ranges("bytes=0-4", 0), Some(vec![]));
+    }
+
+    #[test]
+    fn if_range_compares_entity_tags_strongly() {
+        let strong = headers([(header::ETAG, "\"abc\"")]);
+        let weak = headers([(header::ETAG, "W/\"abc\"")]);
+        assert!(is_range_fresh(&HeaderMap::new(), &weak));
+        assert!(is_range_fresh(
+            &headers([(header::IF_RANGE, "\"abc\"")]),
+            &strong
+        ));
+        assert!(!is_range_fresh(
+            &headers([(header::IF_RANGE, "\"abd\"")]),
+            &strong
+        ));
+        assert!(!is_range_fresh(
+            &headers([(header::IF_RANGE, "W/\"abc\"")]),
+            &weak
+        ));
+        assert!(!is_range_fresh(
+            &headers([(header::IF_RANGE, "W/\"abc\"")]),
+            &strong
+        ));
+    }
+
+    #[test]
+    fn if_range_compares_dates_exactly() {
+        let last_modified = "Wed, 21 Oct 2015 07:28:00 GMT";
+        let response_headers = headers([(header::LAST_MODIFIED, last_modified)]);
+        assert!(is_range_fresh(
+            &headers([(header::IF_RANGE, last_modified)]),
+            &response_headers
+        ));
+        assert!(!is_range_fresh(
+            &headers([(header::IF_RANGE, "Wed, 21 Oct 2015 07:28:01 GMT")]),
+            &response_headers
+        ));
+        assert!(!is_range_fresh(
+            &headers([(header::IF_RANGE, last_modified)]),
+            &HeaderMap::new()
+        ));
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence_over_if_modified_since() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+        let response_headers = headers([(header::ETAG, "W/\"14-5627bd80\"")]);
+        let request_headers = headers([
+            (header::IF_NONE_MATCH, "\"other\""),
+            (header::IF_MODIFIED_SINCE, "Wed, 21 Oct 2015 07:28:00 GMT"),
+        ]);
+        assert!(!is_not_modified(
+            &request_headers,
+            &response_headers,
+            Some(modified)
+        ));
+        let request_headers = headers([
+            (header::IF_NONE_MATCH, "\"other\", \"14-5627bd80\""),
+            (header::IF_MODIFIED_SINCE, "Wed, 21 Oct 2015 07:27:59 GMT"),
+        ]);
+        assert!(is_not_modified(
+            &request_headers,
+            &response_headers,
+            Some(modified)
+        ));
+    }
+
+    #[test]
+    fn if_modified_since_compares_whole_seconds() {
+        let modified = UNIX_EPOCH + Duration::from_millis(1_445_412_480_750);
+        let modified = Some(truncate_to_secs(modified));
+        let at = |date| headers([(header::IF_MODIFIED_SINCE, date)]);
+        let response_headers = HeaderMap::new();
+        assert!(is_not_modified(
+            &at("Wed, 21 Oct 2015 07:28:00 GMT"),
+            &response_headers,
+            modified
+        ));
+        assert!(!is_not_modified(
+            &at("Wed, 21 Oct 2015 07:27:59 GMT"),
+            &response_headers,
+            modified
+        ));
+    }
+
+    #[test]
+    fn if_modified_since_ignores_future_dates() {
+        let modified = Some(truncate_to_secs(SystemTime::now()));
+        let future = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(3600));
+        let request_headers = headers([(header::IF_MODIFIED_SINCE, future.as_str())]);
+        assert!(!is_not_modified(
+            &request_headers,
+            &HeaderMap::new(),
+            modified
+        ));
+    }
+
+    #[tokio::test]
+    async fn responds_a_single_range() {
+        let http_response = serve_range("single", "bytes=2-5").await;
+        assert_eq!(http_response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            http_response.headers()[header::CONTENT_RANGE],
+            "bytes 2-5/20"
+        );
+        assert_eq!(http_response.headers()[header::CONTENT_LENGTH], "4");
+        let body = hyper::body::to_bytes(http_response.into_body()).await;
+        assert_eq!(body.unwrap(), "2345");
+    }
+
+    #[tokio::test]
+    async fn responds_unsatisfiable_ranges() {
+        let http_response = serve_range("unsatisfiable", "bytes=20-").await;
+        assert_eq!(http_response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(http_response.headers()[header::CONTENT_RANGE], "bytes */20");
+    }
+
+    #[tokio::test]
+    async fn responds_several_ranges_as_multipart_byteranges() {
+        let http_response = serve_range("multipart", "bytes=0-1,-2").await;
+        assert_eq!(http_response.status(), StatusCode::PARTIAL_CONTENT);
+        let content_type = http_response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap();
+        let content_length = http_response.headers()[header::CONTENT_LENGTH].clone();
+        let body = hyper::body::to_bytes(http_response.into_body())
+            .await
+            .unwrap();
+        let expected = format!(
+            "--{boundary}\r\ncontent-type: text/plain; charset=utf-8\r\n\
+             content-range: bytes 0-1/20\r\n\r\n01\r\n\
+             --{boundary}\r\ncontent-type: text/plain; charset=utf-8\r\n\
+             content-range: bytes 18-19/20\r\n\r\nij\r\n\
+             --{boundary}--\r\n"
+        );
+        assert_eq!(body, expected);
+        assert_eq!(content_length, expected.len().to_string());
+    }
+
+    #[tokio::test]
+    async fn ignores_ranges_of_weak_if_range() {
+        let http_request = hyper::Request::builder()
+            .header(header::RANGE, "bytes=0-1")
+            .header(header::IF_RANGE, "W/\"14-0\"")
+            .body(Body::empty())
+            .unwrap();
+        let http_response = static_files("weak-if-range")
+            .serve(&http_request, "file.txt")
+            .await
+            .http;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(http_response.into_body()).await;
+        assert_eq!(body.unwrap(), CONTENT);
+    }
+}
@@ -0,0 +1,68 @@
+use super::clock::Clock;
+use screw_components::dyn_fn::DFuture;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+struct TestClockState {
+    now: Instant,
+    sleepers: Vec<(Instant, oneshot::Sender<()>)>,
+}
+
+/// Clock only moving forward when `advance` is called, waking the sleeps it gets past. Clones
+/// share the same time, so a test keeps one while the middleware under test owns another.
+#[derive(Clone)]
+pub struct TestClock {
+    state: Arc<Mutex<TestClockState>>,
+}
+
+impl TestClock {
+    /// Starts the clock at the current instant.
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TestClockState {
+                now: Instant::now(),
+                sleepers: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.now += duration;
+        let now = state.now;
+        let (woken, sleepers) = std::mem::take(&mut state.sleepers)
+            .into_iter()
+            .partition(|(deadline, _)| *deadline <= now);
+        state.sleepers = sleepers;
+        drop(state);
+        for (_, sender) in woken {
+            let _ = sender.send(());
+        }
+    }
+}
+
+impl Default for TestClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> Instant {
+        self.state.lock().unwrap().now
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> DFuture<()> {
+        let mut state = self.state.lock().unwrap();
+        if deadline <= state.now {
+            return Box::pin(std::future::ready(()));
+        }
+        let (sender, receiver) = oneshot::channel();
+        state.sleepers.push((deadline, sender));
+        Box::pin(async move {
+            let _ = receiver.await;
+        })
+    }
+}
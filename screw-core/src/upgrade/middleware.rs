@@ -0,0 +1,208 @@
+use super::super::*;
+use super::{Upgrade, UpgradeContent, UpgradeOriginContent, UpgradeRequest, UpgradeResponse};
+use hyper::header::{CONNECTION, UPGRADE};
+use hyper::{Body, StatusCode, Version};
+use routing::middleware::Middleware;
+use routing::router::RoutedRequest;
+use screw_components::dyn_fn::DFnOnce;
+use tokio::task;
+
+pub fn is_http_version_11_or_larger(request: &hyper::Request<Body>) -> bool {
+    request.version() >= Version::HTTP_11
+}
+
+pub fn is_connection_header_upgrade(request: &hyper::Request<Body>) -> bool {
+    request
+        .headers()
+        .get(CONNECTION)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| {
+            h.split([' ', ','])
+                .any(|p| p.eq_ignore_ascii_case("Upgrade"))
+        })
+        .unwrap_or(false)
+}
+
+pub fn upgrade_header_protocols(request: &hyper::Request<Body>) -> Vec<&str> {
+    request
+        .headers()
+        .get(UPGRADE)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| {
+            h.split(',')
+                .map(|p| p.trim())
+                .filter(|p| !p.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub struct UpgradeMiddlewareConverter {
+    protocols: Vec<&'static str>,
+}
+
+impl UpgradeMiddlewareConverter {
+    pub fn with_protocol(protocol: &'static str) -> Self {
+        Self {
+            protocols: vec![protocol],
+        }
+    }
+
+    pub fn with_protocols<P: Into<Vec<&'static str>>>(protocols: P) -> Self {
+        Self {
+            protocols: protocols.into(),
+        }
+    }
+
+    fn negotiate_protocol(&self, http_request: &hyper::Request<Body>) -> Option<String> {
+        if !is_http_version_11_or_larger(http_request)
+            || !is_connection_header_upgrade(http_request)
+        {
+            return None;
+        }
+        upgrade_header_protocols(http_request)
+            .into_iter()
+            .find(|p| self.protocols.iter().any(|s| s.eq_ignore_ascii_case(p)))
+            .map(|p| p.to_owned())
+    }
+}
+
+#[async_trait]
+impl<Content, Extensions> Middleware<UpgradeRequest<Content, Extensions>, UpgradeResponse>
+    for UpgradeMiddlewareConverter
+where
+    Content: UpgradeContent<Extensions> + Send + 'static,
+    Extensions: Sync + Send + 'static,
+{
+    type Request = RoutedRequest<request::Request<Extensions>>;
+    type Response = response::Response;
+    async fn respond(
+        &self,
+        mut routed_request: RoutedRequest<request::Request<Extensions>>,
+        next: DFnOnce<UpgradeRequest<Content, Extensions>, UpgradeResponse>,
+    ) -> response::Response {
+        let protocol = match self.negotiate_protocol(&routed_request.origin.http) {
+            Some(protocol) => protocol,
+            None => {
+                return response::Response {
+                    http: hyper::Response::builder()
+                        .status(StatusCode::UPGRADE_REQUIRED)
+                        .header(CONNECTION, "Upgrade")
+                        .header(UPGRADE, self.protocols.join(", "))
+                        .body(response::ResponseBody::empty())
+                        .unwrap(),
+                }
+            }
+        };
+
+        let upgrade = Upgrade::with_request(&mut routed_request.origin.http, protocol.clone());
+
+        let request_content = Content::create(UpgradeOriginContent {
+            path: routed_request.path,
+            query: routed_request.query,
+            http_parts: routed_request.origin.http.into_parts().0,
+            remote_addr: routed_request.origin.remote_addr,
+            extensions: routed_request.origin.extensions,
+            protocol: protocol.clone(),
+        });
+
+        let upgrade_request = UpgradeRequest {
+            content: request_content,
+            upgrade,
+            _p_e: Default::default(),
+        };
+
+        let upgrade_response = next(upgrade_request).await;
+
+        task::spawn(upgrade_response.run());
+
+        response::Response {
+            http: hyper::Response::builder()
+                .status(StatusCode::SWITCHING_PROTOCOLS)
+                .header(CONNECTION, "Upgrade")
+                .header(UPGRADE, protocol)
+                .body(response::ResponseBody::empty())
+                .unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::server::conn::Http;
+    use hyper::service::service_fn;
+    use routing::router;
+    use std::convert::Infallible;
+    use std::sync::Arc;
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn hands_the_upgraded_connection_to_the_handler() {
+        let middleware = Arc::new(UpgradeMiddlewareConverter::with_protocol("echo"));
+        let router = Arc::new(
+            router::first::Router::with_fallback_handler(
+                move |routed_request: RoutedRequest<request::Request<()>>| {
+                    let middleware = middleware.clone();
+                    async move {
+                        middleware
+                            .respond(
+                                routed_request,
+                                Box::new(|upgrade_request: UpgradeRequest<(), ()>| {
+                                    Box::pin(async move {
+                                        let (_, upgrade) = upgrade_request.split();
+                                        upgrade.on(|mut upgraded| async move {
+                                            let mut buffer = [0; 4];
+                                            upgraded.read_exact(&mut buffer).await.unwrap();
+                                            upgraded.write_all(&buffer).await.unwrap();
+                                        })
+                                    })
+                                }),
+                            )
+                            .await
+                    }
+                },
+            )
+            .and_routes(|routes| routes),
+        );
+        let (client, server) = duplex(4096);
+        tokio::spawn(
+            Http::new()
+                .serve_connection(
+                    server,
+                    service_fn(move |http_request| {
+                        let router = router.clone();
+                        async move {
+                            let response = router
+                                .process(request::Request::with_http(http_request))
+                                .await;
+                            Ok::<_, Infallible>(response.http)
+                        }
+                    }),
+                )
+                .with_upgrades(),
+        );
+        let (mut send_request, connection) = hyper::client::conn::handshake(client).await.unwrap();
+        tokio::spawn(connection);
+
+        let http_response = send_request
+            .send_request(
+                hyper::Request::builder()
+                    .uri("/")
+                    .header(CONNECTION, "Upgrade")
+                    .header(UPGRADE, "echo")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(http_response.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(http_response.headers()[UPGRADE], "echo");
+
+        let mut upgraded = hyper::upgrade::on(http_response).await.unwrap();
+        upgraded.write_all(b"ping").await.unwrap();
+        let mut buffer = [0; 4];
+        upgraded.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"ping");
+    }
+}
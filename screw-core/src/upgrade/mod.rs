@@ -0,0 +1,7 @@
+mod middleware;
+mod request;
+mod response;
+
+pub use middleware::*;
+pub use request::*;
+pub use response::*;
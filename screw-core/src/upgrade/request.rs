@@ -0,0 +1,80 @@
+use super::super::*;
+use super::*;
+use hyper::http::request::Parts;
+use hyper::upgrade::OnUpgrade;
+use hyper::Body;
+use routing::actix::Path;
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+pub struct UpgradeOriginContent<Extensions> {
+    pub path: Path<String>,
+    pub query: HashMap<String, String>,
+    pub http_parts: Parts,
+    pub remote_addr: SocketAddr,
+    pub extensions: Arc<Extensions>,
+    pub protocol: String,
+}
+
+pub trait UpgradeContent<Extensions> {
+    fn create(origin_content: UpgradeOriginContent<Extensions>) -> Self;
+}
+
+impl<Extensions> UpgradeContent<Extensions> for () {
+    fn create(_origin_content: UpgradeOriginContent<Extensions>) -> Self {}
+}
+
+pub struct Upgrade {
+    pub(super) protocol: String,
+    pub(super) on_upgrade: OnUpgrade,
+}
+
+impl Upgrade {
+    /// Takes the pending upgrade of `http_request` to `protocol`, for middlewares of
+    /// specific protocols such as WebSocket which negotiate the upgrade themselves.
+    pub fn with_request<P: Into<String>>(
+        http_request: &mut hyper::Request<Body>,
+        protocol: P,
+    ) -> Self {
+        Self {
+            protocol: protocol.into(),
+            on_upgrade: hyper::upgrade::on(http_request),
+        }
+    }
+
+    pub fn protocol(&self) -> &str {
+        self.protocol.as_str()
+    }
+
+    pub fn on<F, U>(self, upgraded_fn: F) -> UpgradeResponse
+    where
+        F: FnOnce(hyper::upgrade::Upgraded) -> U + Send + 'static,
+        U: Future<Output = ()> + Send + 'static,
+    {
+        UpgradeResponse {
+            on_upgrade: self.on_upgrade,
+            upgraded_fn: Box::new(move |upgraded| Box::pin(upgraded_fn(upgraded))),
+        }
+    }
+}
+
+pub struct UpgradeRequest<Content, Extensions>
+where
+    Content: UpgradeContent<Extensions> + Send + 'static,
+{
+    pub(super) content: Content,
+    pub(super) upgrade: Upgrade,
+    pub(super) _p_e: PhantomData<Extensions>,
+}
+
+impl<Content, Extensions> UpgradeRequest<Content, Extensions>
+where
+    Content: UpgradeContent<Extensions> + Send + 'static,
+{
+    pub fn split(self) -> (Content, Upgrade) {
+        (self.content, self.upgrade)
+    }
+}
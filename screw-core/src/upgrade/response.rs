@@ -0,0 +1,18 @@
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use screw_components::dyn_fn::DFuture;
+
+pub struct UpgradeResponse {
+    pub(super) on_upgrade: OnUpgrade,
+    pub(super) upgraded_fn: Box<dyn FnOnce(Upgraded) -> DFuture<()> + Send + 'static>,
+}
+
+impl UpgradeResponse {
+    /// Waits for hyper to hand over the connection once `101 Switching Protocols` is written,
+    /// then runs the function given to `Upgrade::on` with it. Meant to be spawned, failing
+    /// when the connection closes before being upgraded.
+    pub async fn run(self) -> hyper::Result<()> {
+        let upgraded = self.on_upgrade.await?;
+        (self.upgraded_fn)(upgraded).await;
+        Ok(())
+    }
+}
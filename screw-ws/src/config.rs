@@ -0,0 +1,69 @@
+use screw_core::clock::Clock;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+
+#[derive(Clone, Debug, Default)]
+pub struct WebSocketMiddlewareConfig {
+    /// Protocol level settings passed to tungstenite. Defaults to tungstenite's own when `None`.
+    pub protocol: Option<WebSocketConfig>,
+    /// Time without any traffic after which the connection is failed with `TimedOut`.
+    /// Defaults to no timeout.
+    pub idle_timeout: Option<Duration>,
+    /// Interval at which the server sends a ping, keeping intermediaries from closing a quiet
+    /// connection. The pongs of the client count as traffic for `idle_timeout`, so combined
+    /// with a longer one it fails connections of clients that stopped responding. Defaults to
+    /// no pings.
+    pub ping_interval: Option<Duration>,
+    /// Maximum number of outgoing messages tungstenite queues while the connection is not
+    /// writable, overriding `max_send_queue` of `protocol` when set. It does not apply
+    /// backpressure: a message beyond it fails with `SendQueueFull`. Sending through the
+    /// `Sink` of the stream already waits for queued messages to be written before taking
+    /// another one, so only messages written without waiting can reach it. Defaults to
+    /// unlimited.
+    pub max_in_flight_messages: Option<usize>,
+    /// Subprotocols supported by the server, in order of preference. The first one offered by
+    /// the client in `Sec-WebSocket-Protocol` is selected. Defaults to none.
+    pub subprotocols: Vec<&'static str>,
+    /// Maximum number of upgraded connections open at once, beyond which upgrades are
+    /// rejected with `503 Service Unavailable`. Counted per middleware unless connections are
+    /// shared with `WebSocketMiddlewareConverter::and_connections`. Defaults to unlimited.
+    pub max_connections: Option<usize>,
+    /// Maximum number of upgraded connections open at once from a single remote IP, beyond
+    /// which upgrades are rejected with `503 Service Unavailable`. Counted like
+    /// `max_connections`. Defaults to unlimited.
+    pub max_connections_per_ip: Option<usize>,
+    /// Derives `Sec-WebSocket-Accept` from `Sec-WebSocket-Key`, e.g. for proxies or fuzzing
+    /// the handshake. Defaults to the RFC 6455 derivation when `None`.
+    pub derive_accept_key: Option<fn(&[u8]) -> String>,
+    /// Time source of `idle_timeout`, e.g. a `TestClock` in tests. Defaults to `TokioClock`
+    /// when `None`.
+    pub clock: Option<Arc<dyn Clock>>,
+}
+
+impl WebSocketMiddlewareConfig {
+    pub(super) fn protocol_config(&self) -> Option<WebSocketConfig> {
+        match self.max_in_flight_messages {
+            Some(max_in_flight_messages) => Some(WebSocketConfig {
+                max_send_queue: Some(max_in_flight_messages),
+                ..self.protocol.unwrap_or_default()
+            }),
+            None => self.protocol,
+        }
+    }
+}
+
+impl From<WebSocketConfig> for WebSocketMiddlewareConfig {
+    fn from(protocol: WebSocketConfig) -> Self {
+        Some(protocol).into()
+    }
+}
+
+impl From<Option<WebSocketConfig>> for WebSocketMiddlewareConfig {
+    fn from(protocol: Option<WebSocketConfig>) -> Self {
+        Self {
+            protocol,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Upgraded connections open at once, in total and per remote IP, counted against
+/// `max_connections` and `max_connections_per_ip` of `WebSocketMiddlewareConfig`. Every
+/// middleware counts its own connections unless given shared ones with
+/// `WebSocketMiddlewareConverter::and_connections`.
+#[derive(Default)]
+pub struct WebSocketConnections {
+    total: AtomicUsize,
+    per_ip: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl WebSocketConnections {
+    /// Number of connections open at once.
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Acquire)
+    }
+
+    /// Number of connections open at once from `ip`.
+    pub fn per_ip(&self, ip: IpAddr) -> usize {
+        self.per_ip
+            .lock()
+            .unwrap()
+            .get(&ip)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub(super) fn acquire(
+        self: &Arc<Self>,
+        ip: IpAddr,
+        max_connections: Option<usize>,
+        max_connections_per_ip: Option<usize>,
+    ) -> Option<WebSocketConnectionGuard> {
+        let max_connections = max_connections.unwrap_or(usize::MAX);
+        self.total
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |total| {
+                (total < max_connections).then_some(total + 1)
+            })
+            .ok()?;
+
+        let max_connections_per_ip = max_connections_per_ip.unwrap_or(usize::MAX);
+        let mut per_ip = self.per_ip.lock().unwrap();
+        let ip_connections = per_ip.entry(ip).or_default();
+        if *ip_connections >= max_connections_per_ip {
+            self.total.fetch_sub(1, Ordering::AcqRel);
+            return None;
+        }
+        *ip_connections += 1;
+
+        Some(WebSocketConnectionGuard {
+            connections: self.clone(),
+            ip,
+        })
+    }
+}
+
+pub(super) struct WebSocketConnectionGuard {
+    connections: Arc<WebSocketConnections>,
+    ip: IpAddr,
+}
+
+impl Drop for WebSocketConnectionGuard {
+    fn drop(&mut self) {
+        let mut per_ip = self.connections.per_ip.lock().unwrap();
+        if let Some(ip_connections) = per_ip.get_mut(&self.ip) {
+            *ip_connections -= 1;
+            if *ip_connections == 0 {
+                per_ip.remove(&self.ip);
+            }
+        }
+        self.connections.total.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const FIRST_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const SECOND_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    #[test]
+    fn acquires_up_to_the_total_limit() {
+        let connections = Arc::new(WebSocketConnections::default());
+        let first = connections.acquire(FIRST_IP, Some(2), None).unwrap();
+        let _second = connections.acquire(SECOND_IP, Some(2), None).unwrap();
+        assert!(connections.acquire(SECOND_IP, Some(2), None).is_none());
+        assert_eq!(connections.total(), 2);
+        drop(first);
+        assert_eq!(connections.total(), 1);
+        assert_eq!(connections.per_ip(FIRST_IP), 0);
+        assert!(connections.acquire(FIRST_IP, Some(2), None).is_some());
+    }
+
+    #[test]
+    fn acquires_up_to_the_per_ip_limit() {
+        let connections = Arc::new(WebSocketConnections::default());
+        let first = connections.acquire(FIRST_IP, None, Some(1)).unwrap();
+        assert!(connections.acquire(FIRST_IP, None, Some(1)).is_none());
+        let _second = connections.acquire(SECOND_IP, None, Some(1)).unwrap();
+        assert_eq!(connections.total(), 2);
+        assert_eq!(connections.per_ip(FIRST_IP), 1);
+        drop(first);
+        assert_eq!(connections.total(), 1);
+        assert!(connections.per_ip.lock().unwrap().get(&FIRST_IP).is_none());
+        assert!(connections.acquire(FIRST_IP, None, Some(1)).is_some());
+    }
+
+    #[test]
+    fn rejection_per_ip_gives_back_the_total() {
+        let connections = Arc::new(WebSocketConnections::default());
+        let _first = connections.acquire(FIRST_IP, Some(2), Some(1)).unwrap();
+        assert!(connections.acquire(FIRST_IP, Some(2), Some(1)).is_none());
+        assert_eq!(connections.total(), 1);
+        assert!(connections.acquire(SECOND_IP, Some(2), Some(1)).is_some());
+    }
+}
@@ -0,0 +1,90 @@
+use hyper::header::HeaderValue;
+use hyper::{Body, HeaderMap, Method, Uri};
+use screw_core::upgrade::{
+    is_connection_header_upgrade, is_http_version_11_or_larger, upgrade_header_protocols,
+};
+use std::net::SocketAddr;
+use tokio_tungstenite::tungstenite::error::ProtocolError;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+
+/// Upgrade request details kept for the stream converter after the upgrade.
+#[derive(Clone, Debug)]
+pub struct WebSocketHandshake {
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    pub remote_addr: SocketAddr,
+    pub subprotocol: Option<&'static str>,
+}
+
+fn is_get_method(request: &hyper::Request<Body>) -> bool {
+    request.method() == Method::GET
+}
+fn is_upgrade_header_web_socket(request: &hyper::Request<Body>) -> bool {
+    upgrade_header_protocols(request)
+        .iter()
+        .any(|p| p.eq_ignore_ascii_case("websocket"))
+}
+fn is_web_socket_version_header_13(request: &hyper::Request<Body>) -> bool {
+    request
+        .headers()
+        .get("Sec-WebSocket-Version")
+        .map(|h| h == "13")
+        .unwrap_or(false)
+}
+fn get_web_socket_key_header(request: &hyper::Request<Body>) -> Option<&HeaderValue> {
+    request.headers().get("Sec-WebSocket-Key")
+}
+
+/// Checks that `request` is a valid WebSocket upgrade request, returning the
+/// `Sec-WebSocket-Accept` value to respond with. Nothing is upgraded, so upgrade handling can
+/// be tested on requests built by hand.
+pub fn validate_handshake(request: &hyper::Request<Body>) -> Result<String, ProtocolError> {
+    validate_handshake_with(request, derive_accept_key)
+}
+
+/// Same as `validate_handshake`, deriving the accept value from `Sec-WebSocket-Key` with
+/// `derive_accept_key` instead of the RFC 6455 derivation.
+pub fn validate_handshake_with(
+    request: &hyper::Request<Body>,
+    derive_accept_key: fn(&[u8]) -> String,
+) -> Result<String, ProtocolError> {
+    if !is_get_method(request) {
+        return Err(ProtocolError::WrongHttpMethod);
+    }
+
+    if !is_http_version_11_or_larger(request) {
+        return Err(ProtocolError::WrongHttpVersion);
+    }
+
+    if !is_connection_header_upgrade(request) {
+        return Err(ProtocolError::MissingConnectionUpgradeHeader);
+    }
+
+    if !is_upgrade_header_web_socket(request) {
+        return Err(ProtocolError::MissingUpgradeWebSocketHeader);
+    }
+
+    if !is_web_socket_version_header_13(request) {
+        return Err(ProtocolError::MissingSecWebSocketVersionHeader);
+    }
+
+    let key = get_web_socket_key_header(request).ok_or(ProtocolError::MissingSecWebSocketKey)?;
+
+    Ok(derive_accept_key(key.as_bytes()))
+}
+
+/// Returns the first of `subprotocols` the client offers in `Sec-WebSocket-Protocol`.
+pub fn select_subprotocol(
+    request: &hyper::Request<Body>,
+    subprotocols: &[&'static str],
+) -> Option<&'static str> {
+    let offered: Vec<&str> = request
+        .headers()
+        .get_all("Sec-WebSocket-Protocol")
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .map(|p| p.trim())
+        .collect();
+    subprotocols.iter().find(|s| offered.contains(s)).copied()
+}
@@ -0,0 +1,375 @@
+use hyper::upgrade::Upgraded;
+use screw_components::dyn_fn::DFuture;
+use screw_core::clock::Clock;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Instant;
+
+struct IdleTimeout {
+    duration: Duration,
+    clock: Arc<dyn Clock>,
+    deadline: Instant,
+    // Sleeps until a past deadline, checked against `deadline` once it elapses
+    sleep: DFuture<()>,
+}
+
+/// Unmasked ping frame without payload, as sent by a server.
+const PING_FRAME: [u8; 2] = [0x89, 0x00];
+
+struct PingInterval {
+    duration: Duration,
+    clock: Arc<dyn Clock>,
+    sleep: DFuture<()>,
+    // Bytes of `PING_FRAME` written so far, from the moment the interval elapsed
+    written: Option<usize>,
+}
+
+/// Follows the frames tungstenite writes, so pings are only written between two of them.
+#[derive(Default)]
+struct OutgoingFrames {
+    header: [u8; 14],
+    header_len: usize,
+    payload_left: u64,
+}
+
+impl OutgoingFrames {
+    fn is_between_frames(&self) -> bool {
+        self.header_len == 0 && self.payload_left == 0
+    }
+
+    fn advance(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.payload_left > 0 {
+                let skipped = bytes.len().min(self.payload_left as usize);
+                self.payload_left -= skipped as u64;
+                bytes = &bytes[skipped..];
+                continue;
+            }
+            self.header[self.header_len] = bytes[0];
+            self.header_len += 1;
+            bytes = &bytes[1..];
+            if let Some(payload_len) = self.payload_len() {
+                self.header_len = 0;
+                self.payload_left = payload_len;
+            }
+        }
+    }
+
+    /// Payload length of the frame once its header is complete.
+    fn payload_len(&self) -> Option<u64> {
+        if self.header_len < 2 {
+            return None;
+        }
+        let length_len = match self.header[1] & 0x7f {
+            126 => 2,
+            127 => 8,
+            _ => 0,
+        };
+        let mask_len = if self.header[1] & 0x80 != 0 { 4 } else { 0 };
+        if self.header_len < 2 + length_len + mask_len {
+            return None;
+        }
+        Some(match length_len {
+            0 => (self.header[1] & 0x7f) as u64,
+            _ => self.header[2..2 + length_len]
+                .iter()
+                .fold(0, |payload_len, byte| payload_len << 8 | *byte as u64),
+        })
+    }
+}
+
+pub struct WebSocketIo {
+    upgraded: Upgraded,
+    idle_timeout: Option<IdleTimeout>,
+    ping_interval: Option<PingInterval>,
+    outgoing_frames: OutgoingFrames,
+}
+
+impl WebSocketIo {
+    pub(super) fn new(
+        upgraded: Upgraded,
+        idle_timeout: Option<Duration>,
+        ping_interval: Option<Duration>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            upgraded,
+            idle_timeout: idle_timeout.map(|duration| {
+                let deadline = clock.now() + duration;
+                IdleTimeout {
+                    duration,
+                    sleep: clock.sleep_until(deadline),
+                    clock: clock.clone(),
+                    deadline,
+                }
+            }),
+            ping_interval: ping_interval.map(|duration| PingInterval {
+                duration,
+                sleep: clock.sleep_until(clock.now() + duration),
+                clock,
+                written: None,
+            }),
+            outgoing_frames: OutgoingFrames::default(),
+        }
+    }
+
+    /// Writes a ping once the ping interval elapsed and tungstenite is between frames, only
+    /// pending while a started ping cannot be written any further.
+    fn poll_ping(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let ping_interval = match &mut self.ping_interval {
+            Some(ping_interval) => ping_interval,
+            None => return Poll::Ready(Ok(())),
+        };
+        if ping_interval.written.is_none() {
+            if !self.outgoing_frames.is_between_frames()
+                || ping_interval.sleep.as_mut().poll(cx).is_pending()
+            {
+                return Poll::Ready(Ok(()));
+            }
+            ping_interval.written = Some(0);
+        }
+        while let Some(written) = ping_interval.written {
+            if written == PING_FRAME.len() {
+                ready!(Pin::new(&mut self.upgraded).poll_flush(cx))?;
+                ping_interval.written = None;
+                let deadline = ping_interval.clock.now() + ping_interval.duration;
+                ping_interval.sleep = ping_interval.clock.sleep_until(deadline);
+                break;
+            }
+            let written_now =
+                ready!(Pin::new(&mut self.upgraded).poll_write(cx, &PING_FRAME[written..]))?;
+            if written_now == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            ping_interval.written = Some(written + written_now);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_idle<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<T>>,
+    ) -> Poll<io::Result<T>> {
+        let idle_timeout = match &mut self.idle_timeout {
+            Some(idle_timeout) => idle_timeout,
+            None => return poll,
+        };
+        match poll {
+            Poll::Ready(result) => {
+                idle_timeout.deadline = idle_timeout.clock.now() + idle_timeout.duration;
+                Poll::Ready(result)
+            }
+            Poll::Pending => loop {
+                if idle_timeout.sleep.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                // Never polled again once elapsed, so a read after the timeout fails again
+                idle_timeout.sleep = idle_timeout.clock.sleep_until(idle_timeout.deadline);
+                if idle_timeout.clock.now() >= idle_timeout.deadline {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "WebSocket connection idle timeout",
+                    )));
+                }
+            },
+        }
+    }
+}
+
+impl AsyncRead for WebSocketIo {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if let Poll::Ready(Err(error)) = self.poll_ping(cx) {
+            return Poll::Ready(Err(error));
+        }
+        let poll = Pin::new(&mut self.upgraded).poll_read(cx, buf);
+        self.poll_idle(cx, poll)
+    }
+}
+
+impl AsyncWrite for WebSocketIo {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_ping(cx))?;
+        let poll = Pin::new(&mut self.upgraded).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            self.outgoing_frames.advance(&buf[..written]);
+        }
+        self.poll_idle(cx, poll)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.upgraded).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.upgraded).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::{upgraded_pair, web_socket_pair};
+    use super::*;
+    use futures_util::StreamExt;
+    use screw_core::test_support::TestClock;
+    use tokio::sync::mpsc;
+    use tokio_tungstenite::tungstenite::{Error, Message};
+    use tokio_tungstenite::WebSocketStream;
+
+    /// Whether `stream` yields a message before the runtime is left idle for a moment.
+    async fn next_message<S>(stream: &mut WebSocketStream<S>) -> Option<Message>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        tokio::time::timeout(Duration::from_millis(100), stream.next())
+            .await
+            .ok()
+            .map(|message| message.unwrap().unwrap())
+    }
+
+    #[test]
+    fn outgoing_frames_follow_headers_and_payloads() {
+        let mut outgoing_frames = OutgoingFrames::default();
+        let frames: [&[u8]; 4] = [
+            &[0x81, 0x03, b'a', b'b', b'c'],
+            &[0x8a, 0x00],
+            &[0x82, 126, 0x01, 0x00],
+            &[0x82, 127, 0, 0, 0, 0, 0, 0x01, 0x00, 0x00],
+        ];
+        let payload_lens = [0, 0, 0x100, 0x10000];
+        for (frame, payload_len) in frames.into_iter().zip(payload_lens) {
+            assert!(outgoing_frames.is_between_frames());
+            for byte in frame {
+                outgoing_frames.advance(std::slice::from_ref(byte));
+            }
+            for chunk in vec![0; payload_len].chunks(1000) {
+                assert!(!outgoing_frames.is_between_frames());
+                outgoing_frames.advance(chunk);
+            }
+        }
+        assert!(outgoing_frames.is_between_frames());
+    }
+
+    #[tokio::test]
+    async fn pings_at_interval() {
+        let clock = TestClock::new();
+        let (mut client, mut server) =
+            web_socket_pair(None, Some(Duration::from_secs(10)), &clock).await;
+        tokio::spawn(async move { while let Some(Ok(_)) = server.next().await {} });
+
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(next_message(&mut client).await, None);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            next_message(&mut client).await,
+            Some(Message::Ping(Vec::new()))
+        );
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(
+            next_message(&mut client).await,
+            Some(Message::Ping(Vec::new()))
+        );
+    }
+
+    #[tokio::test]
+    async fn pings_between_frames() {
+        let clock = TestClock::new();
+        let (mut client, mut server) =
+            web_socket_pair(None, Some(Duration::from_secs(10)), &clock).await;
+        let text = "a".repeat(100_000);
+        let server_text = text.clone();
+        let server_clock = clock.clone();
+        tokio::spawn(async move {
+            let send = futures_util::SinkExt::send(&mut server, Message::Text(server_text));
+            tokio::pin!(send);
+            // Elapses the interval while the message is partly written
+            assert!(futures_util::poll!(send.as_mut()).is_pending());
+            server_clock.advance(Duration::from_secs(10));
+            send.await.unwrap();
+            while let Some(Ok(_)) = server.next().await {}
+        });
+
+        assert_eq!(next_message(&mut client).await, Some(Message::Text(text)));
+        assert_eq!(
+            next_message(&mut client).await,
+            Some(Message::Ping(Vec::new()))
+        );
+    }
+
+    /// Whether `receiver` receives a read result before the runtime is left idle for a moment.
+    async fn next_result(
+        receiver: &mut mpsc::UnboundedReceiver<Result<Message, io::ErrorKind>>,
+    ) -> Option<Result<Message, io::ErrorKind>> {
+        tokio::time::timeout(Duration::from_millis(100), receiver.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    #[tokio::test]
+    async fn times_out_when_idle() {
+        let clock = TestClock::new();
+        let (mut client, mut server) =
+            web_socket_pair(Some(Duration::from_secs(10)), None, &clock).await;
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(result) = server.next().await {
+                let _ = sender.send(result.map_err(|error| match error {
+                    Error::Io(error) => error.kind(),
+                    _ => io::ErrorKind::Other,
+                }));
+            }
+        });
+        clock.advance(Duration::from_secs(9));
+        futures_util::SinkExt::send(&mut client, Message::Text("a".to_owned()))
+            .await
+            .unwrap();
+        assert_eq!(
+            next_result(&mut receiver).await,
+            Some(Ok(Message::Text("a".to_owned())))
+        );
+
+        // Past the deadline the connection started with, but not the one the message moved
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(next_result(&mut receiver).await, None);
+
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(
+            next_result(&mut receiver).await,
+            Some(Err(io::ErrorKind::TimedOut))
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_keep_failing_after_idle_timeout() {
+        let clock = TestClock::new();
+        let (_client_upgraded, server_upgraded) = upgraded_pair().await;
+        let mut io = WebSocketIo::new(
+            server_upgraded,
+            Some(Duration::from_secs(10)),
+            None,
+            Arc::new(clock.clone()),
+        );
+        let mut buf = [0; 16];
+
+        clock.advance(Duration::from_secs(10));
+        for _ in 0..2 {
+            let error = tokio::io::AsyncReadExt::read(&mut io, &mut buf)
+                .await
+                .unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        }
+    }
+}
@@ -1,12 +1,28 @@
+//! Internal events are emitted through `tracing` under the `screw::ws` target, carrying the
+//! peer of a connection as `remote_addr` and failures as `error`: rejected handshakes,
+//! connection limits being reached, upgrades declined by handlers, failed upgrades and closed
+//! connections.
+
+mod config;
+mod connections;
+mod handshake;
+mod io;
 mod middleware;
 mod request;
 mod response;
 mod stream_converter;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 
+pub use config::*;
+pub use connections::WebSocketConnections;
+pub use handshake::*;
+pub use io::*;
 pub use middleware::*;
 pub use request::*;
 pub use response::*;
 pub use stream_converter::*;
+pub use tokio_tungstenite;
 
 #[macro_use]
 extern crate async_trait;
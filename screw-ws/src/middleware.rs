@@ -1,87 +1,29 @@
+use super::connections::WebSocketConnections;
+use super::response::WebSocketResponseKind;
 use super::*;
-use futures_util::{FutureExt, TryFutureExt};
-use hyper::header::HeaderValue;
-use hyper::{upgrade, Body, Method, StatusCode, Version};
+use futures_util::FutureExt;
+use hyper::{Body, StatusCode};
 use screw_components::dyn_fn::DFnOnce;
+use screw_core::clock::TokioClock;
 use screw_core::request::Request;
-use screw_core::response::Response;
+use screw_core::response::{Response, ResponseBody};
 use screw_core::routing::middleware::Middleware;
 use screw_core::routing::router::RoutedRequest;
+use screw_core::upgrade::Upgrade;
 use std::sync::Arc;
 use tokio::task;
 use tokio_tungstenite::tungstenite::error::ProtocolError;
 use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
-use tokio_tungstenite::tungstenite::protocol::{Role, WebSocketConfig};
+use tokio_tungstenite::tungstenite::protocol::Role;
 use tokio_tungstenite::WebSocketStream;
 
-fn is_get_method(request: &hyper::Request<Body>) -> bool {
-    request.method() == Method::GET
-}
-fn is_http_version_11_or_larger(request: &hyper::Request<Body>) -> bool {
-    request.version() >= Version::HTTP_11
-}
-fn is_connection_header_upgrade(request: &hyper::Request<Body>) -> bool {
-    request
-        .headers()
-        .get("Connection")
-        .and_then(|h| h.to_str().ok())
-        .map(|h| {
-            h.split(|c| c == ' ' || c == ',')
-                .any(|p| p.eq_ignore_ascii_case("Upgrade"))
-        })
-        .unwrap_or(false)
-}
-fn is_upgrade_header_web_socket(request: &hyper::Request<Body>) -> bool {
-    request
-        .headers()
-        .get("Upgrade")
-        .and_then(|h| h.to_str().ok())
-        .map(|h| h.eq_ignore_ascii_case("websocket"))
-        .unwrap_or(false)
-}
-fn is_web_socket_version_header_13(request: &hyper::Request<Body>) -> bool {
-    request
-        .headers()
-        .get("Sec-WebSocket-Version")
-        .map(|h| h == "13")
-        .unwrap_or(false)
-}
-fn get_web_socket_key_header(request: &hyper::Request<Body>) -> Option<&HeaderValue> {
-    request.headers().get("Sec-WebSocket-Key")
-}
-
 fn try_upgradable(
     http_request: &mut hyper::Request<Body>,
+    derive_accept_key: fn(&[u8]) -> String,
 ) -> Result<WebSocketUpgradable, ProtocolError> {
-    if !is_get_method(http_request) {
-        return Err(ProtocolError::WrongHttpMethod);
-    }
-
-    if !is_http_version_11_or_larger(http_request) {
-        return Err(ProtocolError::WrongHttpVersion);
-    }
-
-    if !is_connection_header_upgrade(http_request) {
-        return Err(ProtocolError::MissingConnectionUpgradeHeader);
-    }
-
-    if !is_upgrade_header_web_socket(http_request) {
-        return Err(ProtocolError::MissingUpgradeWebSocketHeader);
-    }
-
-    if !is_web_socket_version_header_13(http_request) {
-        return Err(ProtocolError::MissingSecWebSocketVersionHeader);
-    }
-
-    let key = derive_accept_key(
-        get_web_socket_key_header(http_request)
-            .ok_or(ProtocolError::MissingSecWebSocketKey)?
-            .as_bytes(),
-    );
-
-    let on_upgrade = upgrade::on(http_request);
-
-    Ok(WebSocketUpgradable { on_upgrade, key })
+    let key = validate_handshake_with(http_request, derive_accept_key)?;
+    let upgrade = Upgrade::with_request(http_request, "websocket");
+    Ok(WebSocketUpgradable { upgrade, key })
 }
 
 pub struct WebSocketMiddlewareConverter<StreamConverter>
@@ -89,7 +31,8 @@ where
     StreamConverter: Sync + Send + 'static,
 {
     stream_converter: Arc<StreamConverter>,
-    config: Option<WebSocketConfig>,
+    config: WebSocketMiddlewareConfig,
+    connections: Arc<WebSocketConnections>,
 }
 
 impl<StreamConverter> WebSocketMiddlewareConverter<StreamConverter>
@@ -99,11 +42,20 @@ where
     pub fn with_stream_converter(stream_converter: StreamConverter) -> Self {
         Self {
             stream_converter: Arc::new(stream_converter),
-            config: None,
+            config: Default::default(),
+            connections: Default::default(),
         }
     }
-    pub fn and_config(mut self, config: Option<WebSocketConfig>) -> Self {
-        self.config = config;
+    pub fn and_config<C: Into<WebSocketMiddlewareConfig>>(mut self, config: C) -> Self {
+        self.config = config.into();
+        self
+    }
+
+    /// Counts connections in `connections`, so routes sharing them share the
+    /// `max_connections` and `max_connections_per_ip` limits, which otherwise apply to the
+    /// connections of this middleware only.
+    pub fn and_connections(mut self, connections: Arc<WebSocketConnections>) -> Self {
+        self.connections = connections;
         self
     }
 }
@@ -125,22 +77,56 @@ where
         mut routed_request: RoutedRequest<Request<Extensions>>,
         next: DFnOnce<WebSocketRequest<Content, Stream, Extensions>, WebSocketResponse>,
     ) -> Response {
-        let http_response = match try_upgradable(&mut routed_request.origin.http) {
+        let derive_accept_key = self.config.derive_accept_key.unwrap_or(derive_accept_key);
+        let http_response = match try_upgradable(&mut routed_request.origin.http, derive_accept_key)
+        {
             Ok(upgradable) => {
+                let connection_guard = match self.connections.acquire(
+                    routed_request.origin.remote_addr.ip(),
+                    self.config.max_connections,
+                    self.config.max_connections_per_ip,
+                ) {
+                    Some(connection_guard) => connection_guard,
+                    None => {
+                        tracing::warn!(
+                            target: "screw::ws",
+                            remote_addr = %routed_request.origin.remote_addr,
+                            "connection limit reached"
+                        );
+                        return Response {
+                            http: hyper::Response::builder()
+                                .status(StatusCode::SERVICE_UNAVAILABLE)
+                                .body(ResponseBody::empty())
+                                .unwrap(),
+                        };
+                    }
+                };
+                let subprotocol =
+                    select_subprotocol(&routed_request.origin.http, &self.config.subprotocols);
+                let handshake = WebSocketHandshake {
+                    uri: routed_request.origin.http.uri().clone(),
+                    headers: routed_request.origin.http.headers().clone(),
+                    remote_addr: routed_request.origin.remote_addr,
+                    subprotocol,
+                };
                 let request_content = Content::create(WebSocketOriginContent {
                     path: routed_request.path,
                     query: routed_request.query,
                     http_parts: routed_request.origin.http.into_parts().0,
                     remote_addr: routed_request.origin.remote_addr,
                     extensions: routed_request.origin.extensions,
+                    subprotocol,
                 });
 
                 let stream_converter = self.stream_converter.clone();
                 let request_upgrade = WebSocketUpgrade {
                     convert_stream_fn: Box::new(move |generic_stream| {
                         let stream_converter = stream_converter.clone();
+                        let handshake = handshake.clone();
                         Box::pin(async move {
-                            let stream = stream_converter.convert_stream(generic_stream).await;
+                            let stream = stream_converter
+                                .convert_stream(handshake, generic_stream)
+                                .await;
                             stream
                         })
                     }),
@@ -152,38 +138,173 @@ where
                     _p_e: Default::default(),
                 };
 
-                let ws_response = next(ws_request).await;
+                let upgraded_fn = match next(ws_request).await.kind {
+                    WebSocketResponseKind::Upgrade(upgraded_fn) => upgraded_fn,
+                    WebSocketResponseKind::Reject(response) => {
+                        tracing::debug!(
+                            target: "screw::ws",
+                            remote_addr = %routed_request.origin.remote_addr,
+                            status = response.http.status().as_u16(),
+                            "upgrade declined"
+                        );
+                        return response;
+                    }
+                };
 
-                let config = self.config;
-                let future = upgradable
-                    .on_upgrade
-                    .and_then(move |upgraded| {
-                        WebSocketStream::from_raw_socket(upgraded, Role::Server, config).map(Ok)
-                    })
-                    .and_then(move |stream| (ws_response.upgraded_fn)(stream).map(Ok));
+                let protocol_config = self.config.protocol_config();
+                let idle_timeout = self.config.idle_timeout;
+                let ping_interval = self.config.ping_interval;
+                let clock = self
+                    .config
+                    .clock
+                    .clone()
+                    .unwrap_or_else(|| Arc::new(TokioClock));
+                let remote_addr = routed_request.origin.remote_addr;
+                let upgrade_response = upgradable.upgrade.on(move |upgraded| async move {
+                    let io = WebSocketIo::new(upgraded, idle_timeout, ping_interval, clock);
+                    let stream =
+                        WebSocketStream::from_raw_socket(io, Role::Server, protocol_config).await;
+                    upgraded_fn(stream).await;
+                });
+                let future = upgrade_response.run().map(move |result| {
+                    drop(connection_guard);
+                    match result {
+                        Ok(()) => {
+                            tracing::debug!(target: "screw::ws", %remote_addr, "connection closed")
+                        }
+                        Err(error) => {
+                            tracing::debug!(target: "screw::ws", %remote_addr, %error, "upgrade failed")
+                        }
+                    }
+                });
 
                 task::spawn(future);
 
-                hyper::Response::builder()
+                let mut response_builder = hyper::Response::builder()
                     .status(StatusCode::SWITCHING_PROTOCOLS)
                     .header("Connection", "Upgrade")
                     .header("Upgrade", "websocket")
-                    .header("Sec-WebSocket-Accept", upgradable.key)
-                    .body(Body::empty())
-                    .unwrap()
+                    .header("Sec-WebSocket-Accept", upgradable.key);
+                if let Some(subprotocol) = subprotocol {
+                    response_builder =
+                        response_builder.header("Sec-WebSocket-Protocol", subprotocol);
+                }
+                response_builder.body(ResponseBody::empty()).unwrap()
             }
-            Err(protocol_error) => match protocol_error {
-                ProtocolError::WrongHttpMethod => {
-                    panic!("incorrect method for WebSocket, should be GET")
+            Err(protocol_error) => {
+                tracing::debug!(
+                    target: "screw::ws",
+                    remote_addr = %routed_request.origin.remote_addr,
+                    error = %protocol_error,
+                    "handshake rejected"
+                );
+                match protocol_error {
+                    ProtocolError::WrongHttpMethod => {
+                        panic!("incorrect method for WebSocket, should be GET")
+                    }
+                    _ => hyper::Response::builder()
+                        .status(StatusCode::BAD_REQUEST)
+                        .body(ResponseBody::empty())
+                        .unwrap(),
                 }
-                _ => hyper::Response::builder()
-                    .status(StatusCode::BAD_REQUEST)
-                    .body(Body::empty())
-                    .unwrap(),
-            },
+            }
         };
         Response {
             http: http_response,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Method;
+    use screw_core::routing::route::first::Route;
+    use screw_core::routing::router::first::Router;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    struct UnitStreamConverter;
+
+    #[async_trait]
+    impl WebSocketStreamConverter<()> for UnitStreamConverter {
+        async fn convert_stream(
+            &self,
+            _handshake: WebSocketHandshake,
+            _stream: WebSocketStream<WebSocketIo>,
+        ) {
+        }
+    }
+
+    async fn handler(request: WebSocketRequest<(), (), ()>) -> WebSocketResponse {
+        let (_, upgrade) = request.split();
+        upgrade.on(|_| async {})
+    }
+
+    async fn fallback_handler(_request: RoutedRequest<Request<()>>) -> Response {
+        Response {
+            http: hyper::Response::new(ResponseBody::empty()),
+        }
+    }
+
+    /// Status of a WebSocket handshake to `path` of a router whose `/first` and `/second`
+    /// routes share `connections`, allowing a single connection per IP.
+    async fn handshake_status(connections: &Arc<WebSocketConnections>, path: &str) -> StatusCode {
+        let converter = || {
+            WebSocketMiddlewareConverter::with_stream_converter(UnitStreamConverter)
+                .and_config(WebSocketMiddlewareConfig {
+                    max_connections_per_ip: Some(1),
+                    ..Default::default()
+                })
+                .and_connections(connections.clone())
+        };
+        let router = Router::with_fallback_handler(fallback_handler).and_routes(|routes| {
+            routes
+                .middleware(converter(), |routes| {
+                    routes.route(
+                        Route::with_methods([&Method::GET])
+                            .and_path("/first")
+                            .and_handler(handler),
+                    )
+                })
+                .middleware(converter(), |routes| {
+                    routes.route(
+                        Route::with_methods([&Method::GET])
+                            .and_path("/second")
+                            .and_handler(handler),
+                    )
+                })
+        });
+        let http_request = hyper::Request::builder()
+            .uri(path)
+            .header("Connection", "Upgrade")
+            .header("Upgrade", "websocket")
+            .header("Sec-WebSocket-Version", "13")
+            .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .body(Body::empty())
+            .unwrap();
+        router
+            .process(Request::with_http(http_request))
+            .await
+            .http
+            .status()
+    }
+
+    #[tokio::test]
+    async fn shared_connections_limit_every_route() {
+        let connections = Arc::new(WebSocketConnections::default());
+        let guard = connections
+            .acquire(IpAddr::V4(Ipv4Addr::LOCALHOST), None, None)
+            .unwrap();
+        for path in ["/first", "/second"] {
+            assert_eq!(
+                handshake_status(&connections, path).await,
+                StatusCode::SERVICE_UNAVAILABLE
+            );
+        }
+        drop(guard);
+        assert_eq!(
+            handshake_status(&connections, "/second").await,
+            StatusCode::SWITCHING_PROTOCOLS
+        );
+    }
+}
@@ -1,8 +1,9 @@
+use super::response::WebSocketResponseKind;
 use super::*;
 use hyper::http::request::Parts;
-use hyper::upgrade::{OnUpgrade, Upgraded};
 use screw_components::dyn_fn::DFn;
 use screw_core::routing::actix::Path;
+use screw_core::upgrade::Upgrade;
 use std::collections::HashMap;
 use std::future::Future;
 use std::marker::PhantomData;
@@ -16,6 +17,7 @@ pub struct WebSocketOriginContent<Extensions> {
     pub http_parts: Parts,
     pub remote_addr: SocketAddr,
     pub extensions: Arc<Extensions>,
+    pub subprotocol: Option<&'static str>,
 }
 
 pub trait WebSocketContent<Extensions> {
@@ -27,7 +29,7 @@ impl<Extensions> WebSocketContent<Extensions> for () {
 }
 
 pub(super) struct WebSocketUpgradable {
-    pub(super) on_upgrade: OnUpgrade,
+    pub(super) upgrade: Upgrade,
     pub(super) key: String,
 }
 
@@ -35,7 +37,7 @@ pub struct WebSocketUpgrade<Stream>
 where
     Stream: Send + Sync + 'static,
 {
-    pub(super) convert_stream_fn: DFn<WebSocketStream<Upgraded>, Stream>,
+    pub(super) convert_stream_fn: DFn<WebSocketStream<WebSocketIo>, Stream>,
 }
 
 impl<Stream> WebSocketUpgrade<Stream>
@@ -49,13 +51,13 @@ where
     {
         let convert_stream_fn = Arc::new(self.convert_stream_fn);
         WebSocketResponse {
-            upgraded_fn: Box::new(move |generic_stream| {
+            kind: WebSocketResponseKind::Upgrade(Box::new(move |generic_stream| {
                 let convert_stream_fn = convert_stream_fn.clone();
                 Box::pin(async move {
                     let stream = convert_stream_fn(generic_stream).await;
                     upgraded_fn(stream).await;
                 })
-            }),
+            })),
         }
     }
 }
@@ -1,7 +1,31 @@
-use hyper::upgrade::Upgraded;
+use super::WebSocketIo;
 use screw_components::dyn_fn::DFnOnce;
+use screw_core::response::Response;
 use tokio_tungstenite::WebSocketStream;
 
+pub(super) enum WebSocketResponseKind {
+    Upgrade(DFnOnce<WebSocketStream<WebSocketIo>, ()>),
+    Reject(Response),
+}
+
+/// Either upgrades the connection, see `WebSocketUpgrade::on`, or declines the upgrade with
+/// an HTTP response, see `WebSocketResponse::rejected`.
 pub struct WebSocketResponse {
-    pub(super) upgraded_fn: DFnOnce<WebSocketStream<Upgraded>, ()>,
+    pub(super) kind: WebSocketResponseKind,
+}
+
+impl WebSocketResponse {
+    /// Declines the upgrade, responding `response` instead of `101 Switching Protocols`, e.g.
+    /// `401 Unauthorized` after checking the credentials of the request content.
+    pub fn rejected(response: Response) -> Self {
+        Self {
+            kind: WebSocketResponseKind::Reject(response),
+        }
+    }
+}
+
+impl<E: Into<Response>> From<Result<WebSocketResponse, E>> for WebSocketResponse {
+    fn from(result: Result<WebSocketResponse, E>) -> Self {
+        result.unwrap_or_else(|rejection| Self::rejected(rejection.into()))
+    }
 }
@@ -1,7 +1,11 @@
-use hyper::upgrade::Upgraded;
+use super::{WebSocketHandshake, WebSocketIo};
 use tokio_tungstenite::WebSocketStream;
 
 #[async_trait]
 pub trait WebSocketStreamConverter<Stream> {
-    async fn convert_stream(&self, stream: WebSocketStream<Upgraded>) -> Stream;
+    async fn convert_stream(
+        &self,
+        handshake: WebSocketHandshake,
+        stream: WebSocketStream<WebSocketIo>,
+    ) -> Stream;
 }
@@ -0,0 +1,79 @@
+use super::WebSocketIo;
+use hyper::client::conn as client_conn;
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::upgrade::Upgraded;
+use hyper::{header, Body, StatusCode};
+use screw_core::test_support::TestClock;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::duplex;
+use tokio::sync::oneshot;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::WebSocketStream;
+
+/// Client and server ends of an HTTP/1.1 connection upgraded over an in-memory stream.
+pub async fn upgraded_pair() -> (Upgraded, Upgraded) {
+    let (client_io, server_io) = duplex(4096);
+    let (server_sender, server_receiver) = oneshot::channel();
+    let mut server_sender = Some(server_sender);
+    tokio::spawn(
+        Http::new()
+            .serve_connection(
+                server_io,
+                service_fn(move |mut http_request: hyper::Request<Body>| {
+                    let on_upgrade = hyper::upgrade::on(&mut http_request);
+                    let server_sender = server_sender.take().unwrap();
+                    tokio::spawn(async move {
+                        let _ = server_sender.send(on_upgrade.await.unwrap());
+                    });
+                    async {
+                        Ok::<_, Infallible>(
+                            hyper::Response::builder()
+                                .status(StatusCode::SWITCHING_PROTOCOLS)
+                                .header(header::CONNECTION, "upgrade")
+                                .header(header::UPGRADE, "test")
+                                .body(Body::empty())
+                                .unwrap(),
+                        )
+                    }
+                }),
+            )
+            .with_upgrades(),
+    );
+    let (mut send_request, connection) = client_conn::handshake(client_io).await.unwrap();
+    tokio::spawn(connection);
+    let http_response = send_request
+        .send_request(
+            hyper::Request::builder()
+                .header(header::CONNECTION, "upgrade")
+                .header(header::UPGRADE, "test")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let client_upgraded = hyper::upgrade::on(http_response).await.unwrap();
+    (client_upgraded, server_receiver.await.unwrap())
+}
+
+/// Client and server ends of a WebSocket connection, the server reading and writing through
+/// `WebSocketIo` driven by `clock` as the middleware would set it up.
+pub async fn web_socket_pair(
+    idle_timeout: Option<Duration>,
+    ping_interval: Option<Duration>,
+    clock: &TestClock,
+) -> (WebSocketStream<Upgraded>, WebSocketStream<WebSocketIo>) {
+    let (client_upgraded, server_upgraded) = upgraded_pair().await;
+    let io = WebSocketIo::new(
+        server_upgraded,
+        idle_timeout,
+        ping_interval,
+        Arc::new(clock.clone()),
+    );
+    (
+        WebSocketStream::from_raw_socket(client_upgraded, Role::Client, None).await,
+        WebSocketStream::from_raw_socket(io, Role::Server, None).await,
+    )
+}